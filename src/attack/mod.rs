@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::rule::Rule;
+
+// NOTE: This is an offline copy of the Enterprise matrix so that hunts do not need network access,
+// it only needs updating when new techniques are added to ATT&CK.
+const TECHNIQUES: &str = include_str!("techniques.tsv");
+
+const ATTACK_VERSION: &str = "14";
+const LAYER_VERSION: &str = "4.5";
+const NAVIGATOR_VERSION: &str = "4.9.1";
+
+lazy_static! {
+    static ref NAMES: HashMap<&'static str, &'static str> = TECHNIQUES
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| l.split_once('\t'))
+        .collect();
+}
+
+/// Returns the name of a technique, sub-techniques are prefixed with the name of their parent.
+pub fn name(id: &str) -> Option<String> {
+    let name = NAMES.get(id)?;
+    match id.split_once('.') {
+        Some((parent, _)) => match NAMES.get(parent) {
+            Some(parent) => Some(format!("{}: {}", parent, name)),
+            None => Some(name.to_string()),
+        },
+        None => Some(name.to_string()),
+    }
+}
+
+/// Extracts the technique id from a tag, accepting both the Sigma form (`attack.t1059.001`) and
+/// the bare id (`T1059.001`). Tags for tactics, groups and software are ignored.
+pub fn technique(tag: &str) -> Option<String> {
+    let tag = tag.trim();
+    let tag = match tag.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("attack.") => &tag[7..],
+        _ => tag,
+    };
+    let id = tag.strip_prefix('t').or_else(|| tag.strip_prefix('T'))?;
+    let (technique, sub) = match id.split_once('.') {
+        Some((technique, sub)) => (technique, Some(sub)),
+        None => (id, None),
+    };
+    if technique.len() != 4 || !technique.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    match sub {
+        Some(sub) => {
+            if sub.len() != 3 || !sub.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            Some(format!("T{}.{}", technique, sub))
+        }
+        None => Some(format!("T{}", technique)),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Gradient {
+    pub colors: Vec<&'static str>,
+    #[serde(rename = "minValue")]
+    pub min_value: usize,
+    #[serde(rename = "maxValue")]
+    pub max_value: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Metadata {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Technique {
+    #[serde(rename = "techniqueID")]
+    pub technique_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub enabled: bool,
+    pub metadata: Vec<Metadata>,
+    pub show_subtechniques: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Versions {
+    pub attack: &'static str,
+    pub layer: &'static str,
+    pub navigator: &'static str,
+}
+
+/// An ATT&CK Navigator layer, see: https://github.com/mitre-attack/attack-navigator
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Layer {
+    pub name: String,
+    pub versions: Versions,
+    pub domain: &'static str,
+    pub description: String,
+    pub sorting: u8,
+    pub hide_disabled: bool,
+    pub techniques: Vec<Technique>,
+    pub gradient: Gradient,
+}
+
+impl Layer {
+    fn new(
+        title: &str,
+        description: &str,
+        colours: Vec<&'static str>,
+        scores: BTreeMap<String, (usize, BTreeSet<String>)>,
+        unit: &str,
+    ) -> Self {
+        let max = scores.values().map(|(s, _)| *s).max().unwrap_or(0);
+        let mut parents = BTreeSet::new();
+        let mut techniques = Vec::with_capacity(scores.len());
+        for (id, (score, rules)) in scores {
+            if let Some((parent, _)) = id.split_once('.') {
+                parents.insert(parent.to_owned());
+            }
+            let comment = format!(
+                "{} - {} {}",
+                name(&id).unwrap_or_else(|| "Unknown Technique".to_owned()),
+                score,
+                if score == 1 {
+                    unit.to_owned()
+                } else {
+                    format!("{}s", unit)
+                },
+            );
+            techniques.push(Technique {
+                technique_id: id,
+                score: Some(score),
+                comment: Some(comment),
+                enabled: true,
+                metadata: rules
+                    .into_iter()
+                    .map(|r| Metadata {
+                        name: "rule".to_owned(),
+                        value: r,
+                    })
+                    .collect(),
+                show_subtechniques: false,
+            });
+        }
+        // NOTE: The navigator collapses sub-techniques by default, so we expand any parent that
+        // has a scored sub-technique to make sure that it is visible.
+        for parent in parents {
+            match techniques.iter_mut().find(|t| t.technique_id == parent) {
+                Some(technique) => technique.show_subtechniques = true,
+                None => techniques.push(Technique {
+                    technique_id: parent,
+                    score: None,
+                    comment: None,
+                    enabled: true,
+                    metadata: vec![],
+                    show_subtechniques: true,
+                }),
+            }
+        }
+        techniques.sort_by(|x, y| x.technique_id.cmp(&y.technique_id));
+        Self {
+            name: title.to_owned(),
+            versions: Versions {
+                attack: ATTACK_VERSION,
+                layer: LAYER_VERSION,
+                navigator: NAVIGATOR_VERSION,
+            },
+            domain: "enterprise-attack",
+            description: description.to_owned(),
+            sorting: 3,
+            hide_disabled: false,
+            techniques,
+            gradient: Gradient {
+                colors: colours,
+                min_value: 0,
+                max_value: max.max(1),
+            },
+        }
+    }
+
+    /// Builds a layer scored by the number of loaded rules that cover each technique.
+    pub fn coverage(rules: &BTreeMap<Uuid, Rule>) -> Self {
+        let mut scores: BTreeMap<String, (usize, BTreeSet<String>)> = BTreeMap::new();
+        for rule in rules.values() {
+            for technique in rule.attack() {
+                let score = scores.entry(technique).or_default();
+                score.0 += 1;
+                score.1.insert(rule.name().clone());
+            }
+        }
+        Self::new(
+            "Chainsaw Rule Coverage",
+            "The techniques covered by the loaded detection rules, scored by rule count",
+            vec!["#ffffff", "#66b1ff"],
+            scores,
+            "rule",
+        )
+    }
+
+    /// Builds a layer scored by the number of detections for each technique.
+    pub fn hits(hits: &HashMap<Uuid, usize>, rules: &BTreeMap<Uuid, Rule>) -> Self {
+        let mut scores: BTreeMap<String, (usize, BTreeSet<String>)> = BTreeMap::new();
+        for (id, count) in hits {
+            let rule = rules.get(id).expect("could not get rule");
+            for technique in rule.attack() {
+                let score = scores.entry(technique).or_default();
+                score.0 += count;
+                score.1.insert(rule.name().clone());
+            }
+        }
+        Self::new(
+            "Chainsaw Hunt",
+            "The techniques detected by the hunt, scored by detection count",
+            vec!["#ffffff", "#ff6666"],
+            scores,
+            "detection",
+        )
+    }
+}
@@ -1,6 +1,7 @@
-use std::collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet};
+use std::collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::time::Duration;
 
 use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
@@ -15,8 +16,12 @@ use uuid::Uuid;
 use crate::analyse::shimcache::{TimelineEntity, TimelineTimestamp, TimestampType};
 use crate::file::hve::shimcache::EntryType;
 use crate::file::Kind as FileKind;
-use crate::hunt::{Detections, Hunt, Kind};
-use crate::rule::{Kind as RuleKind, Level, Rule, Status};
+use crate::hunt::{Detections, Explanation, Hunt, Kind};
+use crate::profile::{Entry as ProfiledEntry, Report as Profiled};
+use crate::risk::{Contributor, Entity, Risk};
+use crate::rule::{yara::Match as YaraMatch, Kind as RuleKind, Level, Matched, Rule, Status};
+use crate::suppress::Suppression;
+use crate::tester::Report;
 use crate::value::Value;
 use crate::write::WRITER;
 
@@ -111,6 +116,7 @@ pub struct Grouping<'a> {
 pub struct Hit<'a> {
     hunt: &'a Hunt,
     rule: &'a Rule,
+    matched: Option<&'a Matched>,
 }
 
 pub fn print_log(
@@ -178,6 +184,10 @@ pub fn print_log(
                 columns.push("σ".to_string());
                 &rule.name
             }
+            Rule::Yara(rule) => {
+                columns.push("y".to_string());
+                &rule.name
+            }
         };
         //columns.push(format!("{: <width$}", name, width = rule_width - 1));
         //columns.push(format!("{: >6}", count));
@@ -275,6 +285,9 @@ pub fn print_detections(
         }
     }
     let mut headers: HashMap<_, _> = headers.into_iter().map(|(k, (v, _))| (k, v)).collect();
+    let matches = detections
+        .iter()
+        .any(|d| d.hits.iter().any(|h| h.matched.is_some()));
 
     // Build lookups
     let hunts: HashMap<_, _> = hunts.iter().map(|h| (&h.id, h)).collect();
@@ -287,7 +300,11 @@ pub fn print_detections(
             let hunt = &hunts.get(&hit.hunt).expect("could not get hunt");
             let rule = &rules.get(&hit.rule).expect("could not get rule");
             let hits = hits.entry((&hunt.group, &hit.timestamp)).or_insert(vec![]);
-            (*hits).push(Hit { hunt, rule });
+            (*hits).push(Hit {
+                hunt,
+                rule,
+                matched: hit.matched.as_ref(),
+            });
         }
         for ((group, timestamp), mut hits) in hits {
             hits.sort_by(|x, y| x.rule.name().cmp(y.rule.name()));
@@ -314,6 +331,9 @@ pub fn print_detections(
                 cell!("timestamp").style_spec("c"),
                 cell!("detections").style_spec("c"),
             ];
+            if matches {
+                cells.push(cell!("matched").style_spec("c"));
+            }
             if headers.is_empty() {
                 cells.push(cell!("data").style_spec("c"));
             } else {
@@ -356,16 +376,15 @@ pub fn print_detections(
                 };
 
                 let mut rows = vec![];
-                let mut seen: HashMap<u64, Vec<&Rule>> = HashMap::new();
+                let mut seen: HashMap<u64, Vec<&Hit>> = HashMap::new();
                 if headers.is_empty() {
                     let json = serde_json::to_string(&document.data)
                         .expect("could not serialise document");
-                    let rules = grouping.hits.iter().map(|hit| hit.rule).collect();
                     rows.push((
                         0,
                         vec![cell!(format_field_length(&json, false, column_width))],
                     ));
-                    seen.insert(0, rules);
+                    seen.insert(0, grouping.hits.iter().collect());
                 } else {
                     // What we do here is hash each row since if the fields are the same but the values
                     // are not then we would lose data, so in this case we split the row
@@ -440,13 +459,14 @@ pub fn print_detections(
                         if !seen.contains_key(&id) {
                             rows.push((id, cells));
                         }
-                        let rules = seen.entry(id).or_insert(vec![]);
-                        (*rules).push(hit.rule);
+                        let hits = seen.entry(id).or_insert(vec![]);
+                        (*hits).push(hit);
                     }
                 }
 
                 for (id, row) in rows {
-                    let rules = seen.remove(&id).expect("could not get rules");
+                    let hits = seen.remove(&id).expect("could not get rules");
+                    let rules = hits.iter().map(|hit| hit.rule).collect::<Vec<_>>();
                     let mut cells = vec![cell!(localised)];
                     if metadata {
                         let mut table = Table::new();
@@ -477,6 +497,15 @@ pub fn print_detections(
                                         cell!(s.status),
                                     ]));
                                 }
+                                Rule::Yara(y) => {
+                                    table.add_row(Row::new(vec![
+                                        cell!('y'),
+                                        cell!(split_tag(&y.name)),
+                                        cell!(y.authors.join("\n")),
+                                        cell!(y.level),
+                                        cell!(y.status),
+                                    ]));
+                                }
                             }
                         }
                         cells.push(cell!(table));
@@ -487,6 +516,29 @@ pub fn print_detections(
                             .collect::<Vec<_>>()
                             .join("\n")));
                     }
+                    if matches {
+                        let mut lines = vec![];
+                        for hit in &hits {
+                            let matched = match hit.matched {
+                                Some(matched) => matched,
+                                None => continue,
+                            };
+                            if hits.len() > 1 {
+                                lines.push(format!("{} {}", RULE_PREFIX, hit.rule.name()));
+                            }
+                            for (identifier, fields) in matched {
+                                lines.push(identifier.clone());
+                                for (field, value) in fields {
+                                    lines.push(format!(
+                                        "  {}: {}",
+                                        field,
+                                        format_field_length(value, full, column_width)
+                                    ));
+                                }
+                            }
+                        }
+                        cells.push(cell!(lines.join("\n")).style_spec("Fy"));
+                    }
                     cells.extend(row);
                     table.add_row(Row::new(cells));
                 }
@@ -498,6 +550,397 @@ pub fn print_detections(
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct Explained<'a> {
+    pub name: &'a String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: &'a Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub indicators: &'a Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logsource: &'a Option<crate::rule::sigma::LogSource>,
+    pub groups: &'a Vec<Explanation>,
+}
+
+pub fn print_explain(explanations: &[(&Rule, Vec<Explanation>)], json: bool) -> crate::Result<()> {
+    if json {
+        let explained = explanations
+            .iter()
+            .filter_map(|(rule, groups)| match rule {
+                Rule::Sigma(s) => Some(Explained {
+                    name: &s.name,
+                    id: &s.id,
+                    logsource: &s.logsource,
+                    groups,
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        cs_print_json!(&explained)?;
+        return Ok(());
+    }
+
+    let format = format::FormatBuilder::new()
+        .column_separator('│')
+        .borders('│')
+        .separators(
+            &[format::LinePosition::Top],
+            format::LineSeparator::new('─', '┬', '┌', '┐'),
+        )
+        .separators(
+            &[format::LinePosition::Intern],
+            format::LineSeparator::new('─', '┼', '├', '┤'),
+        )
+        .separators(
+            &[format::LinePosition::Bottom],
+            format::LineSeparator::new('─', '┴', '└', '┘'),
+        )
+        .padding(1, 1)
+        .build();
+
+    let mut table = Table::new();
+    table.set_format(format);
+    table.add_row(Row::new(vec![
+        cell!("rule").style_spec("c"),
+        cell!("group").style_spec("c"),
+        cell!("coverage").style_spec("c"),
+        cell!("unmapped fields").style_spec("c"),
+    ]));
+    for (rule, groups) in explanations {
+        if groups.is_empty() {
+            table.add_row(Row::new(vec![
+                cell!(split_tag(rule.name())),
+                cell!("-"),
+                cell!("no groups"),
+                cell!(""),
+            ]));
+            continue;
+        }
+        for (i, group) in groups.iter().enumerate() {
+            let coverage = if group.precondition {
+                format!("{}\n(precondition)", group.coverage)
+            } else {
+                group.coverage.to_string()
+            };
+            table.add_row(Row::new(vec![
+                cell!(if i == 0 {
+                    split_tag(rule.name())
+                } else {
+                    String::new()
+                }),
+                cell!(group.group),
+                cell!(coverage),
+                cell!(group.unmapped.join("\n")),
+            ]));
+        }
+    }
+
+    cs_greenln!("\n[+] Sigma Rule Coverage");
+    cs_print_table!(table);
+    Ok(())
+}
+
+pub fn print_tests(reports: &[Report], json: bool) -> crate::Result<()> {
+    if json {
+        cs_print_json!(&reports)?;
+        return Ok(());
+    }
+
+    let format = format::FormatBuilder::new()
+        .column_separator('│')
+        .borders('│')
+        .separators(
+            &[format::LinePosition::Top],
+            format::LineSeparator::new('─', '┬', '┌', '┐'),
+        )
+        .separators(
+            &[format::LinePosition::Intern],
+            format::LineSeparator::new('─', '┼', '├', '┤'),
+        )
+        .separators(
+            &[format::LinePosition::Bottom],
+            format::LineSeparator::new('─', '┴', '└', '┘'),
+        )
+        .padding(1, 1)
+        .build();
+
+    let mut table = Table::new();
+    table.set_format(format);
+    table.add_row(Row::new(vec![
+        cell!("rule").style_spec("c"),
+        cell!("kind").style_spec("c"),
+        cell!("true positives").style_spec("c"),
+        cell!("true negatives").style_spec("c"),
+        cell!("result").style_spec("c"),
+        cell!("failures").style_spec("c"),
+    ]));
+    for report in reports {
+        let (result, style) = if report.error.is_some() {
+            ("error", "Fr")
+        } else if report.skipped() {
+            ("no samples", "Fy")
+        } else if report.passed() {
+            ("passed", "Fg")
+        } else {
+            ("failed", "Fr")
+        };
+        let failures = match &report.error {
+            Some(error) => format_field_length(error, false, 60),
+            None => report
+                .failures
+                .iter()
+                .map(|f| {
+                    if f.expected {
+                        format!("{} did not match", f.sample)
+                    } else {
+                        format!("{} matched", f.sample)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        table.add_row(Row::new(vec![
+            cell!(split_tag(&report.name)),
+            cell!(report.kind),
+            cell!(format!(
+                "{}/{}",
+                report.true_positives.passed, report.true_positives.total
+            )),
+            cell!(format!(
+                "{}/{}",
+                report.true_negatives.passed, report.true_negatives.total
+            )),
+            cell!(result).style_spec(style),
+            cell!(failures),
+        ]));
+    }
+
+    cs_greenln!("\n[+] Rule Tests");
+    cs_print_table!(table);
+    Ok(())
+}
+
+pub fn print_risk(risks: &[Risk], top: usize, local: bool, timezone: Option<Tz>) {
+    let format = format::FormatBuilder::new()
+        .column_separator('│')
+        .borders('│')
+        .separators(
+            &[format::LinePosition::Top],
+            format::LineSeparator::new('─', '┬', '┌', '┐'),
+        )
+        .separators(
+            &[format::LinePosition::Intern],
+            format::LineSeparator::new('─', '┼', '├', '┤'),
+        )
+        .separators(
+            &[format::LinePosition::Bottom],
+            format::LineSeparator::new('─', '┴', '└', '┘'),
+        )
+        .padding(1, 1)
+        .build();
+
+    let localise = |timestamp: &NaiveDateTime| format_time(rfc3339(timestamp, local, timezone));
+
+    let mut table = Table::new();
+    table.set_format(format);
+    table.add_row(Row::new(vec![
+        cell!("rank").style_spec("c"),
+        cell!("entity").style_spec("c"),
+        cell!("type").style_spec("c"),
+        cell!("score").style_spec("c"),
+        cell!("detections").style_spec("c"),
+        cell!("first seen").style_spec("c"),
+        cell!("last seen").style_spec("c"),
+    ]));
+    for (i, risk) in risks.iter().take(top).enumerate() {
+        table.add_row(Row::new(vec![
+            cell!(i + 1),
+            cell!(risk.entity),
+            cell!(risk.kind),
+            cell!(risk.score),
+            cell!(risk
+                .detections
+                .iter()
+                .map(|d| format!("{} {} ({})", RULE_PREFIX, split_tag(&d.name), d.count))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            cell!(localise(&risk.first_seen)),
+            cell!(localise(&risk.last_seen)),
+        ]));
+    }
+
+    cs_greenln!(
+        "\n[+] Risk Summary (top {} of {} entities)",
+        risks.len().min(top),
+        risks.len()
+    );
+    cs_print_table!(table);
+}
+
+#[derive(Serialize)]
+struct LocalisedRisk<'a> {
+    entity: &'a String,
+    #[serde(rename = "type")]
+    kind: &'a Entity,
+    score: u64,
+    first_seen: String,
+    last_seen: String,
+    groups: &'a BTreeSet<String>,
+    detections: &'a Vec<Contributor>,
+}
+
+pub fn write_risk<W: Write>(
+    writer: W,
+    risks: &[Risk],
+    local: bool,
+    timezone: Option<Tz>,
+) -> crate::Result<()> {
+    let risks = risks
+        .iter()
+        .map(|risk| LocalisedRisk {
+            entity: &risk.entity,
+            kind: &risk.kind,
+            score: risk.score,
+            first_seen: rfc3339(&risk.first_seen, local, timezone),
+            last_seen: rfc3339(&risk.last_seen, local, timezone),
+            groups: &risk.groups,
+            detections: &risk.detections,
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_writer_pretty(writer, &risks)?;
+    Ok(())
+}
+
+fn rfc3339(timestamp: &NaiveDateTime, local: bool, timezone: Option<Tz>) -> String {
+    if let Some(timezone) = timezone {
+        timezone
+            .from_local_datetime(timestamp)
+            .single()
+            .expect("failed to localise timestamp")
+            .to_rfc3339()
+    } else if local {
+        Utc.from_local_datetime(timestamp)
+            .single()
+            .expect("failed to localise timestamp")
+            .to_rfc3339()
+    } else {
+        DateTime::<Utc>::from_utc(*timestamp, Utc).to_rfc3339()
+    }
+}
+
+// The number of rules shown in the profile, the full list is rarely useful as most rules are fast.
+const PROFILE_RULES: usize = 20;
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds >= 1.0 {
+        format!("{:.2}s", seconds)
+    } else if seconds >= 0.001 {
+        format!("{:.2}ms", seconds * 1_000.0)
+    } else {
+        format!("{:.2}µs", seconds * 1_000_000.0)
+    }
+}
+
+// NOTE: The profile is written to stderr so that it does not get mixed in with the detections.
+pub fn print_profile(report: &Profiled) {
+    let format = format::FormatBuilder::new()
+        .column_separator('│')
+        .borders('│')
+        .separators(
+            &[format::LinePosition::Top],
+            format::LineSeparator::new('─', '┬', '┌', '┐'),
+        )
+        .separators(
+            &[format::LinePosition::Intern],
+            format::LineSeparator::new('─', '┼', '├', '┤'),
+        )
+        .separators(
+            &[format::LinePosition::Bottom],
+            format::LineSeparator::new('─', '┴', '└', '┘'),
+        )
+        .padding(1, 1)
+        .build();
+
+    let phases = [
+        ("parsing", report.parsing),
+        ("mapping", report.mapping),
+        ("matching", report.matching),
+        ("output", report.output),
+    ];
+    let sum = phases
+        .iter()
+        .map(|(_, d)| *d)
+        .sum::<Duration>()
+        .as_secs_f64();
+    let mut table = Table::new();
+    table.set_format(format);
+    table.add_row(Row::new(vec![
+        cell!("phase").style_spec("c"),
+        cell!("time").style_spec("c"),
+        cell!("share").style_spec("c"),
+    ]));
+    for (phase, duration) in phases {
+        let share = if sum > 0.0 {
+            duration.as_secs_f64() / sum * 100.0
+        } else {
+            0.0
+        };
+        table.add_row(Row::new(vec![
+            cell!(phase),
+            cell!(format_duration(duration)),
+            cell!(format!("{:.1}%", share)),
+        ]));
+    }
+    cs_egreenln!(
+        "\n[+] Profile (hunted in {}, phases are summed across threads)",
+        format_duration(report.total)
+    );
+    cs_eprintln!("{}", table);
+
+    let entries = |heading: &str, entries: &[ProfiledEntry]| {
+        let mut table = Table::new();
+        table.set_format(format);
+        table.add_row(Row::new(vec![
+            cell!(heading).style_spec("c"),
+            cell!("evaluations").style_spec("c"),
+            cell!("matches").style_spec("c"),
+            cell!("time").style_spec("c"),
+            cell!("average").style_spec("c"),
+        ]));
+        for entry in entries {
+            let average = if entry.evaluations > 0 {
+                Duration::from_nanos((entry.time.as_nanos() / entry.evaluations as u128) as u64)
+            } else {
+                Duration::default()
+            };
+            table.add_row(Row::new(vec![
+                cell!(split_tag(&entry.name)),
+                cell!(entry.evaluations),
+                cell!(entry.matches),
+                cell!(format_duration(entry.time)),
+                cell!(format_duration(average)),
+            ]));
+        }
+        table
+    };
+    if !report.groups.is_empty() {
+        let table = entries("group", &report.groups);
+        cs_egreenln!("\n[+] Mapping Groups");
+        cs_eprintln!("{}", table);
+    }
+    if !report.rules.is_empty() {
+        let top = report.rules.len().min(PROFILE_RULES);
+        let table = entries("rule", &report.rules[..top]);
+        cs_egreenln!(
+            "\n[+] Slowest Rules (top {} of {})",
+            top,
+            report.rules.len()
+        );
+        cs_eprintln!("{}", table);
+    }
+}
+
 pub fn print_shimcache_analysis_csv(timeline: &Vec<TimelineEntity>) -> crate::Result<()> {
     let path = unsafe { &WRITER.path };
     let csv = if let Some(path) = path {
@@ -680,6 +1123,9 @@ pub fn print_csv(
         }
     }
     let mut headers: HashMap<_, _> = headers.into_iter().map(|(k, (v, _))| (k, v)).collect();
+    let matches = detections
+        .iter()
+        .any(|d| d.hits.iter().any(|h| h.matched.is_some()));
 
     // Build lookups
     let hunts: HashMap<_, _> = hunts.iter().map(|h| (&h.id, h)).collect();
@@ -692,7 +1138,11 @@ pub fn print_csv(
             let hunt = &hunts.get(&hit.hunt).expect("could not get hunt");
             let rule = &rules.get(&hit.rule).expect("could not get rule");
             let hits = hits.entry((&hunt.group, &hit.timestamp)).or_insert(vec![]);
-            (*hits).push(Hit { hunt, rule });
+            (*hits).push(Hit {
+                hunt,
+                rule,
+                matched: hit.matched.as_ref(),
+            });
         }
         for ((group, timestamp), mut hits) in hits {
             hits.sort_by(|x, y| x.rule.name().cmp(y.rule.name()));
@@ -719,6 +1169,9 @@ pub fn print_csv(
 
         if let Some(headers) = headers.remove(key) {
             let mut cells = vec!["timestamp", "detections"];
+            if matches {
+                cells.push("matched");
+            }
             if headers.is_empty() {
                 cells.push("data");
             } else {
@@ -759,13 +1212,12 @@ pub fn print_csv(
                 };
 
                 let mut rows = vec![];
-                let mut seen: HashMap<u64, Vec<&Rule>> = HashMap::new();
+                let mut seen: HashMap<u64, Vec<&Hit>> = HashMap::new();
                 if headers.is_empty() {
                     let json = serde_json::to_string(&document.data)
                         .expect("could not serialise document");
-                    let rules = grouping.hits.iter().map(|hit| hit.rule).collect();
                     rows.push((0, vec![json]));
-                    seen.insert(0, rules);
+                    seen.insert(0, grouping.hits.iter().collect());
                 } else {
                     // What we do here is hash each row since if the fields are the same but the values
                     // are not then we would lose data, so in this case we split the row
@@ -825,21 +1277,46 @@ pub fn print_csv(
                         if !seen.contains_key(&id) {
                             rows.push((id, cells));
                         }
-                        let rules = seen.entry(id).or_insert(vec![]);
-                        (*rules).push(hit.rule);
+                        let hits = seen.entry(id).or_insert(vec![]);
+                        (*hits).push(hit);
                     }
                 }
 
                 for (id, row) in rows {
-                    let rules = seen.remove(&id).expect("could not get rules");
+                    let hits = seen.remove(&id).expect("could not get rules");
                     let mut cells = vec![localised.clone()];
                     cells.push(
-                        rules
-                            .iter()
-                            .map(|rule| rule.name().to_string())
+                        hits.iter()
+                            .map(|hit| hit.rule.name().to_string())
                             .collect::<Vec<_>>()
                             .join(";"),
                     );
+                    if matches {
+                        let mut entries = vec![];
+                        for hit in &hits {
+                            let matched = match hit.matched {
+                                Some(matched) => matched,
+                                None => continue,
+                            };
+                            for (identifier, fields) in matched {
+                                for (field, value) in fields {
+                                    if hits.len() > 1 {
+                                        entries.push(format!(
+                                            "{} - {}: {}={}",
+                                            hit.rule.name(),
+                                            identifier,
+                                            field,
+                                            value
+                                        ));
+                                    } else {
+                                        entries
+                                            .push(format!("{}: {}={}", identifier, field, value));
+                                    }
+                                }
+                            }
+                        }
+                        cells.push(entries.join(";"));
+                    }
                     cells.extend(row);
                     csv.write_record(cells)?;
                 }
@@ -862,8 +1339,17 @@ pub struct Detection<'a> {
     pub source: RuleKind,
     pub status: &'a Status,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attack: Option<&'a Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched: Option<&'a Matched>,
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub sigma: Option<Sigma<'a>>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub yara: Option<Yara<'a>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppressed: Option<&'a Suppression>,
 }
 
 #[derive(Debug, Serialize)]
@@ -872,6 +1358,8 @@ pub struct Sigma<'a> {
     pub falsepositives: &'a Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: &'a Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub indicators: &'a Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub logsource: &'a Option<crate::rule::sigma::LogSource>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -880,15 +1368,23 @@ pub struct Sigma<'a> {
     pub tags: &'a Option<Vec<String>>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct Yara<'a> {
+    pub tags: &'a Vec<String>,
+    pub strings: &'a Vec<YaraMatch>,
+}
+
 pub fn print_json(
     detections: &[Detections],
     hunts: &[Hunt],
     rules: &BTreeMap<Uuid, Rule>,
+    suppressions: &[Suppression],
     local: bool,
     timezone: Option<Tz>,
     jsonl: bool,
 ) -> crate::Result<()> {
     let hunts: HashMap<_, _> = hunts.iter().map(|h| (&h.id, h)).collect();
+    let suppressions: HashMap<_, _> = suppressions.iter().map(|s| (&s.id, s)).collect();
     let mut detections = detections
         .iter()
         .flat_map(|d| {
@@ -910,6 +1406,10 @@ pub fn print_json(
                 } else {
                     DateTime::<Utc>::from_utc(hit.timestamp, Utc).to_rfc3339()
                 };
+                let suppressed = hit
+                    .suppressed
+                    .as_ref()
+                    .and_then(|id| suppressions.get(id).copied());
                 match rule {
                     Rule::Chainsaw(c) => detections.push(Detection {
                         authors: &c.authors,
@@ -921,12 +1421,17 @@ pub fn print_json(
                         status: &c.status,
                         timestamp: localised,
 
+                        attack: c.attack.as_ref(),
+                        matched: hit.matched.as_ref(),
                         sigma: None,
+                        suppressed,
+                        yara: None,
                     }),
                     Rule::Sigma(s) => {
                         let sigma = Sigma {
                             falsepositives: &s.falsepositives,
                             id: &s.id,
+                            indicators: &hit.indicators,
                             logsource: &s.logsource,
                             references: &s.references,
                             tags: &s.tags,
@@ -941,9 +1446,32 @@ pub fn print_json(
                             status: &s.status,
                             timestamp: localised,
 
+                            attack: None,
+                            matched: hit.matched.as_ref(),
                             sigma: Some(sigma),
+                            suppressed,
+                            yara: None,
                         })
                     }
+                    Rule::Yara(y) => detections.push(Detection {
+                        authors: &y.authors,
+                        group: &hunt.group,
+                        kind: &d.kind,
+                        level: &y.level,
+                        name: &y.name,
+                        source: RuleKind::Yara,
+                        status: &y.status,
+                        timestamp: localised,
+
+                        attack: None,
+                        matched: None,
+                        sigma: None,
+                        suppressed,
+                        yara: Some(Yara {
+                            tags: &y.tags,
+                            strings: &hit.strings,
+                        }),
+                    }),
                 }
             }
             detections
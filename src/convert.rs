@@ -0,0 +1,652 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use serde_yaml::{Mapping as Yaml, Value};
+use tau_engine::core::parser::{IdentifierParser, Pattern};
+
+use crate::hunt::{Group, Mapping};
+use crate::modifiers::{CIDR, EXISTS, FIELDREF, GLOB};
+use crate::rule::{chainsaw, sigma, Filter, Kind as RuleKind, Level};
+
+/// The rules that a rule file was converted into, along with anything that was not carried over
+/// exactly.
+pub struct Conversion {
+    pub rules: Vec<Value>,
+    pub warnings: Vec<String>,
+}
+
+/// Converts Sigma rules into Chainsaw rules using the groups of a mapping, and Chainsaw rules into
+/// Sigma rules where their filters can be expressed in Sigma.
+pub struct Converter {
+    config: sigma::Config,
+    mapping: Option<(Mapping, Vec<Value>)>,
+}
+
+impl Converter {
+    pub fn new(mapping: Option<&Path>, config: sigma::Config) -> crate::Result<Self> {
+        let mapping = match mapping {
+            Some(path) => {
+                let content = fs::read_to_string(path)?;
+                let raw: Value = serde_yaml::from_str(&content)?;
+                if raw.get("transformations").is_some() && raw.get("groups").is_none() {
+                    anyhow::bail!("processing pipelines cannot be used to convert rules");
+                }
+                let mapping: Mapping = serde_yaml::from_value(raw.clone())?;
+                if mapping.rules != RuleKind::Sigma {
+                    anyhow::bail!("the mapping must be for sigma rules");
+                }
+                let groups = raw
+                    .get("groups")
+                    .and_then(|g| g.as_sequence())
+                    .cloned()
+                    .unwrap_or_default();
+                Some((mapping, groups))
+            }
+            None => None,
+        };
+        Ok(Self { config, mapping })
+    }
+
+    pub fn convert(&self, kind: &RuleKind, path: &Path) -> crate::Result<Conversion> {
+        match kind {
+            RuleKind::Chainsaw => self.to_sigma(path),
+            RuleKind::Sigma => self.to_chainsaw(path),
+            RuleKind::Yara => anyhow::bail!("yara rules cannot be converted"),
+        }
+    }
+
+    fn to_chainsaw(&self, path: &Path) -> crate::Result<Conversion> {
+        let (mapping, groups) = match &self.mapping {
+            Some(mapping) => mapping,
+            None => anyhow::bail!("a mapping is required to convert sigma rules"),
+        };
+        let collection = sigma::load(path, &self.config)?;
+        let mut conversion = Conversion {
+            rules: vec![],
+            warnings: collection.discarded.iter().map(|d| d.to_string()).collect(),
+        };
+        for rule in collection.rules {
+            let parsed: sigma::Rule = serde_yaml::from_value(rule.clone())?;
+            if mapping.exclusions.contains(&parsed.name) {
+                conversion
+                    .warnings
+                    .push(format!("'{}' is excluded by the mapping", parsed.name));
+                continue;
+            }
+            let mut converted = 0;
+            for (group, raw) in mapping.groups.iter().zip(groups) {
+                let provided = match &group.logsource {
+                    Some(logsource) => logsource.provides(parsed.logsource.as_ref()),
+                    None => true,
+                };
+                if !provided {
+                    continue;
+                }
+                let (rule, warnings) = chainsaw_rule(&rule, mapping, group, raw)?;
+                conversion.rules.push(rule);
+                conversion.warnings.extend(warnings);
+                converted += 1;
+            }
+            if converted == 0 {
+                anyhow::bail!(
+                    "no group in the mapping provides the logsource of '{}'",
+                    parsed.name
+                );
+            }
+        }
+        Ok(conversion)
+    }
+
+    fn to_sigma(&self, path: &Path) -> crate::Result<Conversion> {
+        let content = fs::read_to_string(path)?;
+        let raw: Value = serde_yaml::from_str(&content)?;
+        let rule: chainsaw::Rule = serde_yaml::from_value(raw.clone())?;
+        if rule.aggregate.is_some() {
+            anyhow::bail!("aggregations cannot be converted");
+        }
+
+        let mut warnings = vec![];
+        let mut resolve = |key: &str| -> crate::Result<String> {
+            let path = match rule.fields.iter().find(|f| f.from == key) {
+                Some(field) if field.container.is_some() || !field.transforms.is_empty() => {
+                    anyhow::bail!(
+                        "field '{}' uses a container or transforms which cannot be converted",
+                        key
+                    )
+                }
+                Some(field) => field.to.clone(),
+                None => key.to_owned(),
+            };
+            let (mapping, _) = match &self.mapping {
+                Some(mapping) => mapping,
+                None => return Ok(path),
+            };
+            let name = mapping
+                .groups
+                .iter()
+                .flat_map(|g| g.fields.iter())
+                .find(|f| {
+                    f.to == path
+                        && f.cast.is_none()
+                        && f.container.is_none()
+                        && f.transforms.is_empty()
+                })
+                .map(|f| f.from.clone());
+            match name {
+                Some(name) => Ok(name),
+                None => {
+                    let warning = format!("field '{}' is not mapped to a sigma field", path);
+                    if !warnings.contains(&warning) {
+                        warnings.push(warning);
+                    }
+                    Ok(path)
+                }
+            }
+        };
+
+        let filter = raw.get("filter").cloned().unwrap_or_default();
+        let mut detection = Yaml::new();
+        match &rule.filter {
+            Filter::Detection(_) => {
+                let filter = match filter {
+                    Value::Mapping(filter) => filter,
+                    _ => anyhow::bail!("filter must be a mapping"),
+                };
+                let mut identifiers = BTreeSet::new();
+                for (k, v) in &filter {
+                    let k = match k.as_str() {
+                        Some(k) => k,
+                        None => anyhow::bail!("identifiers must be strings"),
+                    };
+                    if k == "condition" {
+                        continue;
+                    }
+                    identifiers.insert(k.to_owned());
+                    detection.insert(k.into(), sigma_identifier(v, &mut resolve)?);
+                }
+                let condition = match filter.get("condition").and_then(|c| c.as_str()) {
+                    Some(condition) => condition,
+                    None => anyhow::bail!("condition must be a string"),
+                };
+                let tokens = condition.replace(['(', ')'], " ");
+                for token in tokens.split_whitespace() {
+                    if !matches!(token, "and" | "or" | "not") && !identifiers.contains(token) {
+                        anyhow::bail!(
+                            "condition uses '{}' which cannot be expressed in sigma",
+                            token
+                        );
+                    }
+                }
+                detection.insert("condition".into(), condition.into());
+            }
+            Filter::Expression(_) => {
+                detection.insert("selection".into(), sigma_identifier(&filter, &mut resolve)?);
+                detection.insert("condition".into(), "selection".into());
+            }
+        }
+
+        // NOTE: Chainsaw rules do not have ids, so one is derived from the title to keep it stable
+        // between conversions.
+        let digest = md5::compute(rule.name.as_bytes());
+        let id = uuid::Builder::from_md5_bytes(digest.0).into_uuid();
+
+        let mut sigma = Yaml::new();
+        sigma.insert("title".into(), rule.name.clone().into());
+        sigma.insert("id".into(), id.to_string().into());
+        sigma.insert("status".into(), rule.status.to_string().into());
+        sigma.insert("description".into(), rule.description.clone().into());
+        sigma.insert("author".into(), rule.authors.join(", ").into());
+        if let Some(attack) = &rule.attack {
+            let tags = attack
+                .iter()
+                .map(|t| format!("attack.{}", t.to_lowercase()))
+                .collect::<Vec<_>>();
+            sigma.insert("tags".into(), tags.into());
+        }
+        let level = match rule.level {
+            Level::Info => "informational".to_owned(),
+            level => level.to_string(),
+        };
+        if matches!(
+            rule.kind,
+            crate::FileKind::Evtx | crate::FileKind::Hve | crate::FileKind::Mft
+        ) {
+            let mut logsource = Yaml::new();
+            logsource.insert("product".into(), "windows".into());
+            sigma.insert("logsource".into(), logsource.into());
+        }
+        sigma.insert("detection".into(), detection.into());
+        sigma.insert("level".into(), level.into());
+        for key in ["true_positives", "true_negatives"] {
+            if let Some(samples) = raw.get(key) {
+                sigma.insert(key.into(), samples.clone());
+            }
+        }
+        Ok(Conversion {
+            rules: vec![sigma.into()],
+            warnings,
+        })
+    }
+}
+
+// Builds the Chainsaw rule for a Sigma rule running under a mapping group, the fields that are
+// simply renamed by the group are replaced with the paths that they map to.
+fn chainsaw_rule(
+    rule: &Value,
+    mapping: &Mapping,
+    group: &Group,
+    raw: &Value,
+) -> crate::Result<(Value, Vec<String>)> {
+    let title = rule
+        .get("title")
+        .and_then(|t| t.as_str())
+        .unwrap_or_default();
+    let raw_fields = raw
+        .get("fields")
+        .and_then(|f| f.as_sequence())
+        .cloned()
+        .unwrap_or_default();
+    if raw_fields.len() != group.fields.len() {
+        anyhow::bail!("could not read the fields of group '{}'", group.name);
+    }
+    let fields = group
+        .fields
+        .iter()
+        .map(|f| (f.from.as_str(), f))
+        .collect::<HashMap<_, _>>();
+
+    let mut warnings = vec![];
+    let mut required = BTreeSet::new();
+    let mut rename = |field: &str| -> String {
+        match fields.get(field) {
+            Some(f) if f.cast.is_none() && f.container.is_none() && f.transforms.is_empty() => {
+                f.to.clone()
+            }
+            Some(_) => {
+                required.insert(field.to_owned());
+                field.to_owned()
+            }
+            None => {
+                let warning = format!(
+                    "'{}' uses field '{}' which is not mapped by group '{}'",
+                    title, field, group.name
+                );
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+                field.to_owned()
+            }
+        }
+    };
+
+    let mut filter = Yaml::new();
+    let detection = rule
+        .get("detection")
+        .and_then(|d| d.as_mapping())
+        .cloned()
+        .unwrap_or_default();
+    let mut condition = String::new();
+    for (k, v) in detection {
+        match k.as_str() {
+            Some("condition") => condition = v.as_str().unwrap_or_default().to_owned(),
+            Some(_) => {
+                filter.insert(k, rename_identifier(&v, &mut rename));
+            }
+            None => anyhow::bail!("identifiers must be strings"),
+        }
+    }
+    let mut identifier = "group_filter".to_owned();
+    while filter.contains_key(identifier.as_str()) {
+        identifier.push('_');
+    }
+    let group_filter = raw.get("filter").cloned().unwrap_or_default();
+    filter.insert(
+        identifier.as_str().into(),
+        rename_identifier(&group_filter, &mut rename),
+    );
+    filter.insert(
+        "condition".into(),
+        format!("{} and ({})", identifier, condition).into(),
+    );
+
+    let aggregate = match rule.get("aggregate") {
+        Some(aggregate) => {
+            let mut aggregate = aggregate.clone();
+            if let Some(Value::Sequence(fields)) = aggregate.get_mut("fields") {
+                for field in fields.iter_mut() {
+                    if let Some(f) = field.as_str() {
+                        *field = rename(f).into();
+                    }
+                }
+            }
+            Some(aggregate)
+        }
+        None => None,
+    };
+
+    let visible = group
+        .fields
+        .iter()
+        .zip(raw_fields)
+        .filter(|(f, _)| f.visible || required.contains(&f.from))
+        .map(|(_, r)| r)
+        .collect::<Vec<_>>();
+
+    let mut chainsaw = Yaml::new();
+    chainsaw.insert("title".into(), title.into());
+    chainsaw.insert("group".into(), group.name.clone().into());
+    for key in ["description", "authors"] {
+        if let Some(value) = rule.get(key) {
+            chainsaw.insert(key.into(), value.clone());
+        }
+    }
+    chainsaw.insert("kind".into(), serde_yaml::to_value(&mapping.kind)?);
+    for key in ["level", "status"] {
+        if let Some(value) = rule.get(key) {
+            chainsaw.insert(key.into(), value.clone());
+        }
+    }
+    chainsaw.insert("timestamp".into(), group.timestamp.clone().into());
+    if let Some(format) = raw.get("timestamp_format") {
+        chainsaw.insert("timestamp_format".into(), format.clone());
+    }
+    chainsaw.insert("fields".into(), visible.into());
+    chainsaw.insert("filter".into(), filter.into());
+    if let Some(aggregate) = aggregate {
+        chainsaw.insert("aggregate".into(), aggregate);
+    }
+    if let Some(Value::Sequence(tags)) = rule.get("tags") {
+        let attack = tags
+            .iter()
+            .filter_map(|t| t.as_str().and_then(crate::attack::technique))
+            .collect::<Vec<_>>();
+        if !attack.is_empty() {
+            chainsaw.insert("attack".into(), attack.into());
+        }
+    }
+    for key in ["true_positives", "true_negatives"] {
+        if let Some(samples) = rule.get(key) {
+            chainsaw.insert(key.into(), samples.clone());
+        }
+    }
+    Ok((chainsaw.into(), warnings))
+}
+
+// Renames the fields used by the keys of an identifier, leaving their values untouched.
+fn rename_identifier(value: &Value, rename: &mut dyn FnMut(&str) -> String) -> Value {
+    match value {
+        Value::Mapping(m) => Value::Mapping(
+            m.iter()
+                .map(|(k, v)| match k.as_str() {
+                    Some(k) => (rename_key(k, rename).into(), v.clone()),
+                    None => (k.clone(), v.clone()),
+                })
+                .collect(),
+        ),
+        Value::Sequence(s) => {
+            Value::Sequence(s.iter().map(|v| rename_identifier(v, rename)).collect())
+        }
+        v => v.clone(),
+    }
+}
+
+fn rename_key(key: &str, rename: &mut dyn FnMut(&str) -> String) -> String {
+    for prefix in [CIDR, GLOB] {
+        if let Some(rest) = key.strip_prefix(prefix) {
+            if let Some((field, values)) = rest.split_once('|') {
+                return format!("{}{}|{}", prefix, rename(field), values);
+            }
+        }
+    }
+    if let Some(rest) = key.strip_prefix(FIELDREF) {
+        if let Some((field, others)) = rest.split_once('|') {
+            let others = others.split(',').map(&mut *rename).collect::<Vec<_>>();
+            return format!("{}{}|{}", FIELDREF, rename(field), others.join(","));
+        }
+    }
+    if let Some(field) = key.strip_prefix(EXISTS) {
+        return format!("{}{}", EXISTS, rename(field));
+    }
+    for wrapper in ["int", "str", "all", "not"] {
+        let inner = key
+            .strip_prefix(wrapper)
+            .and_then(|k| k.strip_prefix('('))
+            .and_then(|k| k.strip_suffix(')'));
+        if let Some(inner) = inner {
+            return format!("{}({})", wrapper, rename_key(inner, rename));
+        }
+    }
+    rename(key)
+}
+
+// Converts a Chainsaw identifier into a Sigma one, keyless identifiers are not supported.
+fn sigma_identifier(
+    value: &Value,
+    resolve: &mut dyn FnMut(&str) -> crate::Result<String>,
+) -> crate::Result<Value> {
+    match value {
+        Value::Mapping(m) => {
+            let mut identifier = Yaml::new();
+            for (k, v) in m {
+                let k = match k.as_str() {
+                    Some(k) => k,
+                    None => anyhow::bail!("keys must be strings"),
+                };
+                let (k, v) = sigma_field(k, v, resolve)?;
+                identifier.insert(k.into(), v);
+            }
+            Ok(identifier.into())
+        }
+        Value::Sequence(s) => {
+            let mut scratch = vec![];
+            for v in s {
+                if !v.is_mapping() {
+                    anyhow::bail!("keyless identifiers cannot be converted");
+                }
+                scratch.push(sigma_identifier(v, resolve)?);
+            }
+            Ok(scratch.into())
+        }
+        _ => anyhow::bail!("keyless identifiers cannot be converted"),
+    }
+}
+
+fn sigma_field(
+    key: &str,
+    value: &Value,
+    resolve: &mut dyn FnMut(&str) -> crate::Result<String>,
+) -> crate::Result<(String, Value)> {
+    if let Some(rest) = key.strip_prefix(CIDR) {
+        let (field, cidrs) = match rest.split_once('|') {
+            Some(split) => split,
+            None => anyhow::bail!("invalid cidr field '{}'", key),
+        };
+        let cidrs = cidrs.split(',').map(Value::from).collect::<Vec<_>>();
+        let value = negatable(value, key, cidrs.into())?;
+        return Ok((format!("{}|cidr", resolve(field)?), value));
+    }
+    if let Some(rest) = key.strip_prefix(FIELDREF) {
+        let (field, others) = match rest.split_once('|') {
+            Some(split) => split,
+            None => anyhow::bail!("invalid fieldref field '{}'", key),
+        };
+        let mut fields = vec![];
+        for other in others.split(',') {
+            fields.push(Value::from(resolve(other)?));
+        }
+        let fields = if fields.len() == 1 {
+            fields.remove(0)
+        } else {
+            fields.into()
+        };
+        let value = negatable(value, key, fields)?;
+        return Ok((format!("{}|fieldref", resolve(field)?), value));
+    }
+    if let Some(rest) = key.strip_prefix(GLOB) {
+        let (field, globs) = match rest.split_once('|') {
+            Some(split) => split,
+            None => anyhow::bail!("invalid glob field '{}'", key),
+        };
+        // NOTE: Globs share their escapes with Sigma, apart from the pipe that separates them.
+        let mut globs = globs.chars();
+        let mut values = vec![];
+        let mut current = String::new();
+        while let Some(c) = globs.next() {
+            match c {
+                '\\' => match globs.next() {
+                    Some('|') => current.push('|'),
+                    Some(e) => {
+                        current.push('\\');
+                        current.push(e);
+                    }
+                    None => current.push('\\'),
+                },
+                '|' => values.push(Value::from(std::mem::take(&mut current))),
+                c => current.push(c),
+            }
+        }
+        values.push(current.into());
+        let values = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            values.into()
+        };
+        let value = negatable(value, key, values)?;
+        return Ok((resolve(field)?, value));
+    }
+    if let Some(field) = key.strip_prefix(EXISTS) {
+        let exists = match value {
+            Value::Bool(b) => *b,
+            Value::String(s) if s == "true" || s == "false" => s == "true",
+            _ => anyhow::bail!("invalid exists value for '{}'", key),
+        };
+        return Ok((format!("{}|exists", resolve(field)?), exists.into()));
+    }
+
+    let mut modifiers = vec![];
+    let mut field = key;
+    for wrapper in ["int", "str", "all", "not"] {
+        let inner = key
+            .strip_prefix(wrapper)
+            .and_then(|k| k.strip_prefix('('))
+            .and_then(|k| k.strip_suffix(')'));
+        if let Some(inner) = inner {
+            match wrapper {
+                "not" => anyhow::bail!("negated field '{}' cannot be converted", key),
+                "all" => modifiers.push("all"),
+                _ => {}
+            }
+            field = inner;
+            break;
+        }
+    }
+    if field.starts_with('@') {
+        anyhow::bail!("virtual field '{}' cannot be converted", field);
+    }
+
+    let values = match value {
+        Value::Sequence(s) => s.iter().collect::<Vec<_>>(),
+        v => vec![v],
+    };
+    let mut kind: Option<Vec<&str>> = None;
+    let mut scratch = vec![];
+    for value in values {
+        let (m, v) = sigma_value(value)?;
+        match &kind {
+            Some(k) if *k != m => {
+                anyhow::bail!(
+                    "field '{}' mixes match types which cannot be converted",
+                    key
+                )
+            }
+            Some(_) => {}
+            None => kind = Some(m),
+        }
+        scratch.push(v);
+    }
+    modifiers.extend(kind.unwrap_or_default());
+    let value = match value {
+        Value::Sequence(_) => scratch.into(),
+        _ => scratch.remove(0),
+    };
+    let mut key = resolve(field)?;
+    for modifier in modifiers {
+        key.push('|');
+        key.push_str(modifier);
+    }
+    Ok((key, value))
+}
+
+// The virtual fields only support being true, as false is their negation.
+fn negatable(value: &Value, key: &str, converted: Value) -> crate::Result<Value> {
+    match value {
+        Value::Bool(true) => Ok(converted),
+        Value::String(s) if s == "true" => Ok(converted),
+        _ => anyhow::bail!("negated field '{}' cannot be converted", key),
+    }
+}
+
+// Converts a Tau value into its Sigma value and the modifiers required to match it.
+fn sigma_value(value: &Value) -> crate::Result<(Vec<&'static str>, Value)> {
+    let s = match value {
+        Value::String(s) => s,
+        Value::Bool(_) | Value::Number(_) | Value::Null => return Ok((vec![], value.clone())),
+        _ => anyhow::bail!("unsupported value '{:?}'", value),
+    };
+    if s.starts_with("@list:") {
+        anyhow::bail!("lookup list '{}' cannot be converted", s);
+    }
+    let identifier = s.clone().into_identifier()?;
+    let cased = |mut modifiers: Vec<&'static str>| {
+        if !identifier.ignore_case {
+            modifiers.push("cased");
+        }
+        modifiers
+    };
+    let converted = match &identifier.pattern {
+        Pattern::Equal(i) => (vec![], Value::from(*i)),
+        Pattern::GreaterThan(i) => (vec!["gt"], Value::from(*i)),
+        Pattern::GreaterThanOrEqual(i) => (vec!["gte"], Value::from(*i)),
+        Pattern::LessThan(i) => (vec!["lt"], Value::from(*i)),
+        Pattern::LessThanOrEqual(i) => (vec!["lte"], Value::from(*i)),
+        Pattern::FEqual(f) => (vec![], Value::from(*f)),
+        Pattern::FGreaterThan(f) => (vec!["gt"], Value::from(*f)),
+        Pattern::FGreaterThanOrEqual(f) => (vec!["gte"], Value::from(*f)),
+        Pattern::FLessThan(f) => (vec!["lt"], Value::from(*f)),
+        Pattern::FLessThanOrEqual(f) => (vec!["lte"], Value::from(*f)),
+        Pattern::Any => (vec![], Value::from("*")),
+        Pattern::Regex(r) => {
+            let modifiers = if identifier.ignore_case {
+                vec!["re", "i"]
+            } else {
+                vec!["re"]
+            };
+            (modifiers, Value::from(r.as_str()))
+        }
+        Pattern::Contains(c) => (cased(vec!["contains"]), escape(c).into()),
+        Pattern::EndsWith(c) => (cased(vec!["endswith"]), escape(c).into()),
+        Pattern::StartsWith(c) => (cased(vec!["startswith"]), escape(c).into()),
+        Pattern::Exact(c) => (cased(vec![]), escape(c).into()),
+    };
+    Ok(converted)
+}
+
+// Escapes the characters that Sigma treats as wildcards, a backslash is only special when it is
+// followed by a wildcard or another backslash.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        let special = match c {
+            '*' | '?' => true,
+            '\\' => matches!(chars.peek(), Some('*' | '?' | '\\')),
+            _ => false,
+        };
+        if special {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,165 @@
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use rustc_hash::{FxHashMap, FxHasher};
+use tau_engine::Document;
+
+use crate::file::Kind as FileKind;
+use crate::value::Value;
+
+const EVTX_KEYS: &[&str] = &[
+    "Event.System.Computer",
+    "Event.System.Channel",
+    "Event.System.EventRecordID",
+    "Event.System.TimeCreated",
+];
+
+/// Tracks the identity of the documents that were detected across a hunt so that records which
+/// appear in more than one source (i.e. archived or VSS recovered copies of a log) are only
+/// reported once. Documents that were not detected are not tracked, as their copies will not be
+/// detected either, which keeps the memory used in line with the number of detections.
+#[derive(Default)]
+pub struct Dedup {
+    keys: Vec<String>,
+
+    collisions: AtomicUsize,
+    duplicates: Mutex<FxHashMap<u64, Vec<PathBuf>>>,
+    files: Mutex<Vec<PathBuf>>,
+    seen: Mutex<FxHashMap<u64, usize>>,
+}
+
+impl Dedup {
+    pub fn new(keys: Vec<String>) -> Self {
+        Self {
+            keys,
+            ..Default::default()
+        }
+    }
+
+    /// The number of documents that shared their identity with another document in the same file.
+    pub fn collisions(&self) -> usize {
+        self.collisions.load(Ordering::Relaxed)
+    }
+
+    /// The number of records that were found in more than one source.
+    pub fn duplicated(&self) -> usize {
+        self.duplicates
+            .lock()
+            .expect("could not lock duplicates")
+            .len()
+    }
+
+    pub fn duplicates(&self, identity: &u64) -> Option<Vec<PathBuf>> {
+        self.duplicates
+            .lock()
+            .expect("could not lock duplicates")
+            .get(identity)
+            .cloned()
+    }
+
+    pub fn identity(&self, kind: &FileKind, value: &Value) -> Option<u64> {
+        let mut hasher = FxHasher::default();
+        match kind {
+            FileKind::Evtx => {
+                let wrapper = crate::evtx::Wrapper(value);
+                for key in EVTX_KEYS {
+                    wrapper.find(key)?.to_string()?.hash(&mut hasher);
+                }
+            }
+            _ => {
+                if self.keys.is_empty() {
+                    return None;
+                }
+                for key in &self.keys {
+                    value.find(key)?.to_string()?.hash(&mut hasher);
+                }
+            }
+        }
+        Some(hasher.finish())
+    }
+
+    pub fn register(&self, file: &Path) -> usize {
+        let mut files = self.files.lock().expect("could not lock files");
+        files.push(file.to_path_buf());
+        files.len() - 1
+    }
+
+    /// Returns true if the identity has already been seen in a different file, recording the source
+    /// against the first occurrence. Documents in the same file are never copies of each other, so
+    /// they are kept and only counted, as it means that the keys do not identify a record.
+    pub fn seen(&self, identity: u64, index: usize) -> bool {
+        let first = {
+            let mut seen = self.seen.lock().expect("could not lock seen");
+            match seen.get(&identity) {
+                Some(first) => *first,
+                None => {
+                    seen.insert(identity, index);
+                    return false;
+                }
+            }
+        };
+        if first == index {
+            self.collisions.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        let path = self.files.lock().expect("could not lock files")[index].clone();
+        let mut duplicates = self.duplicates.lock().expect("could not lock duplicates");
+        let paths = duplicates.entry(identity).or_insert(vec![]);
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_identity() {
+        let dedup = Dedup::new(vec!["host".to_owned(), "id".to_owned()]);
+        let a = Value::from(json!({"host": "a", "id": 1, "message": "x"}));
+        let b = Value::from(json!({"host": "a", "id": 1, "message": "y"}));
+        let c = Value::from(json!({"host": "a", "id": 2}));
+        let d = Value::from(json!({"host": "a"}));
+        let identity = dedup.identity(&FileKind::Json, &a);
+        assert!(identity.is_some());
+        assert_eq!(identity, dedup.identity(&FileKind::Json, &b));
+        assert_ne!(identity, dedup.identity(&FileKind::Json, &c));
+        assert_eq!(dedup.identity(&FileKind::Json, &d), None);
+
+        let dedup = Dedup::new(vec![]);
+        assert_eq!(dedup.identity(&FileKind::Json, &a), None);
+    }
+
+    #[test]
+    fn test_seen() {
+        let dedup = Dedup::new(vec![]);
+        let live = dedup.register(Path::new("Security.evtx"));
+        let vss = dedup.register(Path::new("vss/Security.evtx"));
+        let archive = dedup.register(Path::new("Archive-Security.evtx"));
+
+        assert!(!dedup.seen(1, live));
+        assert!(dedup.seen(1, vss));
+        assert!(dedup.seen(1, archive));
+        assert!(dedup.seen(1, vss));
+        assert_eq!(
+            dedup.duplicates(&1),
+            Some(vec![
+                PathBuf::from("vss/Security.evtx"),
+                PathBuf::from("Archive-Security.evtx")
+            ])
+        );
+
+        // NOTE: Documents in the same file are kept, but counted as collisions.
+        assert!(!dedup.seen(2, vss));
+        assert!(!dedup.seen(2, vss));
+        assert_eq!(dedup.duplicates(&2), None);
+        assert_eq!(dedup.collisions(), 1);
+    }
+}
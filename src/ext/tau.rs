@@ -79,6 +79,79 @@ pub fn extract_fields(expression: &Expression) -> HashSet<String> {
     set
 }
 
+/// Resolves the expression when the provided fields are missing from every document, returning
+/// `None` when the result still depends on the fields that are present.
+pub fn solve_missing(expression: &Expression, missing: &HashSet<String>) -> Option<bool> {
+    match expression {
+        Expression::Boolean(b) => Some(*b),
+        Expression::BooleanGroup(BoolSym::And, expressions) => {
+            let mut all = true;
+            for expression in expressions {
+                match solve_missing(expression, missing) {
+                    Some(false) => return Some(false),
+                    Some(true) => {}
+                    None => all = false,
+                }
+            }
+            if all {
+                Some(true)
+            } else {
+                None
+            }
+        }
+        Expression::BooleanGroup(BoolSym::Or, expressions) => {
+            let mut all = true;
+            for expression in expressions {
+                match solve_missing(expression, missing) {
+                    Some(true) => return Some(true),
+                    Some(false) => {}
+                    None => all = false,
+                }
+            }
+            if all {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        Expression::BooleanExpression(left, BoolSym::And, right) => {
+            match (solve_missing(left, missing), solve_missing(right, missing)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            }
+        }
+        Expression::BooleanExpression(left, BoolSym::Or, right) => {
+            match (solve_missing(left, missing), solve_missing(right, missing)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            }
+        }
+        Expression::Negate(e) => solve_missing(e, missing).map(|b| !b),
+        // NOTE: Anything that reads from a document fails when all of its fields are missing.
+        Expression::BooleanExpression(..)
+        | Expression::Cast(..)
+        | Expression::Field(_)
+        | Expression::Match(..)
+        | Expression::Matrix(..)
+        | Expression::Nested(..)
+        | Expression::Search(..) => {
+            let fields = extract_fields(expression);
+            if !fields.is_empty() && fields.iter().all(|f| missing.contains(f)) {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        Expression::BooleanGroup(..)
+        | Expression::Float(_)
+        | Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Null => None,
+    }
+}
+
 pub fn update_fields(expression: Expression, lookup: &HashMap<String, String>) -> Expression {
     match expression {
         Expression::BooleanGroup(x, expressions) => {
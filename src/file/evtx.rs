@@ -1,5 +1,6 @@
 use std::fs::File;
 use std::path::Path;
+use std::sync::Arc;
 
 use evtx::{err::EvtxError, EvtxParser, ParserSettings, SerializedEvtxRecord};
 use regex::RegexSet;
@@ -13,6 +14,9 @@ pub type Evtx = SerializedEvtxRecord<Json>;
 
 pub struct Parser {
     pub inner: EvtxParser<File>,
+
+    after: Option<u64>,
+    settings: Arc<ParserSettings>,
 }
 
 impl Parser {
@@ -20,14 +24,53 @@ impl Parser {
         let settings = ParserSettings::default()
             .separate_json_attributes(true)
             .num_threads(0);
-        let parser = EvtxParser::from_path(file)?.with_configuration(settings);
-        Ok(Self { inner: parser })
+        let parser = EvtxParser::from_path(file)?.with_configuration(settings.clone());
+        Ok(Self {
+            inner: parser,
+            after: None,
+            settings: Arc::new(settings),
+        })
+    }
+
+    /// Only parse records with an id greater than the one provided.
+    pub fn after(&mut self, record: u64) {
+        self.after = Some(record);
     }
 
     pub fn parse(
         &mut self,
-    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<serde_json::Value>, EvtxError>> + '_ {
-        self.inner.records_json_value()
+    ) -> Box<
+        dyn Iterator<Item = Result<SerializedEvtxRecord<serde_json::Value>, EvtxError>>
+            + Send
+            + Sync
+            + '_,
+    > {
+        let after = match self.after {
+            Some(after) => after,
+            None => return Box::new(self.inner.records_json_value()),
+        };
+        let settings = self.settings.clone();
+        Box::new(self.inner.chunks().flat_map(move |chunk| {
+            let mut chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => return vec![Err(e)],
+            };
+            // NOTE: Record ids are sequential, so we can skip whole chunks without parsing them.
+            if chunk.header.last_event_record_id <= after {
+                return vec![];
+            }
+            let mut chunk = match chunk.parse(settings.clone()) {
+                Ok(chunk) => chunk,
+                Err(e) => return vec![Err(e)],
+            };
+            chunk
+                .iter()
+                .filter_map(|record| match record.and_then(|r| r.into_json_value()) {
+                    Ok(record) if record.event_record_id <= after => None,
+                    record => Some(record),
+                })
+                .collect()
+        }))
     }
 }
 
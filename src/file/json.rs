@@ -62,10 +62,12 @@ pub mod lines {
     use super::*;
 
     use std::io::prelude::*;
-    use std::io::Lines;
+    use std::io::SeekFrom;
 
     pub struct Parser {
         pub inner: Option<BufReader<File>>,
+
+        offset: u64,
     }
 
     impl Parser {
@@ -80,36 +82,55 @@ pub mod lines {
             reader.rewind()?;
             Ok(Self {
                 inner: Some(reader),
+                offset: 0,
             })
         }
 
+        /// The offset of the end of the last line that was parsed.
+        pub fn offset(&self) -> u64 {
+            self.offset
+        }
+
+        /// Start parsing from the provided offset, which must be the start of a line.
+        pub fn seek(&mut self, offset: u64) -> crate::Result<()> {
+            if let Some(reader) = &mut self.inner {
+                reader.seek(SeekFrom::Start(offset))?;
+                self.offset = offset;
+            }
+            Ok(())
+        }
+
         pub fn parse(&mut self) -> impl Iterator<Item = Result<Json, Error>> + '_ {
             if let Some(file) = self.inner.take() {
-                return ParserIter(Some(file.lines()));
+                return ParserIter(Some((file, &mut self.offset)));
             }
             ParserIter(None)
         }
     }
 
-    struct ParserIter(Option<Lines<BufReader<File>>>);
+    struct ParserIter<'a>(Option<(BufReader<File>, &'a mut u64)>);
 
-    impl Iterator for ParserIter {
+    impl Iterator for ParserIter<'_> {
         type Item = Result<Json, Error>;
 
         fn next(&mut self) -> Option<Self::Item> {
-            match &mut self.0 {
-                Some(i) => i.next().map(|l| match l {
-                    Ok(l) => serde_json::from_str(l.as_str()).map_err(Error::from),
-                    Err(e) => Err(Error::from(e)),
-                }),
-                None => None,
-            }
-        }
-
-        fn size_hint(&self) -> (usize, Option<usize>) {
-            match &self.0 {
-                Some(i) => i.size_hint(),
-                None => (0, Some(0)),
+            let (reader, offset) = self.0.as_mut()?;
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line) {
+                    Ok(0) => return None,
+                    Ok(read) => {
+                        **offset += read as u64;
+                        // NOTE: Blank lines are skipped, so that a file missing its final newline
+                        // can still be resumed once it has been appended to.
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        return Some(serde_json::from_str(line.as_str()).map_err(Error::from));
+                    }
+                    Err(e) => return Some(Err(Error::from(e))),
+                }
             }
         }
     }
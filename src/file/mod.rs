@@ -333,6 +333,29 @@ impl Reader {
         Documents { iterator }
     }
 
+    /// Resume parsing after the provided record, this is only supported by EVTX files.
+    pub fn resume(&mut self, record: u64) {
+        if let Parser::Evtx(parser) = &mut self.parser {
+            parser.after(record);
+        }
+    }
+
+    /// Resume parsing from the provided offset, this is only supported by JSONL files.
+    pub fn seek(&mut self, offset: u64) -> crate::Result<()> {
+        if let Parser::Jsonl(parser) = &mut self.parser {
+            parser.seek(offset)?;
+        }
+        Ok(())
+    }
+
+    /// The offset that parsing reached, this is only supported by JSONL files.
+    pub fn offset(&self) -> Option<u64> {
+        match &self.parser {
+            Parser::Jsonl(parser) => Some(parser.offset()),
+            _ => None,
+        }
+    }
+
     pub fn kind(&self) -> Kind {
         match self.parser {
             Parser::Evtx(_) => Kind::Evtx,
@@ -1,10 +1,13 @@
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::hash::{BuildHasherDefault, Hash, Hasher};
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use chrono_tz::Tz;
@@ -23,11 +26,20 @@ use tau_engine::{
 };
 use uuid::Uuid;
 
+use crate::dedup::Dedup;
 use crate::file::{Document as File, Kind as FileKind, Reader};
+use crate::lists::Lists;
+use crate::modifiers::Compiled;
+use crate::profile::Profile;
 use crate::rule::{
-    chainsaw::{Container, Field, Format},
-    Aggregate, Filter, Kind as RuleKind, Rule,
+    chainsaw::{Container, Field, Transform},
+    sigma::LogSource as SigmaLogSource,
+    yara::{Match as YaraMatch, Scanner as YaraScanner, DEFAULT_FIELDS as YARA_FIELDS},
+    Aggregate, Filter, Kind as RuleKind, Matched, Rule,
 };
+use crate::state::{digest, Saved, State};
+use crate::suppress::Suppression;
+use crate::timestamp::Format as TimestampFormat;
 use crate::value::Value;
 
 #[derive(Clone, Deserialize)]
@@ -44,6 +56,51 @@ pub struct Extensions {
     preconditions: Option<Vec<Precondition>>,
 }
 
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Values {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Values {
+    fn values(&self) -> Vec<&str> {
+        match self {
+            Self::One(v) => vec![v.as_str()],
+            Self::Many(v) => v.iter().map(|v| v.as_str()).collect(),
+        }
+    }
+}
+
+/// The Sigma logsource that a group provides, a rule only runs under a group when the group
+/// provides every part of the logsource that the rule sets.
+#[derive(Clone, Deserialize)]
+pub struct Logsource {
+    #[serde(default)]
+    category: Option<Values>,
+    #[serde(default)]
+    product: Option<Values>,
+    #[serde(default)]
+    service: Option<Values>,
+}
+
+impl Logsource {
+    pub(crate) fn provides(&self, logsource: Option<&SigmaLogSource>) -> bool {
+        let logsource = match logsource {
+            Some(logsource) => logsource,
+            None => return true,
+        };
+        crate::rule::sigma::within(
+            logsource.parts(),
+            [
+                self.category.as_ref().map(|v| v.values()),
+                self.product.as_ref().map(|v| v.values()),
+                self.service.as_ref().map(|v| v.values()),
+            ],
+        )
+    }
+}
+
 #[derive(Clone, Deserialize)]
 pub struct Group {
     #[serde(skip, default = "Uuid::new_v4")]
@@ -53,6 +110,14 @@ pub struct Group {
     pub filter: Expression,
     pub name: String,
     pub timestamp: String,
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+    #[serde(default)]
+    pub logsource: Option<Logsource>,
+
+    /// The rules that the group runs, when unset it runs all of the rules of its kind.
+    #[serde(skip)]
+    pub routed: Option<HashSet<Uuid, BuildHasherDefault<FxHasher>>>,
 }
 
 #[derive(Deserialize)]
@@ -67,10 +132,47 @@ pub struct Mapping {
     pub rules: RuleKind,
 }
 
+/// How well a Sigma rule is supported by the fields of a mapping group.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Coverage {
+    Excluded,
+    Unmapped,
+    Partial,
+    Full,
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Excluded => write!(f, "excluded"),
+            Self::Unmapped => write!(f, "unmapped"),
+            Self::Partial => write!(f, "partial"),
+            Self::Full => write!(f, "full"),
+        }
+    }
+}
+
+/// Describes how a Sigma rule would run under a mapping group.
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub group: String,
+    pub coverage: Coverage,
+    pub precondition: bool,
+    pub unmapped: Vec<String>,
+}
+
 pub struct Hit {
     pub hunt: Uuid,
     pub rule: Uuid,
     pub timestamp: NaiveDateTime,
+    pub suppressed: Option<Uuid>,
+    // NOTE: Only set for YARA rules.
+    pub strings: Vec<YaraMatch>,
+    // NOTE: Only set for intel rules, these are the ids of the indicators that matched.
+    pub indicators: Vec<String>,
+    // NOTE: Only set when match explanations are enabled, and never for aggregates.
+    pub matched: Option<Matched>,
 }
 
 pub struct Detections<'a> {
@@ -85,6 +187,9 @@ pub struct Document<'a> {
     pub path: &'a Path,
     // NOTE: Serialised Value using bincode.
     pub data: Vec<u8>,
+    // NOTE: Only set when deduplication is enabled.
+    pub identity: Option<u64>,
+    pub duplicates: Vec<PathBuf>,
 }
 
 impl<'a> Serialize for Document<'a> {
@@ -92,13 +197,17 @@ impl<'a> Serialize for Document<'a> {
     where
         S: Serializer,
     {
-        // 3 is the number of fields in the struct.
-        let mut state = serializer.serialize_struct("Document", 3)?;
+        // 3 is the number of fields in the struct, plus the optional duplicates.
+        let len = if self.duplicates.is_empty() { 3 } else { 4 };
+        let mut state = serializer.serialize_struct("Document", len)?;
         state.serialize_field("kind", &self.kind)?;
         state.serialize_field("path", &self.path)?;
         let value: Value = bincode::deserialize(&self.data).expect("could not decompress");
         let json = Json::from(value);
         state.serialize_field("data", &json)?;
+        if !self.duplicates.is_empty() {
+            state.serialize_field("duplicates", &self.duplicates)?;
+        }
         state.end()
     }
 }
@@ -112,12 +221,22 @@ pub enum Kind<'a> {
 
 #[derive(Default)]
 pub struct HunterBuilder {
+    lists: Option<Lists>,
     mappings: Option<Vec<PathBuf>>,
     rules: Option<Vec<Rule>>,
+    sources: Option<Vec<PathBuf>>,
+    state: Option<PathBuf>,
+    suppressions: Option<Vec<PathBuf>>,
+    yara: Option<Vec<PathBuf>>,
+    yara_fields: Option<Vec<String>>,
 
+    dedup: Option<bool>,
+    dedup_keys: Option<Vec<String>>,
     load_unknown: Option<bool>,
     local: Option<bool>,
+    matches: Option<bool>,
     preprocess: Option<bool>,
+    profile: Option<bool>,
     from: Option<NaiveDateTime>,
     skip_errors: Option<bool>,
     timezone: Option<Tz>,
@@ -130,12 +249,20 @@ impl HunterBuilder {
     }
 
     pub fn build(self) -> crate::Result<Hunter> {
+        let mut sources = self.sources.unwrap_or_default();
+        for paths in [&self.mappings, &self.suppressions, &self.yara] {
+            sources.extend(paths.iter().flatten().cloned());
+        }
+        let matches = self.matches.unwrap_or_default();
         let mut hunts = vec![];
         let mut rules = match self.rules {
             Some(mut rules) => {
                 rules.sort_by(|x, y| x.name().cmp(y.name()));
                 let mut map = BTreeMap::new();
-                for rule in rules {
+                for mut rule in rules {
+                    if !matches {
+                        rule.clear_identifiers();
+                    }
                     let uuid = Uuid::new_v4();
                     if let Rule::Chainsaw(rule) = &rule {
                         let mapper = Mapper::from(rule.fields.clone());
@@ -148,6 +275,7 @@ impl HunterBuilder {
                                 filter: rule.filter.clone(),
                             },
                             timestamp: rule.timestamp.clone(),
+                            timestamp_format: rule.timestamp_format.clone(),
 
                             file: rule.kind.clone(),
                             mapper,
@@ -159,22 +287,89 @@ impl HunterBuilder {
             }
             None => BTreeMap::new(),
         };
+        let mut yara = match self.yara {
+            Some(mut paths) => {
+                paths.sort();
+                let scanner = match YaraScanner::load(&paths) {
+                    Ok(scanner) => scanner,
+                    Err(e) => anyhow::bail!("Provided YARA rules are invalid - {}", e),
+                };
+                let mut lookup = FxHashMap::default();
+                let loaded = match scanner.rules() {
+                    Ok(rules) => rules,
+                    Err(e) => anyhow::bail!("Provided YARA rules are invalid - {}", e),
+                };
+                for rule in loaded {
+                    let uuid = Uuid::new_v4();
+                    lookup.insert((rule.namespace.clone(), rule.name.clone()), uuid);
+                    rules.insert(uuid, Rule::Yara(rule));
+                }
+                let fields = match self.yara_fields {
+                    Some(fields) if !fields.is_empty() => fields,
+                    _ => YARA_FIELDS.iter().map(|f| f.to_string()).collect(),
+                };
+                Some(Yara {
+                    fields: fields.into_iter().map(|f| (f, vec![])).collect(),
+                    hunt: Uuid::new_v4(),
+                    rules: lookup,
+                    scanner,
+                    timestamps: vec![],
+                })
+            }
+            None => None,
+        };
         if let Some(mut mappings) = self.mappings {
             mappings.sort();
             for mapping in mappings {
+                let base = mapping.parent().map(|p| p.to_path_buf());
                 let mut file = match fs::File::open(mapping) {
                     Ok(a) => a,
                     Err(e) => anyhow::bail!("Error loading specified mapping file - {}", e),
                 };
                 let mut content = String::new();
                 file.read_to_string(&mut content)?;
-                let mut mapping: Mapping = match serde_yaml::from_str(&content) {
+                let mut mapping: Mapping = match crate::pipeline::load(&content, &rules) {
                     Ok(a) => a,
                     Err(e) => anyhow::bail!("Provided mapping file is invalid - {}", e),
                 };
                 if let RuleKind::Chainsaw = mapping.rules {
                     anyhow::bail!("Chainsaw rules do not support mappings");
                 }
+                if let RuleKind::Yara = mapping.rules {
+                    anyhow::bail!("YARA rules do not support mappings");
+                }
+                if let Some(yara) = yara.as_mut() {
+                    yara.rules.retain(|_, rid| {
+                        !rules
+                            .get(rid)
+                            .map(|r| mapping.exclusions.contains(r.name()))
+                            .unwrap_or_default()
+                    });
+                }
+                for group in &mut mapping.groups {
+                    let filter = std::mem::replace(&mut group.filter, Expression::Null);
+                    group.filter = match crate::lists::rewrite(filter, base.as_deref())
+                        .and_then(|filter| crate::modifiers::check(&filter).map(|_| filter))
+                    {
+                        Ok(filter) => filter,
+                        Err(e) => anyhow::bail!("Provided mapping file is invalid - {}", e),
+                    };
+                }
+                if let Some(preconditions) = mapping
+                    .extensions
+                    .as_mut()
+                    .and_then(|e| e.preconditions.as_mut())
+                {
+                    for precondition in preconditions {
+                        let filter = std::mem::replace(&mut precondition.filter, Expression::Null);
+                        precondition.filter = match crate::lists::rewrite(filter, base.as_deref())
+                            .and_then(|filter| crate::modifiers::check(&filter).map(|_| filter))
+                        {
+                            Ok(filter) => filter,
+                            Err(e) => anyhow::bail!("Provided mapping file is invalid - {}", e),
+                        };
+                    }
+                }
                 let mut preconds = FxHashMap::default();
                 if let Some(extensions) = &mapping.extensions {
                     if let Some(preconditions) = &extensions.preconditions {
@@ -217,6 +412,24 @@ impl HunterBuilder {
                             exclusions.insert(*rid);
                         }
                     }
+                    // NOTE: Groups from pipelines are already routed, otherwise groups that declare
+                    // a logsource only run the rules that it provides.
+                    let routed = match (group.routed, &group.logsource) {
+                        (Some(routed), _) => Some(routed),
+                        (None, Some(logsource)) => Some(
+                            rules
+                                .iter()
+                                .filter(|(_, rule)| match rule {
+                                    Rule::Sigma(sigma) => {
+                                        logsource.provides(sigma.logsource.as_ref())
+                                    }
+                                    _ => false,
+                                })
+                                .map(|(rid, _)| *rid)
+                                .collect(),
+                        ),
+                        (None, None) => None,
+                    };
                     let mapper = Mapper::from(group.fields);
                     hunts.push(Hunt {
                         id: group.id,
@@ -227,8 +440,10 @@ impl HunterBuilder {
                             filter: group.filter,
                             kind: mapping.rules.clone(),
                             preconditions: preconds.clone(),
+                            routed,
                         },
                         timestamp: group.timestamp,
+                        timestamp_format: group.timestamp_format,
 
                         file: mapping.kind.clone(),
                         mapper,
@@ -236,11 +451,131 @@ impl HunterBuilder {
                 }
             }
         }
+        if let Some(yara) = yara.as_mut() {
+            let groups = hunts
+                .iter()
+                .filter(|h| matches!(h.kind, HuntKind::Group { .. }))
+                .collect::<Vec<_>>();
+            for (name, mappers) in &mut yara.fields {
+                let mut paths = HashSet::new();
+                for field in groups.iter().flat_map(|h| h.mapper.fields()) {
+                    if &field.from != name {
+                        continue;
+                    }
+                    // NOTE: Most groups map a field to the same path, which only needs to be
+                    // looked up once.
+                    if field.cast.is_none()
+                        && field.container.is_none()
+                        && field.transforms.is_empty()
+                        && !paths.insert(field.to.clone())
+                    {
+                        continue;
+                    }
+                    mappers.push(Mapper::from(vec![field.clone()]));
+                }
+                if mappers.is_empty() {
+                    mappers.push(Mapper::from(vec![]));
+                }
+            }
+            for hunt in &groups {
+                let timestamp = (
+                    hunt.file.clone(),
+                    hunt.timestamp.clone(),
+                    hunt.timestamp_format.clone(),
+                );
+                if !yara.timestamps.contains(&timestamp) {
+                    yara.timestamps.push(timestamp);
+                }
+            }
+            if !yara.timestamps.iter().any(|(k, _, _)| k == &FileKind::Evtx) {
+                yara.timestamps.push((
+                    FileKind::Evtx,
+                    "Event.System.TimeCreated".to_owned(),
+                    TimestampFormat::default(),
+                ));
+            }
+        }
+
+        let mut suppressions = vec![];
+        if let Some(mut paths) = self.suppressions {
+            paths.sort();
+            for path in paths {
+                match crate::suppress::load(&path) {
+                    Ok(s) => suppressions.extend(s),
+                    Err(e) => anyhow::bail!("Provided suppression file is invalid - {}", e),
+                }
+            }
+        }
 
+        let state = match self.state {
+            Some(path) => match State::load(&path) {
+                Ok(mut state) => {
+                    state.rules(digest(sources, rules.values().map(|r| r.name()))?);
+                    Some(Mutex::new(state))
+                }
+                Err(e) => anyhow::bail!("Provided state file is invalid - {}", e),
+            },
+            None => None,
+        };
+
+        let dedup = if self.dedup.unwrap_or_default() {
+            Some(Dedup::new(self.dedup_keys.unwrap_or_default()))
+        } else {
+            None
+        };
         let load_unknown = self.load_unknown.unwrap_or_default();
         let local = self.local.unwrap_or_default();
         let preprocess = self.preprocess.unwrap_or_default();
+        let profile = self.profile.unwrap_or_default();
         let skip_errors = self.skip_errors.unwrap_or_default();
+        if matches && preprocess {
+            anyhow::bail!("Match explanations are not supported when preprocessing");
+        }
+
+        // NOTE: The values of virtual fields are compiled, and the lists that they reference loaded,
+        // once for the hunter and shared between the mappers that resolve them.
+        let mut compiled = Compiled::default();
+        let mut lists = self.lists.unwrap_or_default();
+        let mut expressions = vec![];
+        for hunt in &hunts {
+            if let HuntKind::Group {
+                filter,
+                preconditions,
+                ..
+            } = &hunt.kind
+            {
+                expressions.push(filter);
+                expressions.extend(preconditions.values());
+            }
+        }
+        for rule in rules.values() {
+            match rule {
+                Rule::Chainsaw(c) => match &c.filter {
+                    Filter::Detection(d) => {
+                        expressions.push(&d.expression);
+                        expressions.extend(d.identifiers.values());
+                    }
+                    Filter::Expression(e) => expressions.push(e),
+                },
+                Rule::Sigma(s) => {
+                    expressions.push(&s.tau.detection.expression);
+                    expressions.extend(s.tau.detection.identifiers.values());
+                }
+                Rule::Yara(_) => {}
+            }
+        }
+        for expression in expressions {
+            for field in crate::ext::tau::extract_fields(expression) {
+                compiled.insert(&field)?;
+                lists.load(&field)?;
+            }
+        }
+        let compiled = Arc::new(compiled);
+        let lists = Arc::new(lists);
+        for hunt in &mut hunts {
+            hunt.mapper.compiled = compiled.clone();
+            hunt.mapper.lists = lists.clone();
+        }
 
         let mut fields = vec![];
         if preprocess {
@@ -271,6 +606,7 @@ impl HunterBuilder {
                             keys.extend(crate::ext::tau::extract_fields(precondition));
                         }
                     }
+                    HuntKind::Yara => {}
                 }
             }
             for rule in rules.values() {
@@ -294,6 +630,18 @@ impl HunterBuilder {
                         }
                         keys.extend(crate::ext::tau::extract_fields(&s.tau.detection.expression));
                     }
+                    Rule::Yara(_) => {}
+                }
+            }
+            for suppression in &suppressions {
+                match &suppression.filter {
+                    Some(Filter::Detection(d)) => {
+                        keys.extend(crate::ext::tau::extract_fields(&d.expression));
+                    }
+                    Some(Filter::Expression(e)) => {
+                        keys.extend(crate::ext::tau::extract_fields(e));
+                    }
+                    None => {}
                 }
             }
 
@@ -347,6 +695,7 @@ impl HunterBuilder {
                             filter,
                             kind,
                             preconditions,
+                            routed,
                         } => HuntKind::Group {
                             exclusions,
                             filter: crate::ext::tau::update_fields(filter, &lookup),
@@ -355,7 +704,9 @@ impl HunterBuilder {
                                 .into_iter()
                                 .map(|(i, p)| (i, crate::ext::tau::update_fields(p, &lookup)))
                                 .collect(),
+                            routed,
                         },
+                        HuntKind::Yara => HuntKind::Yara,
                     };
                     h
                 })
@@ -398,21 +749,65 @@ impl HunterBuilder {
                                 crate::ext::tau::update_fields(s.tau.detection.expression, &lookup);
                             Rule::Sigma(s)
                         }
+                        Rule::Yara(y) => Rule::Yara(y),
                     };
                     (i, r)
                 })
                 .collect();
+            suppressions = suppressions
+                .into_iter()
+                .map(|mut s| {
+                    s.filter = match s.filter {
+                        Some(Filter::Detection(mut d)) => {
+                            d.expression = crate::ext::tau::update_fields(d.expression, &lookup);
+                            Some(Filter::Detection(d))
+                        }
+                        Some(Filter::Expression(e)) => Some(Filter::Expression(
+                            crate::ext::tau::update_fields(e, &lookup),
+                        )),
+                        None => None,
+                    };
+                    s
+                })
+                .collect();
         }
+        // NOTE: YARA rules are scanned once per document rather than through a hunt, this one only
+        // exists so that their detections have a group.
+        if let Some(yara) = &yara {
+            hunts.push(Hunt {
+                id: yara.hunt,
+
+                group: "YARA".to_owned(),
+                kind: HuntKind::Yara,
+                timestamp: String::new(),
+                timestamp_format: TimestampFormat::default(),
+
+                file: FileKind::Unknown,
+                mapper: Mapper::from(vec![]),
+            });
+        }
+
+        let profile = if profile {
+            Some(Profile::new(&hunts, &rules))
+        } else {
+            None
+        };
 
         Ok(Hunter {
             inner: HunterInner {
                 hunts,
                 fields,
+                profile,
                 rules,
+                state,
+                suppressions,
+                yara,
 
+                dedup,
                 from: self.from.map(|d| DateTime::from_utc(d, Utc)),
                 load_unknown,
                 local,
+                matches,
                 preprocess,
                 skip_errors,
                 timezone: self.timezone,
@@ -421,11 +816,27 @@ impl HunterBuilder {
         })
     }
 
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
+    pub fn dedup_keys(mut self, keys: Vec<String>) -> Self {
+        self.dedup_keys = Some(keys);
+        self
+    }
+
     pub fn from(mut self, datetime: NaiveDateTime) -> Self {
         self.from = Some(datetime);
         self
     }
 
+    /// The lists that were built in memory, i.e. from intel, that the rules reference.
+    pub fn lists(mut self, lists: Lists) -> Self {
+        self.lists = Some(lists);
+        self
+    }
+
     pub fn load_unknown(mut self, allow: bool) -> Self {
         self.load_unknown = Some(allow);
         self
@@ -436,11 +847,21 @@ impl HunterBuilder {
         self
     }
 
+    pub fn matches(mut self, matches: bool) -> Self {
+        self.matches = Some(matches);
+        self
+    }
+
     pub fn preprocess(mut self, preprocess: bool) -> Self {
         self.preprocess = Some(preprocess);
         self
     }
 
+    pub fn profile(mut self, profile: bool) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
     pub fn mappings(mut self, paths: Vec<PathBuf>) -> Self {
         self.mappings = Some(paths);
         self
@@ -456,6 +877,23 @@ impl HunterBuilder {
         self
     }
 
+    /// The files that the rules were loaded from, used to detect when the rules have changed
+    /// between hunts that share a state file.
+    pub fn sources(mut self, paths: Vec<PathBuf>) -> Self {
+        self.sources = Some(paths);
+        self
+    }
+
+    pub fn state(mut self, path: PathBuf) -> Self {
+        self.state = Some(path);
+        self
+    }
+
+    pub fn suppressions(mut self, paths: Vec<PathBuf>) -> Self {
+        self.suppressions = Some(paths);
+        self
+    }
+
     pub fn timezone(mut self, tz: Tz) -> Self {
         self.timezone = Some(tz);
         self
@@ -465,6 +903,16 @@ impl HunterBuilder {
         self.to = Some(datetime);
         self
     }
+
+    pub fn yara(mut self, paths: Vec<PathBuf>) -> Self {
+        self.yara = Some(paths);
+        self
+    }
+
+    pub fn yara_fields(mut self, fields: Vec<String>) -> Self {
+        self.yara_fields = Some(fields);
+        self
+    }
 }
 
 pub enum HuntKind {
@@ -473,22 +921,26 @@ pub enum HuntKind {
         filter: Expression,
         kind: RuleKind,
         preconditions: FxHashMap<Uuid, Expression>,
+        routed: Option<HashSet<Uuid, BuildHasherDefault<FxHasher>>>,
     },
     Rule {
         aggregate: Option<Aggregate>,
         filter: Filter,
     },
+    Yara,
 }
 
 pub enum MapperKind {
     None,
     Fast(FxHashMap<String, String>),
-    Full(FxHashMap<String, (String, Option<Container>, Option<ModSym>)>),
+    Full(FxHashMap<String, (String, Option<Container>, Option<ModSym>, Vec<Transform>)>),
 }
 
 pub struct Mapper {
+    compiled: Arc<Compiled>,
     fields: Vec<Field>,
     kind: MapperKind,
+    lists: Arc<Lists>,
 }
 
 impl Mapper {
@@ -496,7 +948,7 @@ impl Mapper {
         let mut fast = false;
         let mut full = false;
         for field in &fields {
-            if field.cast.is_some() || field.container.is_some() {
+            if field.cast.is_some() || field.container.is_some() || !field.transforms.is_empty() {
                 full = true;
                 break;
             }
@@ -516,6 +968,7 @@ impl Mapper {
                         field.to.clone(),
                         field.container.clone(),
                         field.cast.clone(),
+                        field.transforms.clone(),
                     ),
                 );
             }
@@ -532,7 +985,12 @@ impl Mapper {
         } else {
             MapperKind::None
         };
-        Self { fields, kind }
+        Self {
+            compiled: Arc::default(),
+            fields,
+            kind,
+            lists: Arc::default(),
+        }
     }
 
     pub fn fields(&self) -> &Vec<Field> {
@@ -558,6 +1016,19 @@ pub struct Mapped<'a> {
 }
 impl<'a> TauDocument for Mapped<'a> {
     fn find(&self, key: &str) -> Option<Tau<'_>> {
+        if key.starts_with('@') {
+            if let Some(key) = key.strip_prefix(crate::lists::PREFIX) {
+                return self.mapper.lists.find(self, key);
+            } else if let Some(key) = key.strip_prefix(crate::modifiers::CIDR) {
+                return self.mapper.compiled.cidr(self, key);
+            } else if let Some(key) = key.strip_prefix(crate::modifiers::EXISTS) {
+                return crate::modifiers::exists(self, key);
+            } else if let Some(key) = key.strip_prefix(crate::modifiers::FIELDREF) {
+                return crate::modifiers::fieldref(self, key);
+            } else if let Some(key) = key.strip_prefix(crate::modifiers::GLOB) {
+                return self.mapper.compiled.glob(self, key);
+            }
+        }
         match &self.mapper.kind {
             MapperKind::None => self.document.find(key),
             MapperKind::Fast(map) => match map.get(key) {
@@ -565,64 +1036,87 @@ impl<'a> TauDocument for Mapped<'a> {
                 None => self.document.find(key),
             },
             MapperKind::Full(map) => match map.get(key) {
-                Some((v, Some(container), None)) => {
-                    if let Some(cache) = self.cache.get() {
-                        return cache.get(&container.field).and_then(|hit| hit.find(v));
-                    }
-                    // Due to referencing and ownership, we parse all containers at once, which
-                    // then allows us to use a OnceCell.
-                    let mut lookup = FxHashMap::default();
-                    for field in &self.mapper.fields {
-                        if let Some(container) = &field.container {
-                            if !lookup.contains_key(&container.field) {
-                                let data = match self.document.find(&container.field) {
-                                    Some(Tau::String(s)) => match container.format {
-                                        Format::Json => match serde_json::from_str::<Json>(&s) {
-                                            Ok(j) => Box::new(j) as Box<dyn TauDocument>,
-                                            Err(_) => continue,
-                                        },
-                                    },
-                                    _ => continue,
-                                };
-                                lookup.insert(container.field.clone(), data);
-                            }
+                Some((_, _, sym, transforms)) => {
+                    let mut value = self.lookup(map, key)?;
+                    if !transforms.is_empty() {
+                        let mut data = value.to_string()?.into_bytes();
+                        for transform in transforms {
+                            data = transform.apply(data)?;
                         }
+                        value =
+                            Tau::String(Cow::Owned(String::from_utf8_lossy(&data).into_owned()));
                     }
-                    if self.cache.set(lookup).is_err() {
-                        panic!("cache is already set!");
-                    }
-                    if let Some(cache) = self.cache.get() {
-                        return cache.get(&container.field).and_then(|hit| hit.find(v));
+                    // NOTE: The cast is applied last, so that it applies to the transformed value.
+                    match sym {
+                        Some(sym) => cast(sym, value),
+                        None => Some(value),
                     }
-                    None
                 }
-                Some((v, None, Some(sym))) => match sym {
-                    ModSym::Int => match self.document.find(v) {
-                        Some(res) => {
-                            // NOTE: We only parse string into i64 for now, we leave the other
-                            // types alone...
-                            if let Tau::String(s) = &res {
-                                if let Ok(i) = str::parse::<i64>(s) {
-                                    return Some(Tau::Int(i));
-                                }
-                            }
-                            Some(res)
-                        }
-                        res => res,
-                    },
-                    ModSym::Str => match self.document.find(v) {
-                        Some(value) => value.to_string().map(|s| Tau::String(Cow::Owned(s))),
-                        res => res,
-                    },
-                    _ => unreachable!(),
-                },
-                Some((v, None, None)) => self.document.find(v),
-                _ => self.document.find(key),
+                None => self.document.find(key),
             },
         }
     }
 }
 
+impl<'a> Mapped<'a> {
+    #[allow(clippy::type_complexity)]
+    fn lookup(
+        &self,
+        map: &FxHashMap<String, (String, Option<Container>, Option<ModSym>, Vec<Transform>)>,
+        key: &str,
+    ) -> Option<Tau<'_>> {
+        match map.get(key) {
+            Some((v, Some(container), _, _)) => {
+                if let Some(cache) = self.cache.get() {
+                    return cache.get(&container.field).and_then(|hit| hit.find(v));
+                }
+                // Due to referencing and ownership, we parse all containers at once, which
+                // then allows us to use a OnceCell.
+                let mut lookup = FxHashMap::default();
+                for field in &self.mapper.fields {
+                    if let Some(container) = &field.container {
+                        if !lookup.contains_key(&container.field) {
+                            let data = match self.document.find(&container.field) {
+                                Some(Tau::String(s)) => match container.parse(&s) {
+                                    Some(j) => Box::new(j) as Box<dyn TauDocument>,
+                                    None => continue,
+                                },
+                                _ => continue,
+                            };
+                            lookup.insert(container.field.clone(), data);
+                        }
+                    }
+                }
+                if self.cache.set(lookup).is_err() {
+                    panic!("cache is already set!");
+                }
+                if let Some(cache) = self.cache.get() {
+                    return cache.get(&container.field).and_then(|hit| hit.find(v));
+                }
+                None
+            }
+            Some((v, None, _, _)) => self.document.find(v),
+            None => self.document.find(key),
+        }
+    }
+}
+
+fn cast<'a>(sym: &ModSym, value: Tau<'a>) -> Option<Tau<'a>> {
+    match sym {
+        ModSym::Int => {
+            // NOTE: We only parse string into i64 for now, we leave the other types alone...
+            if let Tau::String(s) = &value {
+                if let Ok(i) = str::parse::<i64>(s) {
+                    return Some(Tau::Int(i));
+                }
+            }
+            Some(value)
+        }
+        ModSym::Str => value.to_string().map(|s| Tau::String(Cow::Owned(s))),
+        _ => unreachable!(),
+    }
+}
+
 struct Cache<'a> {
     cache: Option<Vec<Option<Tau<'a>>>>,
     mapped: &'a Mapped<'a>,
@@ -645,6 +1139,7 @@ pub struct Hunt {
     pub kind: HuntKind,
     pub mapper: Mapper,
     pub timestamp: String,
+    pub timestamp_format: TimestampFormat,
 
     pub file: FileKind,
 }
@@ -654,17 +1149,35 @@ impl Hunt {
         match &self.kind {
             HuntKind::Group { .. } => true,
             HuntKind::Rule { aggregate, .. } => aggregate.is_some(),
+            HuntKind::Yara => false,
         }
     }
 }
 
+/// The compiled YARA rules and the fields they are run against, each field is looked up through
+/// the mapping groups that map it, or in the document as is when none do. The timestamps are
+/// those of the mapping groups, which are tried in turn for the kind of document.
+struct Yara {
+    fields: Vec<(String, Vec<Mapper>)>,
+    hunt: Uuid,
+    rules: FxHashMap<(Option<String>, String), Uuid>,
+    scanner: YaraScanner,
+    timestamps: Vec<(FileKind, String, TimestampFormat)>,
+}
+
 pub struct HunterInner {
     hunts: Vec<Hunt>,
     fields: Vec<String>,
+    profile: Option<Profile>,
     rules: BTreeMap<Uuid, Rule>,
+    state: Option<Mutex<State>>,
+    suppressions: Vec<Suppression>,
+    yara: Option<Yara>,
 
+    dedup: Option<Dedup>,
     load_unknown: bool,
     local: bool,
+    matches: bool,
     preprocess: bool,
     from: Option<DateTime<Utc>>,
     skip_errors: bool,
@@ -684,14 +1197,48 @@ impl Hunter {
     pub fn hunt<'a>(&'a self, file: &'a Path) -> crate::Result<Vec<Detections>> {
         let mut reader = Reader::load(file, self.inner.load_unknown, self.inner.skip_errors)?;
         let kind = reader.kind();
+        let mut entry = match &self.inner.state {
+            Some(state) if kind != FileKind::Unknown => {
+                match state
+                    .lock()
+                    .expect("could not lock state")
+                    .check(file, &kind)?
+                {
+                    Some(entry) => Some(entry),
+                    None => return Ok(vec![]),
+                }
+            }
+            _ => None,
+        };
+        let record = AtomicU64::new(0);
+        if let Some(last) = entry.as_ref().and_then(|e| e.record) {
+            reader.resume(last);
+            record.store(last, Ordering::Relaxed);
+        }
+        if let Some(offset) = entry.as_ref().and_then(|e| e.offset) {
+            reader.seek(offset)?;
+        }
+        let index = self.inner.dedup.as_ref().map(|d| d.register(file));
         let aggregates: Mutex<FxHashMap<(Uuid, Uuid), (&Aggregate, FxHashMap<u64, Vec<Uuid>>)>> =
             Mutex::new(FxHashMap::default());
-        let files: Mutex<FxHashMap<Uuid, (Value, NaiveDateTime)>> =
+        let files: Mutex<FxHashMap<Uuid, (Value, NaiveDateTime, Option<u64>)>> =
             Mutex::new(FxHashMap::default());
-        let mut detections = reader
-            .documents()
+        let profile = self.inner.profile.as_ref();
+        let mut documents = reader.documents();
+        let documents = std::iter::from_fn(move || match profile {
+            Some(profile) => {
+                let start = Instant::now();
+                let document = documents.next();
+                profile.parsing.add(start.elapsed());
+                document
+            }
+            None => documents.next(),
+        });
+        let mut candidates = documents
+            .enumerate()
             .par_bridge()
-            .filter_map(|document| {
+            .filter_map(|(position, document)| {
+                let start = profile.map(|_| Instant::now());
                 let document_id = Uuid::new_v4();
                 let document = match document {
                     Ok(document) => document,
@@ -708,18 +1255,32 @@ impl Hunter {
                     }
                 };
                 let (kind, value): (FileKind, Value) = match document {
-                    File::Evtx(evtx) => (FileKind::Evtx, evtx.data.into()),
+                    File::Evtx(evtx) => {
+                        record.fetch_max(evtx.event_record_id, Ordering::Relaxed);
+                        (FileKind::Evtx, evtx.data.into())
+                    }
                     File::Hve(hve) => (FileKind::Hve, hve.into()),
                     File::Json(json) => (FileKind::Json, json.into()),
                     File::Mft(mft) => (FileKind::Mft, mft.into()),
                     File::Xml(xml) => (FileKind::Xml, xml.into()),
                 };
+                if let (Some(profile), Some(start)) = (profile, start) {
+                    profile.parsing.add(start.elapsed());
+                }
+                let identity = match &self.inner.dedup {
+                    Some(dedup) => dedup.identity(&kind, &value),
+                    None => None,
+                };
+                let mut aggregated = false;
                 let mut hits = vec![];
                 for hunt in &self.inner.hunts {
                     if hunt.file != kind {
                         continue;
                     }
 
+                    // NOTE: Fields are mapped lazily, so the time spent mapping values that are
+                    // only looked up by rules is counted against the rules.
+                    let start = profile.map(|_| Instant::now());
                     let wrapper;
                     let mapped = match &kind {
                         FileKind::Evtx => {
@@ -745,35 +1306,23 @@ impl Hunter {
                     };
 
                     let timestamp = match mapped.find(&hunt.timestamp) {
-                        Some(value) => match value.as_str() {
-                            Some(timestamp) => {
-                                match NaiveDateTime::parse_from_str(
-                                    timestamp,
-                                    "%Y-%m-%dT%H:%M:%S%.6fZ",
-                                ) {
-                                    Ok(t) => t,
-                                    Err(e) => {
-                                        if self.inner.skip_errors {
-                                            cs_eyellowln!(
-                                                "failed to parse timestamp '{}' - {}",
-                                                timestamp,
-                                                e,
-                                            );
-                                            return None;
-                                        } else {
-                                            return Some(Err(anyhow!(
-                                                "failed to parse timestamp '{}' - {}",
-                                                timestamp,
-                                                e
-                                            )));
-                                        }
-                                    }
+                        Some(value) => match hunt.timestamp_format.parse(&value) {
+                            Some(Ok(t)) => t,
+                            Some(Err(e)) => {
+                                if self.inner.skip_errors {
+                                    cs_eyellowln!("{}", e);
+                                    return None;
+                                } else {
+                                    return Some(Err(e));
                                 }
                             }
                             None => continue,
                         },
                         None => continue,
                     };
+                    if let (Some(profile), Some(start)) = (profile, start) {
+                        profile.mapping.add(start.elapsed());
+                    }
 
                     if self.skip(timestamp).ok()? {
                         continue;
@@ -785,8 +1334,11 @@ impl Hunter {
                             filter,
                             kind,
                             preconditions,
+                            routed,
                         } => {
-                            if tau_engine::core::solve(filter, &mapped) {
+                            if self.profiled(&hunt.id, true, || {
+                                tau_engine::core::solve(filter, &mapped)
+                            }) {
                                 let rules = self.inner.rules.iter().collect::<Vec<(_, _)>>();
                                 let matches = rules
                                     .iter()
@@ -797,12 +1349,16 @@ impl Hunter {
                                         if exclusions.contains(rid) {
                                             return None;
                                         }
+                                        if !routed.as_ref().map(|r| r.contains(rid)).unwrap_or(true)
+                                        {
+                                            return None;
+                                        }
                                         if let Some(filter) = preconditions.get(rid) {
                                             if !tau_engine::core::solve(filter, &mapped) {
                                                 return None;
                                             }
                                         }
-                                        if rule.solve(&mapped) {
+                                        if self.profiled(rid, false, || rule.solve(&mapped)) {
                                             Some((*rid, rule))
                                         } else {
                                             None
@@ -810,9 +1366,19 @@ impl Hunter {
                                     })
                                     .collect::<Vec<(_, _)>>();
                                 for (rid, rule) in matches {
+                                    let suppressed = self.suppressed(hunt, rule, &mapped);
                                     if let Some(aggregate) = &rule.aggregate() {
+                                        // NOTE: Suppressed documents are excluded from aggregates
+                                        // rather than tagged, as the count would be misleading.
+                                        if suppressed.is_some() {
+                                            continue;
+                                        }
+                                        aggregated = true;
                                         let mut files = files.lock().expect("could not lock files");
-                                        files.insert(document_id, (value.clone(), timestamp));
+                                        files.insert(
+                                            document_id,
+                                            (value.clone(), timestamp, identity),
+                                        );
                                         let mut hasher = FxHasher::default();
                                         let mut skip = false;
                                         for field in &aggregate.fields {
@@ -837,28 +1403,47 @@ impl Hunter {
                                         let docs = aggregates.1.entry(id).or_insert(vec![]);
                                         docs.push(document_id);
                                     } else {
+                                        let matched = if self.inner.matches {
+                                            Some(rule.matched(&mapped))
+                                        } else {
+                                            None
+                                        };
                                         hits.push(Hit {
                                             hunt: hunt.id,
                                             rule: *rid,
                                             timestamp,
+                                            suppressed,
+                                            strings: vec![],
+                                            indicators: rule
+                                                .indicators(&mapped, &hunt.mapper.lists),
+                                            matched,
                                         });
                                     }
                                 }
                             }
                         }
                         HuntKind::Rule { aggregate, filter } => {
-                            let hit = match &filter {
+                            let hit = self.profiled(&hunt.id, false, || match &filter {
                                 Filter::Detection(detection) => {
                                     tau_engine::solve(detection, &mapped)
                                 }
                                 Filter::Expression(expression) => {
                                     tau_engine::core::solve(expression, &mapped)
                                 }
-                            };
+                            });
                             if hit {
+                                let rule = self.inner.rules.get(&hunt.id);
+                                let suppressed = match rule {
+                                    Some(rule) => self.suppressed(hunt, rule, &mapped),
+                                    None => None,
+                                };
                                 if let Some(aggregate) = aggregate {
+                                    if suppressed.is_some() {
+                                        continue;
+                                    }
+                                    aggregated = true;
                                     let mut files = files.lock().expect("could not lock files");
-                                    files.insert(document_id, (value.clone(), timestamp));
+                                    files.insert(document_id, (value.clone(), timestamp, identity));
                                     let mut hasher = FxHasher::default();
                                     let mut skip = false;
                                     for field in &aggregate.fields {
@@ -883,53 +1468,132 @@ impl Hunter {
                                     let docs = aggregates.1.entry(id).or_insert(vec![]);
                                     docs.push(document_id);
                                 } else {
+                                    let matched = match rule {
+                                        Some(rule) if self.inner.matches => {
+                                            Some(rule.matched(&mapped))
+                                        }
+                                        _ => None,
+                                    };
                                     hits.push(Hit {
                                         hunt: hunt.id,
                                         rule: hunt.id,
                                         timestamp,
+                                        suppressed,
+                                        strings: vec![],
+                                        indicators: vec![],
+                                        matched,
                                     });
                                 }
                             }
                         }
+                        HuntKind::Yara => {}
                     }
                 }
-                if !hits.is_empty() {
-                    Some(Ok(Detections {
+                if let Some(yara) = &self.inner.yara {
+                    let found = match &kind {
+                        FileKind::Evtx => self.scan(yara, &kind, &crate::evtx::Wrapper(&value)),
+                        _ => self.scan(yara, &kind, &value),
+                    };
+                    hits.extend(found);
+                }
+                // NOTE: Only detected documents are checked for duplicates, so that the identities
+                // of every document do not have to be held.
+                let detections = if !hits.is_empty() {
+                    Some(Detections {
                         hits,
                         kind: Kind::Individual {
                             document: Document {
                                 kind,
                                 path: file,
                                 data: bincode::serialize(&value).ok()?,
+                                identity,
+                                duplicates: vec![],
                             },
                         },
-                    }))
-                } else {
+                    })
+                } else if aggregated && identity.is_some() {
                     None
-                }
+                } else {
+                    return None;
+                };
+                Some(Ok((
+                    position,
+                    document_id,
+                    identity,
+                    aggregated,
+                    detections,
+                )))
             })
-            .collect::<crate::Result<Vec<Detections>>>()?;
+            .collect::<crate::Result<Vec<_>>>()?;
+        // NOTE: Duplicates are resolved once the file has been hunted and in the order that its
+        // documents were read, so that the copy that is kept does not depend on the scheduling.
+        candidates.sort_by_key(|(position, ..)| *position);
+        let mut detections = Vec::with_capacity(candidates.len());
+        let mut dropped: HashSet<Uuid> = HashSet::new();
+        for (_, document_id, identity, aggregated, found) in candidates {
+            if let (Some(dedup), Some(index), Some(identity)) = (&self.inner.dedup, index, identity)
+            {
+                if dedup.seen(identity, index) {
+                    if aggregated {
+                        dropped.insert(document_id);
+                    }
+                    continue;
+                }
+            }
+            detections.extend(found);
+        }
         let aggregates = aggregates.into_inner().expect("could not lock aggregates");
         let files = files.into_inner().expect("could not lock aggregates");
         for ((hid, rid), (aggregate, docs)) in aggregates {
-            for ids in docs.values() {
+            let name = self.aggregate(&hid, &rid);
+            for (hash, mut ids) in docs {
+                ids.retain(|id| !dropped.contains(id));
+                if ids.is_empty() {
+                    continue;
+                }
+                // NOTE: When resuming, the documents from previous runs count towards the aggregate
+                // but only those from this run are included in the detection.
+                let saved = match (&mut entry, &name) {
+                    (Some(entry), Some(name)) => {
+                        let saved = entry
+                            .aggregates
+                            .entry(name.clone())
+                            .or_default()
+                            .entry(hash)
+                            .or_default();
+                        let previous = saved.clone();
+                        for id in &ids {
+                            let (_, timestamp, _) = files.get(id).expect("could not get document");
+                            saved.push(timestamp);
+                        }
+                        previous
+                    }
+                    _ => Saved::default(),
+                };
+                let count = saved.count + ids.len();
                 let hit = match aggregate.count {
-                    Pattern::Equal(i) => ids.len() == (i as usize),
-                    Pattern::GreaterThan(i) => ids.len() > (i as usize),
-                    Pattern::GreaterThanOrEqual(i) => ids.len() >= (i as usize),
-                    Pattern::LessThan(i) => ids.len() < (i as usize),
-                    Pattern::LessThanOrEqual(i) => ids.len() <= (i as usize),
+                    Pattern::Equal(i) => count == (i as usize),
+                    Pattern::GreaterThan(i) => count > (i as usize),
+                    Pattern::GreaterThanOrEqual(i) => count >= (i as usize),
+                    Pattern::LessThan(i) => count < (i as usize),
+                    Pattern::LessThanOrEqual(i) => count <= (i as usize),
                     _ => false,
                 };
                 if hit {
                     let mut documents = Vec::with_capacity(ids.len());
-                    let mut timestamps = Vec::with_capacity(ids.len());
-                    for id in ids {
-                        let (value, timestamp) = files.get(id).expect("could not get document");
+                    let mut timestamps = Vec::with_capacity(ids.len() + 1);
+                    if let Some(timestamp) = saved.timestamp()? {
+                        timestamps.push(timestamp);
+                    }
+                    for id in &ids {
+                        let (value, timestamp, identity) =
+                            files.get(id).expect("could not get document");
                         documents.push(Document {
                             kind: kind.clone(),
                             path: file,
                             data: bincode::serialize(&value)?,
+                            identity: *identity,
+                            duplicates: vec![],
                         });
                         timestamps.push(*timestamp);
                     }
@@ -942,15 +1606,157 @@ impl Hunter {
                                 .into_iter()
                                 .next()
                                 .expect("failed to get timestamp"),
+                            suppressed: None,
+                            strings: vec![],
+                            indicators: vec![],
+                            matched: None,
                         }],
                         kind: Kind::Aggregate { documents },
                     });
                 }
             }
         }
+        if let (Some(state), Some(mut entry)) = (&self.inner.state, entry) {
+            let last = record.into_inner();
+            if last > 0 {
+                entry.record = Some(last);
+            }
+            entry.offset = reader.offset();
+            let mut state = state.lock().expect("could not lock state");
+            state.update(file, entry);
+        }
         Ok(detections)
     }
 
+    /// The number of detected documents that shared their identity with another document in the
+    /// same file when deduplicating.
+    pub fn collisions(&self) -> usize {
+        self.inner
+            .dedup
+            .as_ref()
+            .map(|d| d.collisions())
+            .unwrap_or_default()
+    }
+
+    /// The number of detected records that were also found in other sources when deduplicating.
+    pub fn duplicated(&self) -> usize {
+        self.inner
+            .dedup
+            .as_ref()
+            .map(|d| d.duplicated())
+            .unwrap_or_default()
+    }
+
+    /// Annotates documents with the other sources that the same record was found in, this must be
+    /// called once all files have been hunted.
+    pub fn duplicates(&self, detections: &mut [Detections]) {
+        let dedup = match &self.inner.dedup {
+            Some(dedup) => dedup,
+            None => return,
+        };
+        for detection in detections {
+            let documents = match &mut detection.kind {
+                Kind::Aggregate { documents } => documents.iter_mut().collect::<Vec<_>>(),
+                Kind::Individual { document } => vec![document],
+            };
+            for document in documents {
+                if let Some(identity) = &document.identity {
+                    if let Some(paths) = dedup.duplicates(identity) {
+                        document.duplicates = paths;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Explains how each Sigma rule would run under the mapping groups without hunting, listing
+    /// the fields that a group does not map and whether that stops the rule from ever matching.
+    pub fn explain(&self) -> Vec<(&Rule, Vec<Explanation>)> {
+        let mut explanations = vec![];
+        for (rid, rule) in &self.inner.rules {
+            let sigma = match rule {
+                Rule::Sigma(sigma) => sigma,
+                _ => continue,
+            };
+            let expression = &sigma.tau.detection.expression;
+            let mut fields = crate::ext::tau::extract_fields(expression);
+            if let Some(aggregate) = &sigma.aggregate {
+                fields.extend(aggregate.fields.iter().cloned());
+            }
+            let mut groups = vec![];
+            for hunt in &self.inner.hunts {
+                let (exclusions, kind, preconditions, routed) = match &hunt.kind {
+                    HuntKind::Group {
+                        exclusions,
+                        kind,
+                        preconditions,
+                        routed,
+                        ..
+                    } => (exclusions, kind, preconditions, routed),
+                    HuntKind::Rule { .. } | HuntKind::Yara => continue,
+                };
+                if !rule.is_kind(kind) || !routed.as_ref().map(|r| r.contains(rid)).unwrap_or(true)
+                {
+                    continue;
+                }
+                let mapped: HashSet<&str> = hunt
+                    .mapper
+                    .fields()
+                    .iter()
+                    .map(|f| f.from.as_str())
+                    .collect();
+                let missing: HashSet<String> = fields
+                    .iter()
+                    .filter(|f| {
+                        crate::modifiers::fields(f)
+                            .iter()
+                            .any(|f| !mapped.contains(f))
+                    })
+                    .cloned()
+                    .collect();
+                // NOTE: A presence check still resolves when its field is missing, so it cannot
+                // be used to solve the rule.
+                let solvable: HashSet<String> = missing
+                    .iter()
+                    .filter(|f| !f.starts_with(crate::modifiers::EXISTS))
+                    .cloned()
+                    .collect();
+                let coverage = if exclusions.contains(rid) {
+                    Coverage::Excluded
+                } else if missing.is_empty() {
+                    Coverage::Full
+                } else if crate::ext::tau::solve_missing(expression, &solvable) == Some(false)
+                    || sigma
+                        .aggregate
+                        .as_ref()
+                        .map(|a| a.fields.iter().any(|f| missing.contains(f)))
+                        .unwrap_or_default()
+                {
+                    Coverage::Unmapped
+                } else {
+                    Coverage::Partial
+                };
+                let mut unmapped = missing
+                    .iter()
+                    .flat_map(|f| crate::modifiers::fields(f))
+                    .filter(|f| !mapped.contains(f))
+                    .map(|f| f.to_owned())
+                    .collect::<Vec<_>>();
+                unmapped.sort();
+                unmapped.dedup();
+                groups.push(Explanation {
+                    group: hunt.group.clone(),
+                    coverage,
+                    precondition: preconditions.contains_key(rid),
+                    unmapped,
+                });
+            }
+            explanations.push((rule, groups));
+        }
+        explanations.sort_by(|x, y| x.0.name().cmp(y.0.name()));
+        explanations
+    }
+
     pub fn extensions(&self) -> HashSet<String> {
         let mut extensions = HashSet::new();
         for rule in &self.inner.rules {
@@ -970,10 +1776,213 @@ impl Hunter {
         &self.inner.hunts
     }
 
+    pub fn profile(&self) -> Option<&Profile> {
+        self.inner.profile.as_ref()
+    }
+
     pub fn rules(&self) -> &BTreeMap<Uuid, Rule> {
         &self.inner.rules
     }
 
+    /// Commits the progress made so far, this should only be called once the detections for the
+    /// hunted files have been output.
+    pub fn save(&self) -> crate::Result<()> {
+        if let Some(state) = &self.inner.state {
+            let mut state = state.lock().expect("could not lock state");
+            state.commit();
+            state.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn suppressions(&self) -> &Vec<Suppression> {
+        &self.inner.suppressions
+    }
+
+    /// Checks whether any of the loaded rules match the document, when no kind is given the
+    /// document is checked against every hunt. Timestamps and suppressions are ignored, and a
+    /// document that matches an aggregation rule counts as a match regardless of the threshold.
+    pub fn test(&self, kind: Option<&FileKind>, value: &Value) -> bool {
+        for hunt in &self.inner.hunts {
+            if let Some(kind) = kind {
+                if &hunt.file != kind {
+                    continue;
+                }
+            }
+            let wrapper;
+            let mapped = match &hunt.file {
+                FileKind::Evtx => {
+                    wrapper = crate::evtx::Wrapper(value);
+                    hunt.mapper.mapped(&wrapper)
+                }
+                _ => hunt.mapper.mapped(value),
+            };
+            let hit = match &hunt.kind {
+                HuntKind::Group {
+                    exclusions,
+                    filter,
+                    kind,
+                    preconditions,
+                    routed,
+                } => {
+                    tau_engine::core::solve(filter, &mapped)
+                        && self.inner.rules.iter().any(|(rid, rule)| {
+                            if !rule.is_kind(kind) || exclusions.contains(rid) {
+                                return false;
+                            }
+                            if !routed.as_ref().map(|r| r.contains(rid)).unwrap_or(true) {
+                                return false;
+                            }
+                            if let Some(filter) = preconditions.get(rid) {
+                                if !tau_engine::core::solve(filter, &mapped) {
+                                    return false;
+                                }
+                            }
+                            rule.solve(&mapped)
+                        })
+                }
+                HuntKind::Rule { filter, .. } => match filter {
+                    Filter::Detection(detection) => tau_engine::solve(detection, &mapped),
+                    Filter::Expression(expression) => tau_engine::core::solve(expression, &mapped),
+                },
+                HuntKind::Yara => false,
+            };
+            if hit {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn aggregate(&self, hunt: &Uuid, rule: &Uuid) -> Option<String> {
+        let hunt = self.inner.hunts.iter().find(|h| &h.id == hunt)?;
+        let rule = self.inner.rules.get(rule)?;
+        Some(format!("{}:{}", hunt.group, rule.name()))
+    }
+
+    // Solves the filter of a rule or group, recording how long it took when profiling.
+    #[inline]
+    fn profiled<F>(&self, id: &Uuid, group: bool, solve: F) -> bool
+    where
+        F: FnOnce() -> bool,
+    {
+        let profile = match &self.inner.profile {
+            Some(profile) => profile,
+            None => return solve(),
+        };
+        let start = Instant::now();
+        let matched = solve();
+        let elapsed = start.elapsed();
+        let counters = if group {
+            &profile.groups
+        } else {
+            &profile.rules
+        };
+        if let Some(counter) = counters.get(id) {
+            counter.record(elapsed, matched);
+        }
+        profile.matching.add(elapsed);
+        matched
+    }
+
+    #[inline]
+    // Scans the fields of a document with the YARA rules, a value that is mapped by more than one
+    // group is only scanned once.
+    fn scan<D>(&self, yara: &Yara, kind: &FileKind, document: &D) -> Vec<Hit>
+    where
+        D: TauDocument,
+    {
+        let timestamp = yara
+            .timestamps
+            .iter()
+            .filter(|(k, _, _)| k == kind)
+            .find_map(
+                |(_, field, format)| match format.parse(&document.find(field)?) {
+                    Some(Ok(t)) => Some(t),
+                    _ => None,
+                },
+            );
+        let timestamp = match timestamp {
+            Some(timestamp) => timestamp,
+            None => return vec![],
+        };
+        if self.skip(timestamp).unwrap_or(true) {
+            return vec![];
+        }
+        let start = self.inner.profile.as_ref().map(|_| Instant::now());
+        let mut scanned = HashSet::new();
+        let mut found: Vec<(Uuid, Vec<YaraMatch>, &Mapper)> = vec![];
+        for (field, mappers) in &yara.fields {
+            for mapper in mappers {
+                let data = match mapper
+                    .mapped(document)
+                    .find(field)
+                    .and_then(|v| v.to_string())
+                {
+                    Some(data) => data,
+                    None => continue,
+                };
+                if !scanned.insert((field, data.clone())) {
+                    continue;
+                }
+                for (id, strings) in yara.scanner.scan(field, data.as_bytes()) {
+                    let rid = match yara.rules.get(&id) {
+                        Some(rid) => *rid,
+                        None => continue,
+                    };
+                    match found.iter_mut().find(|(r, _, _)| r == &rid) {
+                        Some((_, s, _)) => s.extend(strings),
+                        None => found.push((rid, strings, mapper)),
+                    }
+                }
+            }
+        }
+        if let (Some(profile), Some(start)) = (&self.inner.profile, start) {
+            profile.matching.add(start.elapsed());
+        }
+        if found.is_empty() {
+            return vec![];
+        }
+        let hunt = match self.inner.hunts.iter().find(|h| h.id == yara.hunt) {
+            Some(hunt) => hunt,
+            None => return vec![],
+        };
+        let mut hits = vec![];
+        for (rid, strings, mapper) in found {
+            let rule = self.inner.rules.get(&rid).expect("could not get rule");
+            // NOTE: Suppressions are checked through the mapping that found the match, and when
+            // preprocessing their fields have been renamed so the values are cached as for hunts.
+            let mapped = mapper.mapped(document);
+            let cache = if self.inner.preprocess {
+                Some(self.inner.fields.iter().map(|f| mapped.find(f)).collect())
+            } else {
+                None
+            };
+            let cache = Cache {
+                cache,
+                mapped: &mapped,
+            };
+            hits.push(Hit {
+                hunt: hunt.id,
+                rule: rid,
+                timestamp,
+                suppressed: self.suppressed(hunt, rule, &cache),
+                strings,
+                indicators: vec![],
+                matched: None,
+            });
+        }
+        hits
+    }
+
+    fn suppressed(&self, hunt: &Hunt, rule: &Rule, document: &dyn TauDocument) -> Option<Uuid> {
+        self.inner
+            .suppressions
+            .iter()
+            .find(|s| s.is_match(&hunt.group, rule, document))
+            .map(|s| s.id)
+    }
+
     fn skip(&self, timestamp: NaiveDateTime) -> crate::Result<bool> {
         if self.inner.from.is_some() || self.inner.to.is_some() {
             // TODO: Not sure if this is correct...
@@ -1021,3 +2030,27 @@ impl Hunter {
         Ok(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapped_cast_after_transforms() {
+        let mapper = Mapper::from(vec![Field {
+            name: "Port".to_owned(),
+            from: "Port".to_owned(),
+            to: "Address".to_owned(),
+            cast: Some(ModSym::Int),
+            container: None,
+            transforms: vec![Transform::Split {
+                separator: ":".to_owned(),
+                index: 1,
+            }],
+            visible: true,
+        }]);
+        let document = Value::from(serde_json::json!({"Address": "10.0.0.1:445"}));
+        let mapped = mapper.mapped(&document);
+        assert!(matches!(mapped.find("Port"), Some(Tau::Int(445))));
+    }
+}
@@ -0,0 +1,898 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value as Json;
+use serde_yaml::{Mapping, Value as Yaml};
+
+use crate::lists::{List, Lists, Mode};
+use crate::rule::{Level, Rule, Sigma};
+
+lazy_static! {
+    static ref ATTACK: Regex = Regex::new(r"\b[Tt]\d{4}(?:\.\d{3})?\b").expect("invalid regex");
+    // NOTE: We only support equality comparisons, which covers the vast majority of indicators
+    // that are shared in the wild.
+    static ref COMPARISON: Regex =
+        Regex::new(r"([a-z0-9-]+):([^\s=]+)\s*=\s*'((?:[^'\\]|\\.)*)'").expect("invalid regex");
+    static ref QUOTED: Regex = Regex::new(r"'(?:[^'\\]|\\.)*'").expect("invalid regex");
+}
+
+// The Sigma fields that each kind of indicator is searched for in, these must be present in the
+// mapping file for the indicator to be hunted for.
+const DOMAIN_FIELDS: &[&str] = &["DestinationHostname", "QueryName", "QNAME"];
+const FILE_NAME_FIELDS: &[&str] = &[
+    "Image",
+    "ImageLoaded",
+    "ParentImage",
+    "ProcessName",
+    "TargetFilename",
+];
+const HASH_FIELDS: &[&str] = &["md5", "sha1", "sha256", "Imphash"];
+const IP_FIELDS: &[&str] = &[
+    "DestAddress",
+    "DestinationIp",
+    "IpAddress",
+    "RemoteAddress",
+    "SourceAddress",
+    "SourceIp",
+];
+const REGISTRY_FIELDS: &[&str] = &["ObjectName", "TargetObject"];
+const TEXT_FIELDS: &[&str] = &["CommandLine", "ParentCommandLine", "ScriptBlockText"];
+
+const REGISTRY_HIVES: &[&str] = &[
+    "hkcr",
+    "hkcu",
+    "hklm",
+    "hku",
+    "hkey_classes_root",
+    "hkey_current_config",
+    "hkey_current_user",
+    "hkey_local_machine",
+    "hkey_users",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Type {
+    Domain,
+    FileName,
+    Hash,
+    Ip,
+    Registry,
+    Url,
+    UserAgent,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Domain => write!(f, "domain"),
+            Self::FileName => write!(f, "file name"),
+            Self::Hash => write!(f, "hash"),
+            Self::Ip => write!(f, "ip"),
+            Self::Registry => write!(f, "registry key"),
+            Self::Url => write!(f, "url"),
+            Self::UserAgent => write!(f, "user agent"),
+        }
+    }
+}
+
+impl FromStr for Type {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // NOTE: We accept the names used by MISP so that exports can be converted to CSV as is.
+        let v = match s.trim().to_lowercase().as_str() {
+            "domain" | "hostname" => Self::Domain,
+            "filename" | "file-name" | "file_name" => Self::FileName,
+            "hash" | "md5" | "sha1" | "sha256" | "imphash" => Self::Hash,
+            "ip" | "ipv4" | "ipv6" | "ip-src" | "ip-dst" => Self::Ip,
+            "registry" | "regkey" | "registry-key" => Self::Registry,
+            "url" | "uri" | "link" => Self::Url,
+            "user-agent" | "useragent" | "user_agent" => Self::UserAgent,
+            _ => anyhow::bail!(
+                "unknown indicator type, must be: domain, filename, hash, ip, registry, url or user-agent"
+            ),
+        };
+        Ok(v)
+    }
+}
+
+/// A single observable taken from an intel feed.
+#[derive(Clone, Debug)]
+pub struct Indicator {
+    pub id: String,
+    pub feed: String,
+    pub kind: Type,
+    pub value: String,
+
+    pub attack: Vec<String>,
+    pub description: Option<String>,
+    pub level: Level,
+    pub references: Vec<String>,
+}
+
+/// The indicators loaded from an intel feed.
+pub struct Intel {
+    pub indicators: Vec<Indicator>,
+    /// The number of indicators that could not be hunted for, i.e. unsupported types.
+    pub unsupported: usize,
+}
+
+impl Intel {
+    /// Builds the Sigma rules that hunt for the indicators, one for each type of indicator from a
+    /// feed at a given level. The indicators are held in lists rather than in the rules so that a
+    /// large feed is not solved one indicator at a time, and each entry is labelled with the ids
+    /// of its indicators so that detections can report which of them matched. The lists are added
+    /// to those provided, which must be passed to the hunter along with the rules.
+    pub fn rules(&self, lists: &mut Lists) -> crate::Result<Vec<Rule>> {
+        let mut groups: Vec<((&str, Type, &Level), Vec<&Indicator>)> = vec![];
+        for indicator in &self.indicators {
+            let key = (indicator.feed.as_str(), indicator.kind, &indicator.level);
+            match groups.iter_mut().find(|(k, _)| k == &key) {
+                Some((_, indicators)) => indicators.push(indicator),
+                None => groups.push((key, vec![indicator])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|((feed, kind, level), indicators)| to_rule(feed, kind, level, &indicators, lists))
+            .collect()
+    }
+}
+
+// Builds the rule for a group of indicators, the feed is recorded as the author and any ATT&CK
+// techniques as tags so that they are carried through to the detections.
+fn to_rule(
+    feed: &str,
+    kind: Type,
+    level: &Level,
+    indicators: &[&Indicator],
+    lists: &mut Lists,
+) -> crate::Result<Rule> {
+    let mut exact = List::default();
+    let mut suffix = List::default();
+    for indicator in indicators {
+        let (id, value) = (&indicator.id, &indicator.value);
+        match kind {
+            Type::Domain => {
+                exact.insert(value, id);
+                suffix.insert(&format!(".{}", value), id);
+            }
+            Type::FileName => {
+                // NOTE: Full paths are matched as is, otherwise we only match the final component.
+                if value.contains('\\') {
+                    suffix.insert(value, id);
+                } else {
+                    suffix.insert(&format!("\\{}", value), id);
+                    exact.insert(value, id);
+                }
+            }
+            Type::Ip => {
+                exact.insert(value, id);
+                if value.contains('.') {
+                    // Windows often logs IPv4 addresses in their IPv6 mapped form.
+                    exact.insert(&format!("::ffff:{}", value), id);
+                }
+            }
+            Type::Hash | Type::Registry | Type::Url | Type::UserAgent => exact.insert(value, id),
+        }
+    }
+    let exact = (!exact.is_empty()).then(|| lists.insert(exact));
+    let suffix = (!suffix.is_empty()).then(|| lists.insert(suffix));
+
+    let mut searches = vec![];
+    match kind {
+        Type::Domain => {
+            for field in DOMAIN_FIELDS {
+                searches.extend(search(exact.as_deref(), Mode::Exact, field));
+                searches.extend(search(suffix.as_deref(), Mode::EndsWith, field));
+            }
+        }
+        Type::FileName => {
+            for field in FILE_NAME_FIELDS {
+                searches.extend(search(suffix.as_deref(), Mode::EndsWith, field));
+            }
+            searches.extend(search(exact.as_deref(), Mode::Exact, "OriginalFileName"));
+        }
+        Type::Hash => {
+            for field in HASH_FIELDS {
+                searches.extend(search(exact.as_deref(), Mode::Exact, field));
+            }
+            searches.extend(search(exact.as_deref(), Mode::Contains, "Hashes"));
+        }
+        Type::Ip => {
+            for field in IP_FIELDS {
+                searches.extend(search(exact.as_deref(), Mode::Exact, field));
+            }
+        }
+        Type::Registry => {
+            for field in REGISTRY_FIELDS {
+                searches.extend(search(exact.as_deref(), Mode::Contains, field));
+            }
+        }
+        Type::Url => {
+            for field in TEXT_FIELDS {
+                searches.extend(search(exact.as_deref(), Mode::Contains, field));
+            }
+        }
+        Type::UserAgent => {
+            searches.extend(search(exact.as_deref(), Mode::Exact, "c-useragent"));
+            for field in TEXT_FIELDS {
+                searches.extend(search(exact.as_deref(), Mode::Contains, field));
+            }
+        }
+    }
+
+    let mut tags = vec![];
+    let mut references = vec![];
+    for indicator in indicators {
+        for t in &indicator.attack {
+            let t = format!("attack.{}", t.to_lowercase());
+            if !tags.contains(&t) {
+                tags.push(t);
+            }
+        }
+        for r in &indicator.references {
+            if !references.contains(r) {
+                references.push(r.clone());
+            }
+        }
+    }
+    // NOTE: Indicators from the same event share its description, so it is kept when every
+    // indicator in the group has it.
+    let description = match indicators.first().and_then(|i| i.description.as_ref()) {
+        Some(d) if indicators.iter().all(|i| i.description.as_ref() == Some(d)) => d.clone(),
+        _ => format!("Indicators from the '{}' intel feed.", feed),
+    };
+
+    let mut detection = Mapping::new();
+    detection.insert("condition".into(), "intel".into());
+    detection.insert("intel".into(), Yaml::Sequence(searches));
+
+    let mut rule = Mapping::new();
+    rule.insert("title".into(), format!("Intel - {}: {}", feed, kind).into());
+    rule.insert("authors".into(), vec![feed.to_owned()].into());
+    rule.insert("description".into(), description.into());
+    rule.insert("level".into(), level.to_string().into());
+    rule.insert("status".into(), "stable".into());
+    if !tags.is_empty() {
+        rule.insert("tags".into(), tags.into());
+    }
+    if !references.is_empty() {
+        rule.insert("references".into(), references.into());
+    }
+    rule.insert("detection".into(), Yaml::Mapping(detection));
+
+    let sigma: Sigma = match serde_yaml::from_value(Yaml::Mapping(rule)) {
+        Ok(sigma) => sigma,
+        Err(e) => anyhow::bail!(
+            "could not convert {} indicators from '{}' - {}",
+            kind,
+            feed,
+            e
+        ),
+    };
+    Ok(Rule::Sigma(crate::rule::optimise(sigma)))
+}
+
+/// Loads a STIX 2.1 bundle, MISP event export or CSV file of indicators.
+pub fn load(path: &Path) -> crate::Result<Intel> {
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let feed = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if path.extension().and_then(|e| e.to_str()) == Some("csv") {
+        return from_csv(&contents, &feed);
+    }
+    let json: Json = match serde_json::from_str(&contents) {
+        Ok(json) => json,
+        Err(e) => anyhow::bail!("intel must be a csv or json file - {}", e),
+    };
+    if json.get("type").and_then(|t| t.as_str()) == Some("bundle") {
+        return from_stix(&json, &feed);
+    }
+    // NOTE: MISP exports come in a few shapes, a single event, a list of events, or the response
+    // from the REST API.
+    let events = match &json {
+        Json::Array(events) => events.iter().collect(),
+        Json::Object(object) => match object.get("response") {
+            Some(Json::Array(events)) => events.iter().collect(),
+            _ => vec![&json],
+        },
+        _ => vec![],
+    };
+    let mut intel = Intel {
+        indicators: vec![],
+        unsupported: 0,
+    };
+    for event in events {
+        let event = event.get("Event").unwrap_or(event);
+        if event.get("Attribute").is_none() && event.get("Object").is_none() {
+            anyhow::bail!("unsupported intel format, must be a stix bundle, misp event or csv");
+        }
+        let misp = from_misp(event, &feed);
+        intel.indicators.extend(misp.indicators);
+        intel.unsupported += misp.unsupported;
+    }
+    Ok(intel)
+}
+
+fn from_csv(contents: &str, feed: &str) -> crate::Result<Intel> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+    let headers = reader
+        .headers()?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect::<Vec<_>>();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let (kinds, values) = match (column("type"), column("value")) {
+        (Some(kinds), Some(values)) => (kinds, values),
+        _ => anyhow::bail!("intel csv files must have a 'type' and 'value' column"),
+    };
+    let (attack, description, id, level, reference, source) = (
+        column("attack"),
+        column("description"),
+        column("id"),
+        column("level"),
+        column("reference"),
+        column("feed"),
+    );
+    let mut intel = Intel {
+        indicators: vec![],
+        unsupported: 0,
+    };
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let get = |index: Option<usize>| {
+            index
+                .and_then(|i| record.get(i))
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_owned())
+        };
+        let kind = match record.get(kinds).and_then(|k| Type::from_str(k).ok()) {
+            Some(kind) => kind,
+            None => {
+                intel.unsupported += 1;
+                continue;
+            }
+        };
+        let value = match record.get(values).and_then(|v| normalise(kind, v)) {
+            Some(value) => value,
+            None => {
+                intel.unsupported += 1;
+                continue;
+            }
+        };
+        let level = match get(level) {
+            Some(level) => match Level::from_str(&level) {
+                Ok(level) => level,
+                Err(e) => anyhow::bail!("invalid level on line {} - {}", i + 2, e),
+            },
+            None => Level::High,
+        };
+        intel.indicators.push(Indicator {
+            id: get(id).unwrap_or_else(|| format!("{}:{}", feed, i + 1)),
+            feed: get(source).unwrap_or_else(|| feed.to_owned()),
+            kind,
+            value,
+
+            attack: get(attack).map(|a| techniques(&a)).unwrap_or_default(),
+            description: get(description),
+            level,
+            references: get(reference).into_iter().collect(),
+        });
+    }
+    Ok(intel)
+}
+
+fn from_misp(event: &Json, feed: &str) -> Intel {
+    let feed = event
+        .pointer("/Orgc/name")
+        .or_else(|| event.pointer("/Org/name"))
+        .and_then(|n| n.as_str())
+        .unwrap_or(feed)
+        .to_owned();
+    let description = event
+        .get("info")
+        .and_then(|i| i.as_str())
+        .map(|i| i.to_owned());
+    let level = match event.get("threat_level_id").and_then(|t| match t {
+        Json::String(s) => s.parse::<u64>().ok(),
+        _ => t.as_u64(),
+    }) {
+        Some(2) => Level::Medium,
+        Some(3) => Level::Low,
+        _ => Level::High,
+    };
+    let attack = misp_techniques(event);
+
+    let mut attributes = vec![];
+    if let Some(Json::Array(a)) = event.get("Attribute") {
+        attributes.extend(a.iter());
+    }
+    if let Some(Json::Array(objects)) = event.get("Object") {
+        for object in objects {
+            if let Some(Json::Array(a)) = object.get("Attribute") {
+                attributes.extend(a.iter());
+            }
+        }
+    }
+
+    let mut intel = Intel {
+        indicators: vec![],
+        unsupported: 0,
+    };
+    for attribute in attributes {
+        // NOTE: Attributes not flagged for detection are context only, so we ignore them.
+        if attribute.get("to_ids").and_then(|t| t.as_bool()) == Some(false)
+            || attribute.get("deleted").and_then(|d| d.as_bool()) == Some(true)
+        {
+            continue;
+        }
+        let (kinds, values) = match (
+            attribute.get("type").and_then(|t| t.as_str()),
+            attribute.get("value").and_then(|v| v.as_str()),
+        ) {
+            (Some(kinds), Some(values)) => (kinds, values),
+            _ => {
+                intel.unsupported += 1;
+                continue;
+            }
+        };
+        let id = attribute
+            .get("uuid")
+            .or_else(|| attribute.get("id"))
+            .and_then(|i| match i {
+                Json::String(s) => Some(s.clone()),
+                Json::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let mut techniques = attack.clone();
+        for t in misp_techniques(attribute) {
+            if !techniques.contains(&t) {
+                techniques.push(t);
+            }
+        }
+        // Composite attributes such as 'filename|md5' hold a value for each type.
+        let mut supported = false;
+        for (kind, value) in kinds.split('|').zip(values.split('|')) {
+            let kind = match Type::from_str(kind) {
+                Ok(kind) => kind,
+                Err(_) => continue,
+            };
+            if let Some(value) = normalise(kind, value) {
+                supported = true;
+                intel.indicators.push(Indicator {
+                    id: id.clone(),
+                    feed: feed.clone(),
+                    kind,
+                    value,
+
+                    attack: techniques.clone(),
+                    description: description.clone(),
+                    level: level.clone(),
+                    references: vec![],
+                });
+            }
+        }
+        if !supported {
+            intel.unsupported += 1;
+        }
+    }
+    intel
+}
+
+fn misp_techniques(object: &Json) -> Vec<String> {
+    let mut names = vec![];
+    if let Some(Json::Array(tags)) = object.get("Tag") {
+        for tag in tags {
+            if let Some(name) = tag.get("name").and_then(|n| n.as_str()) {
+                if name.contains("mitre-attack") {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+    }
+    if let Some(Json::Array(galaxies)) = object.get("Galaxy") {
+        for galaxy in galaxies {
+            if let Some(Json::Array(clusters)) = galaxy.get("GalaxyCluster") {
+                for cluster in clusters {
+                    if !cluster
+                        .get("type")
+                        .and_then(|t| t.as_str())
+                        .map(|t| t.contains("mitre-attack"))
+                        .unwrap_or_default()
+                    {
+                        continue;
+                    }
+                    if let Some(value) = cluster.get("value").and_then(|v| v.as_str()) {
+                        names.push(value.to_owned());
+                    }
+                }
+            }
+        }
+    }
+    let mut attack = vec![];
+    for name in names {
+        for t in techniques(&name) {
+            if !attack.contains(&t) {
+                attack.push(t);
+            }
+        }
+    }
+    attack
+}
+
+fn from_stix(bundle: &Json, feed: &str) -> crate::Result<Intel> {
+    let objects = match bundle.get("objects") {
+        Some(Json::Array(objects)) => objects,
+        _ => anyhow::bail!("stix bundle does not contain any objects"),
+    };
+    let ids: HashMap<&str, &Json> = objects
+        .iter()
+        .filter_map(|o| o.get("id").and_then(|i| i.as_str()).map(|i| (i, o)))
+        .collect();
+    // Indicators are linked to the techniques they detect through relationships.
+    let mut indicates: HashMap<&str, Vec<String>> = HashMap::new();
+    for object in objects {
+        if object.get("type").and_then(|t| t.as_str()) != Some("relationship")
+            || object.get("relationship_type").and_then(|t| t.as_str()) != Some("indicates")
+        {
+            continue;
+        }
+        let (source, target) = match (
+            object.get("source_ref").and_then(|s| s.as_str()),
+            object
+                .get("target_ref")
+                .and_then(|t| ids.get(t.as_str().unwrap_or_default())),
+        ) {
+            (Some(source), Some(target)) => (source, target),
+            _ => continue,
+        };
+        if target.get("type").and_then(|t| t.as_str()) == Some("attack-pattern") {
+            indicates
+                .entry(source)
+                .or_default()
+                .extend(stix_references(target).0);
+        }
+    }
+
+    let mut intel = Intel {
+        indicators: vec![],
+        unsupported: 0,
+    };
+    for object in objects {
+        if object.get("type").and_then(|t| t.as_str()) != Some("indicator")
+            || object.get("revoked").and_then(|r| r.as_bool()) == Some(true)
+        {
+            continue;
+        }
+        let (id, pattern) = match (
+            object.get("id").and_then(|i| i.as_str()),
+            object.get("pattern").and_then(|p| p.as_str()),
+        ) {
+            (Some(id), Some(pattern)) => (id, pattern),
+            _ => {
+                intel.unsupported += 1;
+                continue;
+            }
+        };
+        let kind = object.get("pattern_type").and_then(|p| p.as_str());
+        if kind.is_some() && kind != Some("stix") {
+            intel.unsupported += 1;
+            continue;
+        }
+        // NOTE: As each comparison is matched on its own, only comparisons that are joined by OR
+        // can be expressed.
+        let stripped = QUOTED.replace_all(pattern, "''").to_uppercase();
+        if [
+            " AND ",
+            "FOLLOWEDBY",
+            "!=",
+            "<",
+            ">",
+            " IN ",
+            "LIKE",
+            "MATCHES",
+            "ISSUBSET",
+            "ISSUPERSET",
+            "NOT ",
+            "REPEATS",
+            "WITHIN",
+        ]
+        .iter()
+        .any(|o| stripped.contains(o))
+        {
+            intel.unsupported += 1;
+            continue;
+        }
+        let feed = object
+            .get("created_by_ref")
+            .and_then(|c| ids.get(c.as_str().unwrap_or_default()))
+            .and_then(|i| i.get("name"))
+            .and_then(|n| n.as_str())
+            .unwrap_or(feed);
+        let description = object
+            .get("name")
+            .or_else(|| object.get("description"))
+            .and_then(|d| d.as_str())
+            .map(|d| d.to_owned());
+        let (mut attack, references) = stix_references(object);
+        if let Some(techniques) = indicates.get(id) {
+            for t in techniques {
+                if !attack.contains(t) {
+                    attack.push(t.clone());
+                }
+            }
+        }
+
+        let mut supported = false;
+        for captures in COMPARISON.captures_iter(pattern) {
+            let (object, path, value) = (&captures[1], &captures[2], &captures[3]);
+            let kind = match (object, path) {
+                ("ipv4-addr", "value") | ("ipv6-addr", "value") => Type::Ip,
+                ("domain-name", "value") => Type::Domain,
+                ("url", "value") => Type::Url,
+                ("file", "name") => Type::FileName,
+                ("file", p) if p.starts_with("hashes.") => Type::Hash,
+                ("windows-registry-key", "key") => Type::Registry,
+                ("network-traffic", p) if p.to_lowercase().contains("user-agent") => {
+                    Type::UserAgent
+                }
+                _ => continue,
+            };
+            let value = value.replace("\\'", "'").replace("\\\\", "\\");
+            if let Some(value) = normalise(kind, &value) {
+                supported = true;
+                intel.indicators.push(Indicator {
+                    id: id.to_owned(),
+                    feed: feed.to_owned(),
+                    kind,
+                    value,
+
+                    attack: attack.clone(),
+                    description: description.clone(),
+                    level: Level::High,
+                    references: references.clone(),
+                });
+            }
+        }
+        if !supported {
+            intel.unsupported += 1;
+        }
+    }
+    Ok(intel)
+}
+
+fn stix_references(object: &Json) -> (Vec<String>, Vec<String>) {
+    let mut attack = vec![];
+    let mut references = vec![];
+    if let Some(Json::Array(refs)) = object.get("external_references") {
+        for r in refs {
+            let source = r.get("source_name").and_then(|s| s.as_str());
+            if source == Some("mitre-attack") {
+                if let Some(id) = r.get("external_id").and_then(|i| i.as_str()) {
+                    attack.extend(techniques(id));
+                }
+            } else if let Some(url) = r.get("url").and_then(|u| u.as_str()) {
+                references.push(url.to_owned());
+            }
+        }
+    }
+    (attack, references)
+}
+
+fn techniques(value: &str) -> Vec<String> {
+    ATTACK
+        .find_iter(value)
+        .map(|m| m.as_str().to_uppercase())
+        .collect()
+}
+
+/// Refangs and canonicalises the value so that it can be compared against what is logged.
+fn normalise(kind: Type, value: &str) -> Option<String> {
+    let value = value
+        .trim()
+        .replace("[.]", ".")
+        .replace("(.)", ".")
+        .replace("[:]", ":");
+    if value.is_empty() {
+        return None;
+    }
+    let value = match kind {
+        Type::Ip => IpAddr::from_str(&value).ok()?.to_string(),
+        Type::Domain => value.trim_end_matches('.').to_lowercase(),
+        Type::Url => match value.get(..4) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("hxxp") => format!("http{}", &value[4..]),
+            _ => value,
+        },
+        // NOTE: Hives are logged in different forms depending on the source, so we strip them and
+        // match on the rest of the key.
+        Type::Registry => match value.split_once('\\') {
+            Some((hive, key)) if REGISTRY_HIVES.contains(&hive.to_lowercase().as_str()) => {
+                format!("\\{}", key)
+            }
+            _ => value,
+        },
+        Type::FileName | Type::Hash | Type::UserAgent => value,
+    };
+    Some(value)
+}
+
+// Looks up the field in the list with the name, when there is one.
+fn search(name: Option<&str>, mode: Mode, field: &str) -> Option<Yaml> {
+    let mut mapping = Mapping::new();
+    mapping.insert(crate::lists::key(name?, mode, field).into(), "true".into());
+    Some(Yaml::Mapping(mapping))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_from_stix() {
+        let bundle = json!({
+            "type": "bundle",
+            "objects": [
+                {
+                    "type": "identity",
+                    "id": "identity--acme",
+                    "name": "ACME CERT"
+                },
+                {
+                    "type": "indicator",
+                    "id": "indicator--c2",
+                    "created_by_ref": "identity--acme",
+                    "name": "C2 infrastructure",
+                    "pattern": "[ipv4-addr:value = '10.0.0[.]1'] OR [domain-name:value = 'Evil.com.']",
+                    "pattern_type": "stix",
+                    "external_references": [
+                        { "source_name": "mitre-attack", "external_id": "T1071" },
+                        { "source_name": "report", "url": "https://example.com/report" }
+                    ]
+                },
+                {
+                    "type": "indicator",
+                    "id": "indicator--both",
+                    "pattern": "[file:name = 'a.exe' AND file:hashes.MD5 = 'abc']",
+                    "pattern_type": "stix"
+                },
+                {
+                    "type": "indicator",
+                    "id": "indicator--revoked",
+                    "pattern": "[url:value = 'http://revoked.com']",
+                    "revoked": true
+                },
+                {
+                    "type": "attack-pattern",
+                    "id": "attack-pattern--powershell",
+                    "external_references": [
+                        { "source_name": "mitre-attack", "external_id": "T1059.001" }
+                    ]
+                },
+                {
+                    "type": "relationship",
+                    "relationship_type": "indicates",
+                    "source_ref": "indicator--c2",
+                    "target_ref": "attack-pattern--powershell"
+                }
+            ]
+        });
+        let intel = from_stix(&bundle, "feed").unwrap();
+        assert_eq!(intel.unsupported, 1);
+        assert_eq!(intel.indicators.len(), 2);
+
+        let ip = &intel.indicators[0];
+        assert_eq!(ip.id, "indicator--c2");
+        assert_eq!(ip.feed, "ACME CERT");
+        assert_eq!(ip.kind, Type::Ip);
+        assert_eq!(ip.value, "10.0.0.1");
+        assert_eq!(ip.attack, vec!["T1071", "T1059.001"]);
+        assert_eq!(ip.description.as_deref(), Some("C2 infrastructure"));
+        assert_eq!(ip.references, vec!["https://example.com/report"]);
+
+        let domain = &intel.indicators[1];
+        assert_eq!(domain.kind, Type::Domain);
+        assert_eq!(domain.value, "evil.com");
+    }
+
+    #[test]
+    fn test_from_misp() {
+        let event = json!({
+            "info": "Phishing campaign",
+            "threat_level_id": "2",
+            "Orgc": { "name": "ACME CERT" },
+            "Tag": [
+                { "name": "misp-galaxy:mitre-attack-pattern=\"Phishing - T1566\"" },
+                { "name": "tlp:white" }
+            ],
+            "Attribute": [
+                { "uuid": "attr-ip", "type": "ip-dst", "value": "192.168.1[.]10", "to_ids": true },
+                { "uuid": "attr-file", "type": "filename|md5", "value": "invoice.exe|d41d8cd98f00b204e9800998ecf8427e", "to_ids": true },
+                { "uuid": "attr-context", "type": "domain", "value": "context.com", "to_ids": false },
+                { "uuid": "attr-btc", "type": "btc", "value": "1BoatSLRHtKNngkdXEeobR76b53LETtpyT", "to_ids": true }
+            ],
+            "Object": [
+                {
+                    "Attribute": [
+                        { "id": 7, "type": "hostname", "value": "evil.com", "to_ids": true }
+                    ]
+                }
+            ]
+        });
+        let intel = from_misp(&event, "feed");
+        assert_eq!(intel.unsupported, 1);
+        let indicators = intel
+            .indicators
+            .iter()
+            .map(|i| (i.id.as_str(), i.kind, i.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            indicators,
+            vec![
+                ("attr-ip", Type::Ip, "192.168.1.10"),
+                ("attr-file", Type::FileName, "invoice.exe"),
+                ("attr-file", Type::Hash, "d41d8cd98f00b204e9800998ecf8427e"),
+                ("7", Type::Domain, "evil.com"),
+            ]
+        );
+        for indicator in &intel.indicators {
+            assert_eq!(indicator.feed, "ACME CERT");
+            assert_eq!(indicator.level, Level::Medium);
+            assert_eq!(indicator.attack, vec!["T1566"]);
+            assert_eq!(indicator.description.as_deref(), Some("Phishing campaign"));
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let intel = from_csv(
+            "type,value,id,feed\n\
+             filename,services.exe,indicator--services,Test Feed\n\
+             filename,C:\\Temp\\evil.exe,indicator--evil,Test Feed\n\
+             domain,evil.com,indicator--domain,Test Feed\n",
+            "feed",
+        )
+        .unwrap();
+        let mut lists = Lists::default();
+        let rules = intel.rules(&mut lists).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].name(), "Intel - Test Feed: file name");
+        assert_eq!(rules[1].name(), "Intel - Test Feed: domain");
+
+        let files = &rules[0];
+        let document = json!({ "Image": "C:\\Windows\\System32\\SERVICES.EXE" });
+        assert_eq!(
+            files.indicators(&document, &lists),
+            vec!["indicator--services"]
+        );
+        let document = json!({ "TargetFilename": "c:\\temp\\evil.exe" });
+        assert_eq!(files.indicators(&document, &lists), vec!["indicator--evil"]);
+        let document = json!({ "Image": "C:\\Windows\\System32\\notservices.exe" });
+        assert!(files.indicators(&document, &lists).is_empty());
+
+        let domains = &rules[1];
+        let document = json!({ "QueryName": "www.evil.com" });
+        assert_eq!(
+            domains.indicators(&document, &lists),
+            vec!["indicator--domain"]
+        );
+        let document = json!({ "QueryName": "notevil.com" });
+        assert!(domains.indicators(&document, &lists).is_empty());
+    }
+}
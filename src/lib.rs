@@ -4,22 +4,44 @@ extern crate anyhow;
 pub(crate) use anyhow::Result;
 
 pub use analyse::shimcache::ShimcacheAnalyzer;
+pub use attack::Layer as AttackLayer;
+pub use convert::{Conversion, Converter};
 pub use file::{evtx, get_files, Document, Kind as FileKind, Reader};
-pub use hunt::{Hunter, HunterBuilder};
+pub use hunt::{Coverage as RuleCoverage, Hunter, HunterBuilder};
+pub use intel::load as load_intel;
+pub use lists::Lists;
+pub use risk::{Scorer as RiskScorer, Weights as RiskWeights};
 pub use rule::{
-    lint, load, sigma, Filter, Kind as RuleKind, Level as RuleLevel, Status as RuleStatus,
+    lint, load, sigma,
+    sigma::{Config as SigmaConfig, Unresolved as SigmaUnresolved},
+    Filter, Kind as RuleKind, Level as RuleLevel, Status as RuleStatus,
 };
 pub use search::{Searcher, SearcherBuilder};
+pub use tester::{Report as TestReport, Tester};
+pub use timestamp::Format as TimestampFormat;
 pub use write::{set_writer, Format, Writer, WRITER};
 
 #[macro_use]
 mod write;
 
 mod analyse;
+mod attack;
 pub mod cli;
+mod convert;
+mod dedup;
 mod ext;
 mod file;
 mod hunt;
+mod intel;
+mod lists;
+mod modifiers;
+mod pipeline;
+mod profile;
+mod risk;
 mod rule;
 mod search;
+mod state;
+mod suppress;
+mod tester;
+mod timestamp;
 mod value;
@@ -0,0 +1,409 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+use once_cell::sync::OnceCell;
+use rustc_hash::{FxHashMap, FxHashSet};
+use tau_engine::{
+    core::parser::{Expression, MatchType, Search},
+    Document, Value as Tau,
+};
+
+/// The prefix used to reference a list from a filter, i.e. `'@list:bad_ips.txt'`.
+pub const PREFIX: &str = "@list:";
+
+/// The lists that a hunter's rules reference, keyed by the name that lookups reference them by,
+/// which is the escaped canonical path of a list loaded from a file. Each list is only loaded once
+/// no matter how many filters reference it.
+#[derive(Default)]
+pub struct Lists(FxHashMap<String, List>);
+
+impl Lists {
+    /// Adds a list that was built in memory, returning the name that lookups reference it by.
+    pub fn insert(&mut self, list: List) -> String {
+        // NOTE: Canonical paths are absolute, so they cannot collide with these names.
+        let name = format!("#{}", self.0.len());
+        self.0.insert(name.clone(), list);
+        name
+    }
+
+    /// Loads the list that a lookup references, other fields are ignored.
+    pub fn load(&mut self, field: &str) -> crate::Result<()> {
+        let name = match field.strip_prefix(PREFIX).and_then(parse) {
+            Some((_, name, _)) => name,
+            None => return Ok(()),
+        };
+        if self.0.contains_key(name) {
+            return Ok(());
+        }
+        let path = unescape(name);
+        let list = match List::load(Path::new(&path)) {
+            Ok(list) => list,
+            Err(e) => anyhow::bail!("could not load list '{}' - {}", path, e),
+        };
+        self.0.insert(name.to_owned(), list);
+        Ok(())
+    }
+
+    /// Resolves a list lookup that was produced by `rewrite`, the key is in the form
+    /// `<name>:<field>` where the name is marked with the mode of the lookup when it is not an
+    /// exact match.
+    pub fn find(&self, document: &dyn Document, key: &str) -> Option<Tau<'static>> {
+        let (mode, name, field) = parse(key)?;
+        let value = document.find(field)?.to_string()?;
+        if self.0.get(name)?.matches(mode, &value) {
+            Some(Tau::String(Cow::Borrowed("true")))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the labels of the list entries that a lookup matched in the document.
+    pub fn labels(&self, document: &dyn Document, key: &str) -> Vec<String> {
+        let (mode, name, field) = match key.strip_prefix(PREFIX).and_then(parse) {
+            Some(parsed) => parsed,
+            None => return vec![],
+        };
+        let value = match document.find(field).and_then(|v| v.to_string()) {
+            Some(value) => value,
+            None => return vec![],
+        };
+        match self.0.get(name) {
+            Some(list) => list.labels(mode, &value),
+            None => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (&self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                (u32::from(*network) & mask) == (u32::from(*ip) & mask)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                (u128::from(*network) & mask) == (u128::from(*ip) & mask)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (network, prefix) = match s.split_once('/') {
+            Some((network, prefix)) => (network, Some(prefix)),
+            None => (s, None),
+        };
+        let network = IpAddr::from_str(network.trim())?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse::<u8>()?,
+            None => max,
+        };
+        if prefix > max {
+            anyhow::bail!("invalid prefix length '{}' for '{}'", prefix, s);
+        }
+        Ok(Self { network, prefix })
+    }
+}
+
+/// How a value is compared against the entries of a list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Contains,
+    EndsWith,
+    Exact,
+}
+
+impl Mode {
+    // NOTE: The mode is marked on the list of a rewritten lookup, i.e. `'@list:*#0:CommandLine'`.
+    fn marker(&self) -> Option<char> {
+        match self {
+            Self::Contains => Some('*'),
+            Self::EndsWith => Some('$'),
+            Self::Exact => None,
+        }
+    }
+}
+
+/// A set of values loaded from a newline delimited file, entries are matched case-insensitively
+/// and entries in CIDR notation match any address within the network.
+#[derive(Default)]
+pub struct List {
+    cidrs: Vec<Cidr>,
+    values: FxHashSet<String>,
+    // NOTE: Only set for lists that are built in memory, i.e. from intel, so that a match can be
+    // traced back to where its entries came from.
+    labels: FxHashMap<String, Vec<String>>,
+    // NOTE: Only built when the list is used for substring matches.
+    matcher: OnceCell<(AhoCorasick, Vec<String>)>,
+}
+
+impl List {
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        let file = File::open(path)?;
+        let mut list = Self::default();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.contains('/') {
+                if let Ok(cidr) = Cidr::from_str(line) {
+                    list.cidrs.push(cidr);
+                    continue;
+                }
+            }
+            list.values.insert(line.to_lowercase());
+        }
+        Ok(list)
+    }
+
+    /// Adds an entry to the list, along with a label that is returned when the entry matches.
+    pub fn insert(&mut self, value: &str, label: &str) {
+        let value = value.trim().to_lowercase();
+        let labels = self.labels.entry(value.clone()).or_default();
+        if !labels.iter().any(|l| l == label) {
+            labels.push(label.to_owned());
+        }
+        self.values.insert(value);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cidrs.is_empty() && self.values.is_empty()
+    }
+
+    pub fn contains(&self, value: &str) -> bool {
+        let value = value.trim();
+        if self.values.contains(&value.to_lowercase()) {
+            return true;
+        }
+        if !self.cidrs.is_empty() {
+            if let Ok(ip) = IpAddr::from_str(value) {
+                return self.cidrs.iter().any(|c| c.contains(&ip));
+            }
+        }
+        false
+    }
+
+    /// Returns true when the value ends with any of the list's entries, CIDR entries are not used.
+    pub fn ends(&self, value: &str) -> bool {
+        let value = value.trim().to_lowercase();
+        value
+            .char_indices()
+            .any(|(i, _)| self.values.contains(&value[i..]))
+    }
+
+    /// Returns true when the value contains any of the list's entries, CIDR entries are not used.
+    pub fn within(&self, value: &str) -> bool {
+        if self.values.is_empty() {
+            return false;
+        }
+        // NOTE: Entries are lowercased with Unicode rules, which the matcher does not apply.
+        self.matcher().0.is_match(&value.trim().to_lowercase())
+    }
+
+    pub fn matches(&self, mode: Mode, value: &str) -> bool {
+        match mode {
+            Mode::Contains => self.within(value),
+            Mode::EndsWith => self.ends(value),
+            Mode::Exact => self.contains(value),
+        }
+    }
+
+    /// Returns the labels of the entries that the value matches.
+    pub fn labels(&self, mode: Mode, value: &str) -> Vec<String> {
+        if self.labels.is_empty() {
+            return vec![];
+        }
+        let value = value.trim().to_lowercase();
+        let entries: Vec<&str> = match mode {
+            Mode::Contains => {
+                let (matcher, entries) = self.matcher();
+                matcher
+                    .find_overlapping_iter(&value)
+                    .map(|m| entries[m.pattern()].as_str())
+                    .collect()
+            }
+            Mode::EndsWith => value
+                .char_indices()
+                .map(|(i, _)| &value[i..])
+                .filter(|v| self.values.contains(*v))
+                .collect(),
+            Mode::Exact => vec![value.as_str()],
+        };
+        let mut labels = vec![];
+        for entry in entries {
+            for label in self.labels.get(entry).into_iter().flatten() {
+                if !labels.contains(label) {
+                    labels.push(label.clone());
+                }
+            }
+        }
+        labels
+    }
+
+    fn matcher(&self) -> &(AhoCorasick, Vec<String>) {
+        self.matcher.get_or_init(|| {
+            let entries = self.values.iter().cloned().collect::<Vec<_>>();
+            let matcher = AhoCorasickBuilder::new()
+                .ascii_case_insensitive(true)
+                .dfa(true)
+                .build(&entries);
+            (matcher, entries)
+        })
+    }
+}
+
+/// Returns the key of the virtual field that looks up a field's value in the list with the name.
+pub fn key(name: &str, mode: Mode, field: &str) -> String {
+    let mut key = PREFIX.to_owned();
+    if let Some(marker) = mode.marker() {
+        key.push(marker);
+    }
+    format!("{}{}:{}", key, escape(name), field)
+}
+
+// Splits a key produced by `key` (without its prefix) into its mode, list name and field.
+fn parse(key: &str) -> Option<(Mode, &str, &str)> {
+    let (mode, key) = match key.chars().next()? {
+        '*' => (Mode::Contains, &key[1..]),
+        '$' => (Mode::EndsWith, &key[1..]),
+        _ => (Mode::Exact, key),
+    };
+    let (name, field) = key.split_once(':')?;
+    Some((mode, name, field))
+}
+
+// NOTE: Paths can contain the separators of a key, i.e. `C:\lists\x.txt`, and a pipe would be
+// taken as a modifier when intel keys are parsed as Sigma, so they are escaped.
+fn escape(name: &str) -> String {
+    name.replace('%', "%25")
+        .replace(':', "%3A")
+        .replace('|', "%7C")
+}
+
+fn unescape(name: &str) -> String {
+    name.replace("%7C", "|")
+        .replace("%3A", ":")
+        .replace("%25", "%")
+}
+
+/// Replaces any searches that reference a list with a lookup against that list, relative paths
+/// are resolved against the directory provided, falling back to the current working directory.
+/// A reference wrapped in wildcards, i.e. `'*@list:bad_urls.txt*'`, matches values that contain
+/// any entry of the list, and one with a leading wildcard values that end with an entry.
+pub fn rewrite(expression: Expression, base: Option<&Path>) -> crate::Result<Expression> {
+    let expression = match expression {
+        Expression::BooleanGroup(x, expressions) => {
+            let expressions = expressions
+                .into_iter()
+                .map(|e| rewrite(e, base))
+                .collect::<crate::Result<Vec<_>>>()?;
+            Expression::BooleanGroup(x, expressions)
+        }
+        Expression::BooleanExpression(left, x, right) => Expression::BooleanExpression(
+            Box::new(rewrite(*left, base)?),
+            x,
+            Box::new(rewrite(*right, base)?),
+        ),
+        Expression::Match(x, e) => Expression::Match(x, Box::new(rewrite(*e, base)?)),
+        Expression::Negate(e) => Expression::Negate(Box::new(rewrite(*e, base)?)),
+        Expression::Search(search, field, cast) => match reference(&search) {
+            Some((path, mode)) => {
+                let path = resolve(Path::new(path), base)?;
+                Expression::Search(
+                    Search::Exact("true".to_owned()),
+                    key(&path, mode, &field),
+                    false,
+                )
+            }
+            None => Expression::Search(search, field, cast),
+        },
+        _ => expression,
+    };
+    Ok(expression)
+}
+
+// Returns the path of the list that a search references and how it is matched, case-insensitive
+// values (as produced from Sigma rules) are parsed into single pattern searches.
+fn reference(search: &Search) -> Option<(&str, Mode)> {
+    let (value, mode) = match search {
+        Search::Contains(value) => (value, Mode::Contains),
+        Search::EndsWith(value) => (value, Mode::EndsWith),
+        Search::Exact(value) => (value, Mode::Exact),
+        Search::AhoCorasick(_, matches, _) if matches.len() == 1 => match &matches[0] {
+            MatchType::Contains(value) => (value, Mode::Contains),
+            MatchType::EndsWith(value) => (value, Mode::EndsWith),
+            MatchType::Exact(value) => (value, Mode::Exact),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    value.strip_prefix(PREFIX).map(|path| (path, mode))
+}
+
+// Resolves the path of a list, so that each list is only loaded once by the hunter.
+fn resolve(path: &Path, base: Option<&Path>) -> crate::Result<String> {
+    let path = match base {
+        Some(base) if path.is_relative() && base.join(path).exists() => base.join(path),
+        _ => path.to_path_buf(),
+    };
+    match path.canonicalize() {
+        Ok(path) => Ok(path.to_string_lossy().into_owned()),
+        Err(e) => anyhow::bail!("could not load list '{}' - {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists_find() {
+        let mut list = List::default();
+        list.insert("évil.exe", "indicator--evil");
+        let mut lists = Lists::default();
+        let name = lists.insert(list);
+
+        let contains = key(&name, Mode::Contains, "CommandLine");
+        let contains = contains.strip_prefix(PREFIX).unwrap();
+        let document = serde_json::json!({ "CommandLine": "start ÉVIL.exe /q" });
+        assert!(lists.find(&document, contains).is_some());
+        let document = serde_json::json!({ "CommandLine": "start good.exe" });
+        assert!(lists.find(&document, contains).is_none());
+
+        let exact = key(&name, Mode::Exact, "CommandLine");
+        let document = serde_json::json!({ "CommandLine": "ÉVIL.EXE" });
+        assert!(lists
+            .find(&document, exact.strip_prefix(PREFIX).unwrap())
+            .is_some());
+        assert_eq!(lists.labels(&document, &exact), vec!["indicator--evil"]);
+    }
+
+    #[test]
+    fn test_key_escaped() {
+        let key = key("C:\\lists\\a|b%.txt", Mode::EndsWith, "Image");
+        assert_eq!(key, "@list:$C%3A\\lists\\a%7Cb%25.txt:Image");
+        let (mode, name, field) = parse(key.strip_prefix(PREFIX).unwrap()).unwrap();
+        assert_eq!(mode, Mode::EndsWith);
+        assert_eq!(unescape(name), "C:\\lists\\a|b%.txt");
+        assert_eq!(field, "Image");
+    }
+}
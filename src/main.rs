@@ -5,7 +5,11 @@ extern crate term_size;
 use std::fs::{self, File};
 use std::io::BufRead;
 use std::path::PathBuf;
-use std::{collections::HashSet, io::BufReader};
+use std::time::{Duration, Instant};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::BufReader,
+};
 
 use anyhow::{Context, Result};
 use bytesize::ByteSize;
@@ -13,10 +17,13 @@ use chrono::NaiveDateTime;
 use chrono_tz::Tz;
 
 use clap::{Parser, Subcommand};
+use uuid::Uuid;
 
 use chainsaw::{
-    cli, get_files, lint as lint_rule, load as load_rule, set_writer, Document, Filter, Format,
-    Hunter, Reader, RuleKind, RuleLevel, RuleStatus, Searcher, ShimcacheAnalyzer, Writer,
+    cli, get_files, lint as lint_rule, load as load_rule, load_intel, set_writer, sigma,
+    AttackLayer, Converter, Document, Filter, Format, Hunter, Lists, Reader, RiskScorer,
+    RiskWeights, RuleCoverage, RuleKind, RuleLevel, RuleStatus, Searcher, ShimcacheAnalyzer,
+    SigmaConfig, SigmaUnresolved, Tester, TimestampFormat, Writer,
 };
 
 #[derive(Parser)]
@@ -52,6 +59,29 @@ struct Args {
 
 #[derive(Subcommand)]
 enum Command {
+    /// Convert Sigma rules into Chainsaw rules, or Chainsaw rules into Sigma rules.
+    Convert {
+        /// The path to a collection of rules to convert.
+        path: PathBuf,
+        /// The kind of rule to convert from: chainsaw or sigma
+        #[arg(long = "kind")]
+        kind: RuleKind,
+
+        /// A mapping file used to resolve the fields of the rules (required for Sigma rules).
+        #[arg(short = 'm', long = "mapping", required_if_eq("kind", "sigma"))]
+        mapping: Option<PathBuf>,
+        /// A directory to write the converted rules to, instead of printing them.
+        /// (the paths of the rules relative to the provided path are kept)
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+        /// Supress informational output.
+        #[arg(short = 'q')]
+        quiet: bool,
+        /// A file containing the values for the placeholders used by Sigma rules.
+        #[arg(long = "sigma-config")]
+        sigma_config: Option<PathBuf>,
+    },
+
     /// Dump an artefact into a different format.
     Dump {
         /// The path to an artefact to dump.
@@ -92,12 +122,30 @@ enum Command {
         #[arg(short = 'r', long = "rule", number_of_values = 1)]
         rule: Option<Vec<PathBuf>>,
 
+        /// Write an ATT&CK Navigator layer of the loaded rules' technique coverage to the path
+        /// provided. (hunting is skipped when no paths are provided)
+        #[arg(long = "attack-coverage")]
+        attack_coverage: Option<PathBuf>,
+        /// Write an ATT&CK Navigator layer scored by the number of detections to the path provided.
+        #[arg(long = "attack-layer")]
+        attack_layer: Option<PathBuf>,
         /// Set the column width for the tabular output.
         #[arg(long = "column-width", conflicts_with = "json")]
         column_width: Option<u32>,
         /// Print the output in csv format.
         #[arg(group = "format", long = "csv", requires("output"))]
         csv: bool,
+        /// Drop duplicate records found across overlapping sources (i.e. archived logs).
+        /// (the other sources are not listed against detections when streaming with --jsonl)
+        #[arg(long = "dedup")]
+        dedup: bool,
+        /// The fields used to identify duplicate records in non-EVTX artefacts.
+        #[arg(long = "dedup-key", number_of_values = 1, requires = "dedup")]
+        dedup_key: Option<Vec<String>>,
+        /// List the mapping groups that each Sigma rule runs under and the fields they do not map,
+        /// without hunting.
+        #[arg(long = "explain", conflicts_with_all = ["csv", "jsonl", "log", "preprocess"])]
+        explain: bool,
         /// Only hunt through files with the provided extension.
         #[arg(long = "extension", number_of_values = 1)]
         extension: Option<Vec<String>>,
@@ -108,6 +156,9 @@ enum Command {
         /// Print the full values for the tabular output.
         #[arg(long = "full", conflicts_with = "json")]
         full: bool,
+        /// An intel feed to hunt for indicators from (STIX 2.1 bundle, MISP event or CSV).
+        #[arg(long = "intel", number_of_values = 1, requires("mapping"))]
+        intel: Option<Vec<PathBuf>>,
         /// Print the output in json format.
         #[arg(group = "format", short = 'j', long = "json")]
         json: bool,
@@ -126,6 +177,9 @@ enum Command {
         /// Output the timestamp using the local machine's timestamp.
         #[arg(long = "local", group = "tz")]
         local: bool,
+        /// Show the conditions and fields that caused each detection (table, csv and json output).
+        #[arg(long = "matched", conflicts_with = "preprocess")]
+        matched: bool,
         /// Display additional metadata in the tablar output.
         #[arg(long = "metadata", conflicts_with = "json")]
         metadata: bool,
@@ -138,18 +192,53 @@ enum Command {
         /// (BETA) Enable preprocessing, which can result in increased performance.
         #[arg(long = "preprocess")]
         preprocess: bool,
+        /// Print the time spent parsing, mapping, matching and writing output, along with the
+        /// slowest rules and mapping groups.
+        #[arg(long = "profile")]
+        profile: bool,
         /// Supress informational output.
         #[arg(short = 'q')]
         quiet: bool,
+        /// Print a ranked summary of the riskiest hosts, users and IPs (default: top 10).
+        #[arg(
+            long = "risk",
+            num_args = 0..=1,
+            default_missing_value = "10",
+            conflicts_with_all = ["csv", "json", "jsonl"]
+        )]
+        risk: Option<usize>,
+        /// Write the risk scores for all entities to the path provided in json format.
+        #[arg(long = "risk-json")]
+        risk_json: Option<PathBuf>,
+        /// A file containing the weights used to calculate risk scores.
+        #[arg(long = "risk-weights")]
+        risk_weights: Option<PathBuf>,
         /// A path containing Sigma rules to hunt with.
         #[arg(short = 's', long = "sigma", number_of_values = 1, requires("mapping"))]
         sigma: Option<Vec<PathBuf>>,
+        /// A file containing the values for the placeholders used by Sigma rules.
+        #[arg(long = "sigma-config", requires("sigma"))]
+        sigma_config: Option<PathBuf>,
+        /// A path containing Sigma filter rules to tune the Sigma rules with.
+        #[arg(long = "sigma-filter", number_of_values = 1, requires("sigma"))]
+        sigma_filter: Option<Vec<PathBuf>>,
         /// Continue to hunt when an error is encountered.
         #[arg(long = "skip-errors")]
         skip_errors: bool,
+        /// A file used to save progress, so that later hunts only process new records.
+        /// (files are only skipped once their results have been output, which is after each file
+        /// when using --jsonl)
+        #[arg(long = "state")]
+        state: Option<PathBuf>,
         /// Restrict loaded rules to specified statuses.
         #[arg(long = "status", number_of_values = 1)]
         status: Vec<RuleStatus>,
+        /// A suppression file used to drop known-good detections.
+        #[arg(long = "suppress", number_of_values = 1)]
+        suppress: Option<Vec<PathBuf>>,
+        /// Tag suppressed detections instead of dropping them (JSON output only).
+        #[arg(long = "tag-suppressed", requires = "suppress")]
+        tag_suppressed: bool,
         /// Output the timestamp using the timezone provided.
         #[arg(long = "timezone", group = "tz")]
         timezone: Option<Tz>,
@@ -157,15 +246,25 @@ enum Command {
         /// (YYYY-MM-ddTHH:mm:SS)
         #[arg(long = "to")]
         to: Option<NaiveDateTime>,
+        /// A path containing YARA rules to scan the fields of documents with.
+        #[arg(long = "yara", number_of_values = 1)]
+        yara: Option<Vec<PathBuf>>,
+        /// The fields to scan with the YARA rules, by their mapped names when a mapping is provided.
+        /// (defaults to CommandLine, ParentCommandLine and ScriptBlockText)
+        #[arg(long = "yara-field", number_of_values = 1, requires("yara"))]
+        yara_field: Option<Vec<String>>,
     },
 
     /// Lint provided rules to ensure that they load correctly
     Lint {
         /// The path to a collection of rules.
         path: PathBuf,
-        /// The kind of rule to lint: chainsaw, sigma or stalker
+        /// The kind of rule to lint: chainsaw, sigma or yara
         #[arg(long = "kind")]
         kind: RuleKind,
+        /// A file containing the values for the placeholders used by Sigma rules.
+        #[arg(long = "sigma-config")]
+        sigma_config: Option<PathBuf>,
         /// Output tau logic.
         #[arg(short = 't', long = "tau")]
         tau: bool,
@@ -227,6 +326,9 @@ enum Command {
         /// The field that contains the timestamp.
         #[arg(long = "timestamp")]
         timestamp: Option<String>,
+        /// The format of the timestamp: rfc3339, epoch, epoch_ms, filetime or strftime:<format>.
+        #[arg(long = "timestamp-format", requires = "timestamp")]
+        timestamp_format: Option<TimestampFormat>,
         /// Output the timestamp using the timezone provided.
         #[arg(long = "timezone", group = "tz")]
         timezone: Option<Tz>,
@@ -236,6 +338,35 @@ enum Command {
         to: Option<NaiveDateTime>,
     },
 
+    /// Test detection rules against the sample documents that they embed or reference.
+    Test {
+        /// The path to a collection of rules to test.
+        rules: Option<PathBuf>,
+
+        /// A mapping file to tell Chainsaw how to use third-party rules.
+        #[arg(short = 'm', long = "mapping", number_of_values = 1)]
+        mapping: Option<Vec<PathBuf>>,
+        /// A path containing additional rules to test.
+        #[arg(short = 'r', long = "rule", number_of_values = 1)]
+        rule: Option<Vec<PathBuf>>,
+
+        /// Print the output in json format.
+        #[arg(short = 'j', long = "json")]
+        json: bool,
+        /// Supress informational output.
+        #[arg(short = 'q')]
+        quiet: bool,
+        /// A path containing Sigma rules to test.
+        #[arg(short = 's', long = "sigma", number_of_values = 1, requires("mapping"))]
+        sigma: Option<Vec<PathBuf>>,
+        /// A file containing the values for the placeholders used by Sigma rules.
+        #[arg(long = "sigma-config", requires("sigma"))]
+        sigma_config: Option<PathBuf>,
+        /// A path containing Sigma filter rules to tune the Sigma rules with.
+        #[arg(long = "sigma-filter", number_of_values = 1, requires("sigma"))]
+        sigma_filter: Option<Vec<PathBuf>>,
+    },
+
     /// Perform various analyses on artifacts
     Analyse {
         #[command(subcommand)]
@@ -340,6 +471,33 @@ fn init_writer(output: Option<PathBuf>, csv: bool, json: bool, quiet: bool) -> c
     Ok(())
 }
 
+fn load_sigma_config(
+    path: &Option<PathBuf>,
+    filters: &Option<Vec<PathBuf>>,
+) -> Result<SigmaConfig> {
+    let mut config = match path {
+        Some(path) => match SigmaConfig::load(path) {
+            Ok(config) => config,
+            Err(e) => anyhow::bail!("Provided Sigma config file is invalid - {}", e),
+        },
+        None => SigmaConfig::default(),
+    };
+    let extensions = Some(HashSet::from(["yml".to_owned(), "yaml".to_owned()]));
+    for path in filters.iter().flatten() {
+        for file in get_files(path, &extensions, false)? {
+            match sigma::load(&file, &config) {
+                Ok(collection) => config.filters.extend(collection.filters),
+                Err(e) => anyhow::bail!(
+                    "Provided Sigma filter file '{}' is invalid - {}",
+                    file.display(),
+                    e
+                ),
+            }
+        }
+    }
+    Ok(config)
+}
+
 fn run() -> Result<()> {
     let args = Args::parse();
     if let Some(num_threads) = args.num_threads {
@@ -348,6 +506,101 @@ fn run() -> Result<()> {
             .build_global()?;
     }
     match args.cmd {
+        Command::Convert {
+            path,
+            kind,
+            mapping,
+            output,
+            quiet,
+            sigma_config,
+        } => {
+            init_writer(None, false, false, quiet)?;
+            if !args.no_banner {
+                print_title();
+            }
+            cs_eprintln!("[+] Converting {} rules...", kind);
+            let config = load_sigma_config(&sigma_config, &None)?;
+            let converter = Converter::new(mapping.as_deref(), config)?;
+            if let Some(output) = &output {
+                fs::create_dir_all(output)?;
+            }
+            let extensions = Some(HashSet::from(["yml".to_owned(), "yaml".to_owned()]));
+            let mut count = 0;
+            let mut failed = 0;
+            let mut written = HashSet::new();
+            for file in get_files(&path, &extensions, false)? {
+                let file_name = match file
+                    .display()
+                    .to_string()
+                    .strip_prefix(&path.display().to_string())
+                {
+                    Some(e) => e.to_string(),
+                    None => file.display().to_string(),
+                };
+                let conversion = match converter.convert(&kind, &file) {
+                    Ok(conversion) => conversion,
+                    Err(e) => {
+                        failed += 1;
+                        cs_eprintln!("[!] {}: {}", file_name, e);
+                        continue;
+                    }
+                };
+                for warning in &conversion.warnings {
+                    cs_eyellowln!("[!] {}: {}", file_name, warning);
+                }
+                // NOTE: The directory structure is kept so that rules with the same name in
+                // different directories do not overwrite each other.
+                let relative = match file.strip_prefix(&path) {
+                    Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+                    _ => PathBuf::from(file.file_name().unwrap_or_default()),
+                };
+                let stem = relative
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let total = conversion.rules.len();
+                let mut outputs = Vec::with_capacity(total);
+                for (i, rule) in conversion.rules.into_iter().enumerate() {
+                    let yaml = serde_yaml::to_string(&rule)?;
+                    let name = if total > 1 {
+                        format!("{}-{}.yml", stem, i + 1)
+                    } else {
+                        format!("{}.yml", stem)
+                    };
+                    outputs.push((relative.with_file_name(name), yaml));
+                }
+                match &output {
+                    Some(output) => {
+                        if let Some((existing, _)) =
+                            outputs.iter().find(|(name, _)| written.contains(name))
+                        {
+                            failed += 1;
+                            cs_eprintln!(
+                                "[!] {}: output '{}' was already written by another rule",
+                                file_name,
+                                output.join(existing).display()
+                            );
+                            continue;
+                        }
+                        for (name, yaml) in outputs {
+                            let target = output.join(&name);
+                            if let Some(parent) = target.parent() {
+                                fs::create_dir_all(parent)?;
+                            }
+                            fs::write(target, format!("---\n{}", yaml))?;
+                            written.insert(name);
+                        }
+                    }
+                    None => {
+                        for (_, yaml) in outputs {
+                            cs_println!("---\n{}", yaml);
+                        }
+                    }
+                }
+                count += 1;
+            }
+            cs_eprintln!("[+] Converted {} rules out of {}", count, count + failed);
+        }
         Command::Dump {
             path,
 
@@ -421,26 +674,44 @@ fn run() -> Result<()> {
             rule,
 
             load_unknown,
+            attack_coverage,
+            attack_layer,
             mut column_width,
             csv,
+            dedup,
+            dedup_key,
+            explain,
             extension,
             from,
             full,
+            intel,
             json,
             jsonl,
             kind,
             level,
             local,
+            matched,
             metadata,
             output,
             log,
             preprocess,
+            profile,
             quiet,
+            risk,
+            risk_json,
+            risk_weights,
             sigma,
+            sigma_config,
+            sigma_filter,
             skip_errors,
+            state,
             status,
+            suppress,
+            tag_suppressed,
             timezone,
             to,
+            yara,
+            yara_field,
         } => {
             if column_width.is_none() {
                 column_width = resolve_col_width();
@@ -464,7 +735,7 @@ fn run() -> Result<()> {
                 print_title();
             }
             let mut rs = vec![];
-            if rule.is_some() || sigma.is_some() {
+            if rule.is_some() || sigma.is_some() || intel.is_some() || yara.is_some() {
                 if let Some(rules) = rules {
                     let mut paths = vec![rules];
                     paths.extend(path);
@@ -503,16 +774,33 @@ fn run() -> Result<()> {
             } else {
                 Some(HashSet::from_iter(status.into_iter()))
             };
+            let config = load_sigma_config(&sigma_config, &sigma_filter)?;
             let mut failed = 0;
             let mut count = 0;
+            let mut unresolved = 0;
+            let mut placeholders = BTreeSet::new();
             let mut rs = vec![];
+            let mut sources = vec![];
+            sources.extend(sigma_config);
+            for path in sigma_filter.iter().flatten() {
+                let extensions = Some(HashSet::from(["yml".to_owned(), "yaml".to_owned()]));
+                sources.extend(get_files(path, &extensions, false)?);
+            }
             for path in &rules {
                 for file in get_files(path, &None, skip_errors)? {
-                    match load_rule(RuleKind::Chainsaw, &file, &kinds, &levels, &statuses) {
+                    match load_rule(
+                        RuleKind::Chainsaw,
+                        &file,
+                        &kinds,
+                        &levels,
+                        &statuses,
+                        &config,
+                    ) {
                         Ok(r) => {
                             if !r.is_empty() {
                                 count += 1;
-                                rs.extend(r)
+                                rs.extend(r);
+                                sources.push(file);
                             }
                         }
                         Err(_) => {
@@ -523,23 +811,66 @@ fn run() -> Result<()> {
             }
             for path in &sigma {
                 for file in get_files(path, &None, skip_errors)? {
-                    match load_rule(RuleKind::Sigma, &file, &kinds, &levels, &statuses) {
+                    match load_rule(RuleKind::Sigma, &file, &kinds, &levels, &statuses, &config) {
                         Ok(r) => {
                             if !r.is_empty() {
                                 count += 1;
-                                rs.extend(r)
+                                rs.extend(r);
+                                sources.push(file);
                             }
                         }
-                        Err(_) => {
+                        Err(e) => {
+                            if let Some(placeholder) = e.downcast_ref::<SigmaUnresolved>() {
+                                placeholders.insert(format!("%{}%", placeholder.0));
+                                unresolved += 1;
+                            }
                             failed += 1;
                         }
                     }
                 }
             }
+            if unresolved > 0 {
+                cs_eyellowln!(
+                    "[!] {} Sigma rules were not loaded due to unresolved placeholders: {}",
+                    unresolved,
+                    placeholders.into_iter().collect::<Vec<_>>().join(", ")
+                );
+            }
             if failed > 500 && sigma.is_empty() {
                 cs_eyellowln!("[!] {} rules failed to load, ensure Sigma rule paths are specified with the '-s' flag", failed);
             }
-            if count == 0 {
+            let mut indicators = 0;
+            let mut unsupported = 0;
+            let mut lists = Lists::default();
+            for path in intel.unwrap_or_default() {
+                let intel = match load_intel(&path) {
+                    Ok(intel) => intel,
+                    Err(e) => anyhow::bail!("Provided intel file is invalid - {}", e),
+                };
+                let mut r = intel.rules(&mut lists)?;
+                if let Some(levels) = levels.as_ref() {
+                    r.retain(|r| levels.contains(r.level()));
+                }
+                indicators += intel
+                    .indicators
+                    .iter()
+                    .filter(|i| {
+                        levels
+                            .as_ref()
+                            .map(|l| l.contains(&i.level))
+                            .unwrap_or(true)
+                    })
+                    .count();
+                unsupported += intel.unsupported;
+                rs.extend(r);
+                sources.push(path);
+            }
+            let mut yaras = vec![];
+            for path in yara.unwrap_or_default() {
+                let extensions = Some(HashSet::from(["yar".to_owned(), "yara".to_owned()]));
+                yaras.extend(get_files(&path, &extensions, skip_errors)?);
+            }
+            if count == 0 && indicators == 0 && yaras.is_empty() {
                 return Err(anyhow::anyhow!(
                     "No valid detection rules were found in the provided paths",
                 ));
@@ -553,18 +884,53 @@ fn run() -> Result<()> {
             } else {
                 cs_eprintln!("[+] Loaded {} detection rules", count);
             }
+            if !config.filters.is_empty() {
+                cs_eprintln!("[+] Loaded {} Sigma filters", config.filters.len());
+            }
+            for filter in config.unreferenced(&rs) {
+                cs_eyellowln!(
+                    "[!] Sigma filter '{}' does not reference any of the loaded rules",
+                    filter.name
+                );
+            }
+            if indicators > 0 || unsupported > 0 {
+                if unsupported > 0 {
+                    cs_eprintln!(
+                        "[+] Loaded {} intel indicators ({} unsupported)",
+                        indicators,
+                        unsupported
+                    );
+                } else {
+                    cs_eprintln!("[+] Loaded {} intel indicators", indicators);
+                }
+            }
 
             let rules = rs;
             let mut hunter = Hunter::builder()
                 .rules(rules)
+                .lists(lists)
+                .sources(sources)
                 .mappings(mapping.unwrap_or_default())
+                .dedup(dedup)
+                .dedup_keys(dedup_key.unwrap_or_default())
                 .load_unknown(load_unknown)
                 .local(local)
+                .matches(matched)
                 .preprocess(preprocess)
-                .skip_errors(skip_errors);
+                .profile(profile)
+                .skip_errors(skip_errors)
+                .suppressions(suppress.unwrap_or_default());
+            if !yaras.is_empty() {
+                hunter = hunter
+                    .yara(yaras)
+                    .yara_fields(yara_field.unwrap_or_default());
+            }
             if let Some(from) = from {
                 hunter = hunter.from(from);
             }
+            if let Some(state) = state {
+                hunter = hunter.state(state);
+            }
             if let Some(timezone) = timezone {
                 hunter = hunter.timezone(timezone);
             }
@@ -572,6 +938,54 @@ fn run() -> Result<()> {
                 hunter = hunter.to(to);
             }
             let hunter = hunter.build()?;
+            if !hunter.suppressions().is_empty() {
+                cs_eprintln!("[+] Loaded {} suppressions", hunter.suppressions().len());
+            }
+            let yara = hunter
+                .rules()
+                .values()
+                .filter(|r| r.is_kind(&RuleKind::Yara))
+                .count();
+            if yara > 0 {
+                cs_eprintln!("[+] Loaded {} YARA rules", yara);
+            }
+            if let Some(coverage) = &attack_coverage {
+                let layer = AttackLayer::coverage(hunter.rules());
+                let file = File::create(coverage)?;
+                serde_json::to_writer_pretty(file, &layer)?;
+                cs_eprintln!(
+                    "[+] ATT&CK coverage layer written to: {} ({} techniques)",
+                    coverage.display(),
+                    layer
+                        .techniques
+                        .iter()
+                        .filter(|t| t.score.is_some())
+                        .count()
+                );
+                if path.is_empty() && !explain {
+                    return Ok(());
+                }
+            }
+            if explain {
+                let explanations = hunter.explain();
+                cli::print_explain(&explanations, json)?;
+                let mut counts: HashMap<Option<RuleCoverage>, usize> = HashMap::new();
+                for (_, groups) in &explanations {
+                    let best = groups.iter().map(|g| g.coverage).max();
+                    *counts.entry(best).or_insert(0) += 1;
+                }
+                let tally = |c: Option<RuleCoverage>| counts.get(&c).copied().unwrap_or_default();
+                cs_eprintln!(
+                    "[+] Explained {} Sigma rules: {} full, {} partial, {} unmapped, {} excluded, {} without groups",
+                    explanations.len(),
+                    tally(Some(RuleCoverage::Full)),
+                    tally(Some(RuleCoverage::Partial)),
+                    tally(Some(RuleCoverage::Unmapped)),
+                    tally(Some(RuleCoverage::Excluded)),
+                    tally(None)
+                );
+                return Ok(());
+            }
 
             /* if no user-defined extensions are specified, then we parse rules and
             mappings to build a list of file extensions that should be loaded */
@@ -632,32 +1046,79 @@ fn run() -> Result<()> {
             } else {
                 cs_eprintln!("[+] Loaded {} forensic artefacts ({})", files.len(), size);
             }
+            let mut scorer = if risk.is_some() || risk_json.is_some() {
+                let weights = match &risk_weights {
+                    Some(path) => match RiskWeights::load(path) {
+                        Ok(weights) => weights,
+                        Err(e) => anyhow::bail!("Provided risk weights file is invalid - {}", e),
+                    },
+                    None => RiskWeights::default(),
+                };
+                Some(RiskScorer::new(weights))
+            } else {
+                None
+            };
+            let mut techniques: HashMap<Uuid, usize> = HashMap::new();
             let mut hits = 0;
             let mut documents = 0;
             let mut detections = vec![];
+            let mut suppressed: HashMap<Uuid, usize> = HashMap::new();
+            let tag_suppressed = tag_suppressed && (json || jsonl);
+            let started = Instant::now();
+            let mut writing = Duration::default();
             let pb = cli::init_progress_bar(files.len() as u64, "Hunting".to_string());
             for file in &files {
                 pb.tick();
-                let scratch = hunter.hunt(file).with_context(|| {
+                let mut scratch = hunter.hunt(file).with_context(|| {
                     format!("Failed to hunt through file '{}'", file.to_string_lossy())
                 })?;
+                for detection in &mut scratch {
+                    for hit in &detection.hits {
+                        if let Some(id) = hit.suppressed {
+                            *suppressed.entry(id).or_insert(0) += 1;
+                        }
+                    }
+                    if !tag_suppressed {
+                        detection.hits.retain(|hit| hit.suppressed.is_none());
+                    }
+                }
+                scratch.retain(|d| !d.hits.is_empty());
+                if let Some(scorer) = scorer.as_mut() {
+                    scorer.add(&scratch, hunter.hunts(), hunter.rules());
+                }
+                if attack_layer.is_some() {
+                    for hit in scratch.iter().flat_map(|d| &d.hits) {
+                        if hit.suppressed.is_none() {
+                            *techniques.entry(hit.rule).or_insert(0) += 1;
+                        }
+                    }
+                }
                 hits += scratch.iter().map(|d| d.hits.len()).sum::<usize>();
                 documents += scratch.len();
                 if jsonl {
+                    // NOTE: As we stream the results, duplicates are dropped but their sources
+                    // cannot be listed against the detection that was already written, so they
+                    // are only counted.
+                    let start = Instant::now();
                     cli::print_json(
                         &scratch,
                         hunter.hunts(),
                         hunter.rules(),
+                        hunter.suppressions(),
                         local,
                         timezone,
                         jsonl,
                     )?;
+                    writing += start.elapsed();
+                    hunter.save()?;
                 } else {
                     detections.extend(scratch);
                 }
                 pb.inc(1);
             }
             pb.finish();
+            hunter.duplicates(&mut detections);
+            let start = Instant::now();
             if csv {
                 cli::print_csv(&detections, hunter.hunts(), hunter.rules(), local, timezone)?;
             } else if json {
@@ -668,6 +1129,7 @@ fn run() -> Result<()> {
                     &detections,
                     hunter.hunts(),
                     hunter.rules(),
+                    hunter.suppressions(),
                     local,
                     timezone,
                     jsonl,
@@ -688,19 +1150,106 @@ fn run() -> Result<()> {
                     timezone,
                 );
             }
+            writing += start.elapsed();
+            if let Some(scorer) = scorer {
+                let risks = scorer.finish();
+                if let Some(top) = risk {
+                    cli::print_risk(&risks, top, local, timezone);
+                }
+                if let Some(path) = &risk_json {
+                    let file = File::create(path)?;
+                    cli::write_risk(file, &risks, local, timezone)?;
+                    cs_eprintln!("[+] Risk scores written to: {}", path.display());
+                }
+            }
+            if let Some(path) = &attack_layer {
+                let layer = AttackLayer::hits(&techniques, hunter.rules());
+                let file = File::create(path)?;
+                serde_json::to_writer_pretty(file, &layer)?;
+                cs_eprintln!(
+                    "[+] ATT&CK layer written to: {} ({} techniques)",
+                    path.display(),
+                    layer
+                        .techniques
+                        .iter()
+                        .filter(|t| t.score.is_some())
+                        .count()
+                );
+            }
+            hunter.save()?;
+            if hunter.collisions() > 0 {
+                cs_eyellowln!(
+                    "[!] {} detected documents shared their dedup key with another document in the same file, so were kept (consider adding more --dedup-key fields)",
+                    hunter.collisions()
+                );
+            }
+            if jsonl && hunter.duplicated() > 0 {
+                cs_eyellowln!(
+                    "[!] {} detected records were also found in other sources, which cannot be listed when streaming with --jsonl",
+                    hunter.duplicated()
+                );
+            }
+            if let Some(profile) = hunter.profile() {
+                let report =
+                    profile.report(hunter.hunts(), hunter.rules(), writing, started.elapsed());
+                cli::print_profile(&report);
+            }
             cs_eprintln!("[+] {} Detections found on {} documents", hits, documents,);
+            if !suppressed.is_empty() {
+                cs_eprintln!(
+                    "[+] {} Detections suppressed{}",
+                    suppressed.values().sum::<usize>(),
+                    if tag_suppressed { " (tagged)" } else { "" }
+                );
+                for suppression in hunter.suppressions() {
+                    if let Some(count) = suppressed.get(&suppression.id) {
+                        cs_eprintln!(
+                            "    {} {}: {}{}",
+                            cli::RULE_PREFIX,
+                            suppression
+                                .rule
+                                .as_ref()
+                                .or(suppression.group.as_ref())
+                                .expect("could not get suppression target"),
+                            count,
+                            suppression
+                                .justification
+                                .as_ref()
+                                .map(|j| format!(" - {}", j))
+                                .unwrap_or_default()
+                        );
+                    }
+                }
+            }
         }
-        Command::Lint { path, kind, tau } => {
+        Command::Lint {
+            path,
+            kind,
+            sigma_config,
+            tau,
+        } => {
             init_writer(None, false, false, false)?;
             if !args.no_banner {
                 print_title();
             }
             cs_eprintln!("[+] Validating as {} for supplied detection rules...", kind);
+            let config = load_sigma_config(&sigma_config, &None)?;
             let mut count = 0;
             let mut failed = 0;
             for file in get_files(&path, &None, false)? {
-                match lint_rule(&kind, &file) {
-                    Ok(filters) => {
+                let file_name = match file
+                    .display()
+                    .to_string()
+                    .strip_prefix(&path.display().to_string())
+                {
+                    Some(e) => e.to_string(),
+                    None => file.display().to_string(),
+                };
+                match lint_rule(&kind, &file, &config) {
+                    Ok((filters, discarded)) => {
+                        for d in discarded {
+                            cs_eyellowln!("[!] {}: {}", file_name, d);
+                        }
                         if tau {
                             cs_eprintln!("[+] Rule {}:", file.to_string_lossy());
                             for filter in filters {
@@ -730,14 +1279,6 @@ fn run() -> Result<()> {
                     }
                     Err(e) => {
                         failed += 1;
-                        let file_name = match file
-                            .display()
-                            .to_string()
-                            .strip_prefix(&path.display().to_string())
-                        {
-                            Some(e) => e.to_string(),
-                            None => file.display().to_string(),
-                        };
                         cs_eprintln!("[!] {}: {}", file_name, e);
                         continue;
                     }
@@ -768,6 +1309,7 @@ fn run() -> Result<()> {
             skip_errors,
             tau,
             timestamp,
+            timestamp_format,
             timezone,
             to,
         } => {
@@ -851,6 +1393,9 @@ fn run() -> Result<()> {
             if let Some(timestamp) = timestamp {
                 searcher = searcher.timestamp(timestamp);
             }
+            if let Some(timestamp_format) = timestamp_format {
+                searcher = searcher.timestamp_format(timestamp_format);
+            }
             if let Some(timezone) = timezone {
                 searcher = searcher.timezone(timezone);
             }
@@ -894,6 +1439,63 @@ fn run() -> Result<()> {
             }
             cs_eprintln!("[+] Found {} hits", hits);
         }
+        Command::Test {
+            rules,
+            mapping,
+            rule,
+            json,
+            quiet,
+            sigma,
+            sigma_config,
+            sigma_filter,
+        } => {
+            init_writer(None, false, json, quiet)?;
+            if !args.no_banner {
+                print_title();
+            }
+            let mut paths = rules.map(|r| vec![r]).unwrap_or_default();
+            paths.extend(rule.unwrap_or_default());
+            let sigma = sigma.unwrap_or_default();
+            if paths.is_empty() && sigma.is_empty() {
+                anyhow::bail!("No detection rules were provided to test");
+            }
+            cs_eprintln!(
+                "[+] Testing detection rules from: {}",
+                paths
+                    .iter()
+                    .chain(sigma.iter())
+                    .map(|r| r.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            // NOTE: Sample files can live alongside the rules, so only the YAML files are loaded.
+            let extensions = Some(HashSet::from(["yml".to_owned(), "yaml".to_owned()]));
+            let config = load_sigma_config(&sigma_config, &sigma_filter)?;
+            let tester = Tester::new(mapping.unwrap_or_default(), config);
+            let mut reports = vec![];
+            for (kind, paths) in [(RuleKind::Chainsaw, &paths), (RuleKind::Sigma, &sigma)] {
+                for path in paths {
+                    for file in get_files(path, &extensions, false)? {
+                        if let Some(report) = tester.test(&kind, &file) {
+                            reports.push(report);
+                        }
+                    }
+                }
+            }
+            cli::print_tests(&reports, json)?;
+            let failed = reports.iter().filter(|r| !r.passed()).count();
+            let skipped = reports.iter().filter(|r| r.skipped()).count();
+            cs_eprintln!(
+                "[+] Tested {} detection rules: {} passed, {} failed, {} without samples",
+                reports.len(),
+                reports.len() - failed - skipped,
+                failed,
+                skipped
+            );
+            if failed > 0 {
+                anyhow::bail!("{} detection rules failed their tests", failed);
+            }
+        }
         Command::Analyse { cmd } => {
             match cmd {
                 AnalyseCommand::Shimcache {
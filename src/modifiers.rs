@@ -0,0 +1,327 @@
+use std::borrow::Cow;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use rustc_hash::FxHashMap;
+use tau_engine::{
+    core::parser::{Expression, Search},
+    Document, Value as Tau,
+};
+
+use crate::lists::Cidr;
+
+/// The prefix used for a network containment check, i.e. `@cidr:DestinationIp|10.0.0.0/8`.
+pub const CIDR: &str = "@cidr:";
+/// The prefix used for a field presence check, i.e. `@exists:CommandLine`.
+pub const EXISTS: &str = "@exists:";
+/// The prefix used to compare a field against another, i.e. `'@fieldref:TargetUserName'`.
+pub const FIELDREF: &str = "@fieldref:";
+/// The prefix used for a wildcard match, i.e. `@glob:Image|C:\\Users\\*\\AppData\\*.exe`.
+pub const GLOB: &str = "@glob:";
+
+// NOTE: Tau has no primitives for some of the Sigma modifiers, so they are converted into virtual
+// fields which are resolved against the mapped document, much like lists are.
+
+/// The compiled values of the virtual fields that a hunter's rules use, so that they are not
+/// parsed each time that they are resolved. They are keyed by the virtual field without its prefix.
+#[derive(Default)]
+pub struct Compiled {
+    cidrs: FxHashMap<String, Vec<Cidr>>,
+    globs: FxHashMap<String, Vec<Glob>>,
+}
+
+impl Compiled {
+    /// Compiles the values of a network containment check, `@cidr:<field>|<cidr>,<cidr>`, or a
+    /// wildcard match, `@glob:<field>|<glob>|<glob>`, other fields are ignored.
+    pub fn insert(&mut self, field: &str) -> crate::Result<()> {
+        if let Some(key) = field.strip_prefix(CIDR) {
+            if !self.cidrs.contains_key(key) {
+                let cidrs = values(field, key)?
+                    .split(',')
+                    .map(Cidr::from_str)
+                    .collect::<crate::Result<Vec<_>>>()?;
+                self.cidrs.insert(key.to_owned(), cidrs);
+            }
+        } else if let Some(key) = field.strip_prefix(GLOB) {
+            if !self.globs.contains_key(key) {
+                let globs = globs(values(field, key)?);
+                self.globs.insert(key.to_owned(), globs);
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves a network containment check, the key is in the form `<field>|<cidr>,<cidr>`.
+    pub fn cidr(&self, document: &dyn Document, key: &str) -> Option<Tau<'static>> {
+        let cidrs = self.cidrs.get(key)?;
+        let (field, _) = key.split_once('|')?;
+        let value = document.find(field)?.to_string()?;
+        let ip = IpAddr::from_str(value.trim()).ok()?;
+        if cidrs.iter().any(|c| c.contains(&ip)) {
+            return Some(Tau::String(Cow::Borrowed("true")));
+        }
+        None
+    }
+
+    /// Resolves a wildcard match, the key is in the form `<field>|<glob>|<glob>`.
+    pub fn glob(&self, document: &dyn Document, key: &str) -> Option<Tau<'static>> {
+        let globs = self.globs.get(key)?;
+        let (field, _) = key.split_once('|')?;
+        let value = document.find(field)?.to_string()?;
+        if globs.iter().any(|g| g.matches(&value)) {
+            return Some(Tau::String(Cow::Borrowed("true")));
+        }
+        None
+    }
+}
+
+/// Resolves a field presence check, unlike other fields this is always present so that it can
+/// match when the field is missing.
+pub fn exists(document: &dyn Document, key: &str) -> Option<Tau<'static>> {
+    match document.find(key) {
+        Some(Tau::Null) | None => Some(Tau::String(Cow::Borrowed("false"))),
+        Some(_) => Some(Tau::String(Cow::Borrowed("true"))),
+    }
+}
+
+/// Resolves a field comparison, the key is in the form `<field>|<field>,<field>`. Like other
+/// string matches in Sigma, the values are compared case-insensitively.
+pub fn fieldref(document: &dyn Document, key: &str) -> Option<Tau<'static>> {
+    let (field, others) = key.split_once('|')?;
+    let value = document.find(field)?.to_string()?;
+    for other in others.split(',') {
+        if let Some(other) = document.find(other).and_then(|v| v.to_string()) {
+            if value.eq_ignore_ascii_case(&other) {
+                return Some(Tau::String(Cow::Borrowed("true")));
+            }
+        }
+    }
+    None
+}
+
+enum Token {
+    Any,
+    One,
+    Text(char),
+}
+
+/// A wildcard pattern, where `*` matches any number of characters and `?` matches a single
+/// character. A backslash escapes the character that follows it, and a trailing one is literal.
+pub struct Glob(Vec<Token>);
+
+impl Glob {
+    pub fn new(glob: &str) -> Self {
+        let mut tokens = vec![];
+        let mut chars = glob.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' => Token::Any,
+                '?' => Token::One,
+                '\\' => Token::Text(chars.next().unwrap_or('\\')),
+                c => Token::Text(c),
+            });
+        }
+        Self(tokens)
+    }
+
+    /// Matches a value against the glob, like other string matches in Sigma the comparison is
+    /// case-insensitive.
+    pub fn matches(&self, value: &str) -> bool {
+        let mut g = 0;
+        let mut v = 0;
+        // NOTE: When text stops matching we backtrack to the last `*`, letting it consume one
+        // more character, which avoids the exponential blow up of a recursive matcher.
+        let mut backtrack: Option<(usize, usize)> = None;
+        while let Some(c) = value[v..].chars().next() {
+            match self.0.get(g) {
+                Some(Token::Any) => {
+                    backtrack = Some((g + 1, v));
+                    g += 1;
+                    continue;
+                }
+                Some(Token::One) => {
+                    g += 1;
+                    v += c.len_utf8();
+                    continue;
+                }
+                Some(Token::Text(t)) if t.eq_ignore_ascii_case(&c) => {
+                    g += 1;
+                    v += c.len_utf8();
+                    continue;
+                }
+                _ => {}
+            }
+            match backtrack {
+                Some((next, start)) => {
+                    let start = start + value[start..].chars().next().map_or(1, |c| c.len_utf8());
+                    backtrack = Some((next, start));
+                    g = next;
+                    v = start;
+                }
+                None => return false,
+            }
+        }
+        while let Some(Token::Any) = self.0.get(g) {
+            g += 1;
+        }
+        g == self.0.len()
+    }
+}
+
+// Splits the globs of a wildcard match on the separators that are not escaped.
+fn globs(globs: &str) -> Vec<Glob> {
+    let mut split = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in globs.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '|' {
+            split.push(Glob::new(&globs[start..i]));
+            start = i + 1;
+        }
+    }
+    split.push(Glob::new(&globs[start..]));
+    split
+}
+
+// Returns the values of a virtual field, which follow the field that they are checked against.
+fn values<'a>(field: &str, key: &'a str) -> crate::Result<&'a str> {
+    match key.split_once('|') {
+        Some((_, values)) => Ok(values),
+        None => anyhow::bail!("invalid virtual field '{}'", field),
+    }
+}
+
+/// Checks that the values of the virtual fields in an expression can be compiled, so that invalid
+/// values are reported when the rules are loaded rather than when they are hunted with.
+pub fn check(expression: &Expression) -> crate::Result<()> {
+    let mut compiled = Compiled::default();
+    for field in crate::ext::tau::extract_fields(expression) {
+        compiled.insert(&field)?;
+    }
+    Ok(())
+}
+
+/// Returns the fields that a virtual field is resolved from, a field comparison returns each of
+/// the fields that it compares, while other fields are returned as is.
+pub fn fields(key: &str) -> Vec<&str> {
+    if let Some(key) = key.strip_prefix(FIELDREF) {
+        match key.split_once('|') {
+            Some((field, others)) => std::iter::once(field).chain(others.split(',')).collect(),
+            None => vec![key],
+        }
+    } else if let Some(key) = key.strip_prefix(CIDR).or_else(|| key.strip_prefix(GLOB)) {
+        vec![key.split_once('|').map(|(field, _)| field).unwrap_or(key)]
+    } else if let Some(key) = key.strip_prefix(crate::lists::PREFIX) {
+        vec![key.split_once(':').map(|(_, field)| field).unwrap_or(key)]
+    } else {
+        vec![key.strip_prefix(EXISTS).unwrap_or(key)]
+    }
+}
+
+/// Replaces any searches that reference another field with a comparison against that field, so
+/// that Chainsaw rules can compare fields using a value of the form `'@fieldref:<field>'`.
+pub fn rewrite(expression: Expression) -> Expression {
+    match expression {
+        Expression::BooleanGroup(x, expressions) => {
+            Expression::BooleanGroup(x, expressions.into_iter().map(rewrite).collect())
+        }
+        Expression::BooleanExpression(left, x, right) => {
+            Expression::BooleanExpression(Box::new(rewrite(*left)), x, Box::new(rewrite(*right)))
+        }
+        Expression::Match(x, e) => Expression::Match(x, Box::new(rewrite(*e))),
+        Expression::Negate(e) => Expression::Negate(Box::new(rewrite(*e))),
+        Expression::Search(Search::Exact(value), field, cast) => {
+            match value.strip_prefix(FIELDREF) {
+                Some(other) => Expression::Search(
+                    Search::Exact("true".to_owned()),
+                    format!("{}{}|{}", FIELDREF, field, other),
+                    false,
+                ),
+                None => Expression::Search(Search::Exact(value), field, cast),
+            }
+        }
+        _ => expression,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(fields("Image"), vec!["Image"]);
+        assert_eq!(fields("@exists:CommandLine"), vec!["CommandLine"]);
+        assert_eq!(
+            fields("@cidr:DestinationIp|10.0.0.0/8"),
+            vec!["DestinationIp"]
+        );
+        assert_eq!(fields("@glob:Image|*\\\\x.exe"), vec!["Image"]);
+        assert_eq!(fields("@list:*#3:Image"), vec!["Image"]);
+        assert_eq!(
+            fields("@fieldref:TargetUserName|SubjectUserName,User"),
+            vec!["TargetUserName", "SubjectUserName", "User"]
+        );
+    }
+
+    #[test]
+    fn test_glob() {
+        let matches = |glob: &str, value: &str| Glob::new(glob).matches(value);
+
+        assert!(matches("foo*bar", "foobar"));
+        assert!(matches("foo*bar", "FOO and BAR"));
+        assert!(!matches("foo*bar", "foobarbaz"));
+        assert!(matches("foo?bar", "foo-bar"));
+        assert!(!matches("foo?bar", "foobar"));
+        assert!(matches("*a*b*a*", "xaxbxbxax"));
+        assert!(!matches("*a*b*a*", "xbxbxax"));
+        assert!(matches("", ""));
+        assert!(matches("*", ""));
+        assert!(matches("?", "\u{e9}"));
+        assert!(matches("foo\\*", "foo*"));
+        assert!(!matches("foo\\*", "foobar"));
+        assert!(matches("foo\\?", "foo?"));
+        assert!(!matches("foo\\?", "foox"));
+        assert!(matches("C:\\\\*\\\\x.exe", "C:\\Users\\x.exe"));
+    }
+
+    #[test]
+    fn test_compile_glob() {
+        let mut compiled = Compiled::default();
+        let field = "@glob:Image|C:\\\\Users\\\\*\\\\AppData|foo\\|bar";
+        compiled.insert(field).unwrap();
+        let key = field.strip_prefix(GLOB).unwrap();
+        let document = serde_json::json!({ "Image": "C:\\Users\\x\\AppData" });
+        assert!(compiled.glob(&document, key).is_some());
+        let document = serde_json::json!({ "Image": "foo|bar" });
+        assert!(compiled.glob(&document, key).is_some());
+        let document = serde_json::json!({ "Image": "C:\\Windows" });
+        assert!(compiled.glob(&document, key).is_none());
+        assert!(compiled.glob(&document, "Image|*").is_none());
+    }
+
+    #[test]
+    fn test_compile_cidr() {
+        let mut compiled = Compiled::default();
+        let field = "@cidr:DestinationIp|10.0.0.0/8,fd00::/8";
+        compiled.insert(field).unwrap();
+        let key = field.strip_prefix(CIDR).unwrap();
+        let document = serde_json::json!({ "DestinationIp": "10.1.2.3" });
+        assert!(compiled.cidr(&document, key).is_some());
+        let document = serde_json::json!({ "DestinationIp": "fd00::1" });
+        assert!(compiled.cidr(&document, key).is_some());
+        let document = serde_json::json!({ "DestinationIp": "192.168.0.1" });
+        assert!(compiled.cidr(&document, key).is_none());
+
+        let expression = Expression::Search(
+            Search::Exact("true".to_owned()),
+            "@cidr:DestinationIp|10.0.0.0/33".to_owned(),
+            false,
+        );
+        assert!(check(&expression).is_err());
+    }
+}
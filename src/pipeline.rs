@@ -0,0 +1,380 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::hash::BuildHasherDefault;
+
+use rustc_hash::FxHasher;
+use serde::Deserialize;
+use serde_yaml::{Mapping as Yaml, Value};
+use tau_engine::core::parser::{parse_identifier, BoolSym, Expression};
+use uuid::Uuid;
+
+use crate::file::Kind as FileKind;
+use crate::hunt::{Group, Mapping};
+use crate::rule::{chainsaw::Field, sigma, Kind as RuleKind, Rule};
+use crate::timestamp::Format as TimestampFormat;
+
+/// A pySigma processing pipeline, which can be used in place of a mapping file. As pipelines do
+/// not describe the documents that they apply to, Chainsaw also reads the optional `kind`,
+/// `timestamp` and `timestamp_format` keys, which pySigma ignores.
+#[derive(Deserialize)]
+pub struct Pipeline {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub transformations: Vec<Transformation>,
+
+    #[serde(default = "default_kind")]
+    pub kind: FileKind,
+    #[serde(default = "default_timestamp")]
+    pub timestamp: String,
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+}
+
+fn default_kind() -> FileKind {
+    FileKind::Evtx
+}
+
+fn default_timestamp() -> String {
+    "Event.System.TimeCreated".to_owned()
+}
+
+#[derive(Deserialize)]
+pub struct Transformation {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub kind: TransformationKind,
+
+    #[serde(default)]
+    pub rule_conditions: Vec<RuleCondition>,
+    #[serde(default)]
+    pub rule_cond_op: Operator,
+    #[serde(default)]
+    pub rule_cond_not: bool,
+
+    // NOTE: These are only read so that we can refuse them, as ignoring them would apply the
+    // transformation to more of the rule than intended.
+    #[serde(default)]
+    pub detection_item_conditions: Option<Value>,
+    #[serde(default)]
+    pub field_name_conditions: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum TransformationKind {
+    AddCondition {
+        conditions: Yaml,
+        #[serde(default)]
+        negated: bool,
+    },
+    ChangeLogsource {
+        #[serde(default)]
+        category: Option<String>,
+        #[serde(default)]
+        product: Option<String>,
+        #[serde(default)]
+        service: Option<String>,
+    },
+    FieldNameMapping {
+        mapping: BTreeMap<String, Target>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Target {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operator {
+    And,
+    Or,
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        Self::And
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum RuleCondition {
+    Logsource {
+        #[serde(default)]
+        category: Option<String>,
+        #[serde(default)]
+        product: Option<String>,
+        #[serde(default)]
+        service: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct LogSource {
+    pub category: Option<String>,
+    pub product: Option<String>,
+    pub service: Option<String>,
+}
+
+impl LogSource {
+    // NOTE: Like pySigma, a condition only checks the parts of the logsource that it sets.
+    fn contains(&self, other: &LogSource) -> bool {
+        let part = |p: &Option<String>| p.as_deref().map(|p| vec![p]);
+        sigma::within(
+            [
+                self.category.as_deref(),
+                self.product.as_deref(),
+                self.service.as_deref(),
+            ],
+            [
+                part(&other.category),
+                part(&other.product),
+                part(&other.service),
+            ],
+        )
+    }
+}
+
+impl Transformation {
+    fn applies(&self, logsource: &LogSource) -> bool {
+        if self.rule_conditions.is_empty() {
+            return true;
+        }
+        let mut results = self.rule_conditions.iter().map(|c| match c {
+            RuleCondition::Logsource {
+                category,
+                product,
+                service,
+            } => LogSource {
+                category: category.clone(),
+                product: product.clone(),
+                service: service.clone(),
+            }
+            .contains(logsource),
+        });
+        let applies = match self.rule_cond_op {
+            Operator::And => results.all(|r| r),
+            Operator::Or => results.any(|r| r),
+        };
+        applies != self.rule_cond_not
+    }
+}
+
+type Routed = HashSet<Uuid, BuildHasherDefault<FxHasher>>;
+
+impl Pipeline {
+    /// Translates the pipeline into a mapping, as the transformations that apply depend on the
+    /// logsource of a rule a group is created for each logsource, which only runs the rules that
+    /// have it.
+    pub fn mapping(self, rules: &BTreeMap<Uuid, Rule>) -> crate::Result<Mapping> {
+        for transformation in &self.transformations {
+            let id = transformation.id.as_deref().unwrap_or("unnamed");
+            if transformation.detection_item_conditions.is_some()
+                || transformation.field_name_conditions.is_some()
+            {
+                anyhow::bail!("transformation '{}' uses unsupported conditions", id);
+            }
+            if let TransformationKind::FieldNameMapping { mapping } = &transformation.kind {
+                for (field, target) in mapping {
+                    if let Target::Many(targets) = target {
+                        if targets.len() != 1 {
+                            anyhow::bail!(
+                                "transformation '{}' maps '{}' to multiple fields which is unsupported",
+                                id,
+                                field
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut logsources: BTreeMap<LogSource, Routed> = BTreeMap::new();
+        for (rid, rule) in rules {
+            if let Rule::Sigma(rule) = rule {
+                let logsource = match &rule.logsource {
+                    Some(l) => LogSource {
+                        category: l.category.clone(),
+                        product: l.product.clone(),
+                        service: l.service.clone(),
+                    },
+                    None => LogSource::default(),
+                };
+                logsources.entry(logsource).or_default().insert(*rid);
+            }
+        }
+
+        let name = self.name.clone().unwrap_or_else(|| "pipeline".to_owned());
+        let mut groups = vec![];
+        for (logsource, routed) in logsources {
+            let (fields, filter) = self.apply(logsource.clone())?;
+            let fields = fields
+                .into_iter()
+                .map(|(from, to)| Field {
+                    name: from.clone(),
+                    from,
+                    to,
+                    cast: None,
+                    container: None,
+                    transforms: vec![],
+                    visible: true,
+                })
+                .collect();
+            // NOTE: Without a condition the group runs its rules against every document, which
+            // is what pySigma would do but is rarely what was intended.
+            if let Expression::Boolean(true) = filter {
+                cs_eyellowln!(
+                    "[!] No conditions are added for the '{}' logsource in pipeline '{}', its rules run against every document",
+                    describe(&logsource),
+                    name
+                );
+            }
+            groups.push(Group {
+                id: Uuid::new_v4(),
+                fields,
+                filter,
+                name: format!("{} - {}", name, describe(&logsource)),
+                timestamp: self.timestamp.clone(),
+                timestamp_format: self.timestamp_format.clone(),
+                logsource: None,
+                routed: Some(routed),
+            });
+        }
+        Ok(Mapping {
+            exclusions: Default::default(),
+            extensions: None,
+            groups,
+            kind: self.kind,
+            name,
+            rules: RuleKind::Sigma,
+        })
+    }
+
+    // Runs the transformations in order for a logsource, returning the field mappings and the
+    // filter built from the conditions that they add.
+    fn apply(
+        &self,
+        mut logsource: LogSource,
+    ) -> crate::Result<(Vec<(String, String)>, Expression)> {
+        let mut fields: Vec<(String, String)> = vec![];
+        let mut conditions: Vec<(Vec<(String, Value)>, bool)> = vec![];
+        for transformation in &self.transformations {
+            if !transformation.applies(&logsource) {
+                continue;
+            }
+            match &transformation.kind {
+                TransformationKind::AddCondition {
+                    conditions: c,
+                    negated,
+                } => {
+                    let mut items = vec![];
+                    for (k, v) in c {
+                        match k.as_str() {
+                            Some(k) => items.push((k.to_owned(), v.clone())),
+                            None => anyhow::bail!("condition fields must be strings"),
+                        }
+                    }
+                    conditions.push((items, *negated));
+                }
+                TransformationKind::ChangeLogsource {
+                    category,
+                    product,
+                    service,
+                } => {
+                    logsource = LogSource {
+                        category: category.clone(),
+                        product: product.clone(),
+                        service: service.clone(),
+                    };
+                }
+                TransformationKind::FieldNameMapping { mapping } => {
+                    let target = |t: &Target| match t {
+                        Target::One(t) => t.clone(),
+                        Target::Many(t) => t[0].clone(),
+                    };
+                    // NOTE: Fields that were already renamed are renamed again, just as pySigma
+                    // would when it applies one mapping after another.
+                    for (_, to) in fields.iter_mut() {
+                        if let Some(t) = mapping.get(to) {
+                            *to = target(t);
+                        }
+                    }
+                    // NOTE: The group filter is not passed through the field mappings, so the
+                    // fields that conditions use are renamed instead.
+                    for (items, _) in conditions.iter_mut() {
+                        for (key, _) in items.iter_mut() {
+                            let (field, modifiers) = match key.split_once('|') {
+                                Some((f, m)) => (f, Some(m)),
+                                None => (key.as_str(), None),
+                            };
+                            if let Some(t) = mapping.get(field) {
+                                *key = match modifiers {
+                                    Some(m) => format!("{}|{}", target(t), m),
+                                    None => target(t),
+                                };
+                            }
+                        }
+                    }
+                    let sources = fields
+                        .iter()
+                        .map(|(f, _)| f.clone())
+                        .collect::<BTreeSet<_>>();
+                    for (from, to) in mapping {
+                        if !sources.contains(from) {
+                            fields.push((from.clone(), target(to)));
+                        }
+                    }
+                }
+            }
+        }
+        let mut expressions = vec![];
+        for (items, negated) in conditions {
+            let mut group = vec![];
+            for (key, value) in items {
+                for mapping in sigma::parse_condition(&key, &value)? {
+                    group.push(parse_identifier(&Value::Mapping(mapping))?);
+                }
+            }
+            let expression = Expression::BooleanGroup(BoolSym::And, group);
+            if negated {
+                expressions.push(Expression::Negate(Box::new(expression)));
+            } else {
+                expressions.push(expression);
+            }
+        }
+        let filter = match expressions.len() {
+            0 => Expression::Boolean(true),
+            1 => expressions.remove(0),
+            _ => Expression::BooleanGroup(BoolSym::And, expressions),
+        };
+        Ok((fields, filter))
+    }
+}
+
+/// Loads a mapping file, which can either be a Chainsaw mapping or a pySigma processing pipeline.
+pub fn load(content: &str, rules: &BTreeMap<Uuid, Rule>) -> crate::Result<Mapping> {
+    let value: Value = serde_yaml::from_str(content)?;
+    let pipeline = value.get("transformations").is_some() && value.get("groups").is_none();
+    if pipeline {
+        serde_yaml::from_value::<Pipeline>(value)?.mapping(rules)
+    } else {
+        Ok(serde_yaml::from_value(value)?)
+    }
+}
+
+fn describe(logsource: &LogSource) -> String {
+    let parts = [&logsource.product, &logsource.category, &logsource.service]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+    if parts.is_empty() {
+        "any".to_owned()
+    } else {
+        parts.join("/")
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rustc_hash::FxHashMap;
+use uuid::Uuid;
+
+use crate::hunt::{Hunt, HuntKind};
+use crate::rule::Rule;
+
+/// The time spent in, and the outcomes of, a rule or group filter across a hunt.
+#[derive(Debug, Default)]
+pub struct Counter {
+    evaluations: AtomicU64,
+    matches: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl Counter {
+    #[inline]
+    pub fn record(&self, elapsed: Duration, matched: bool) {
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        if matched {
+            self.matches.fetch_add(1, Ordering::Relaxed);
+        }
+        self.nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Timer(AtomicU64);
+
+impl Timer {
+    #[inline]
+    pub fn add(&self, elapsed: Duration) {
+        self.0
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+}
+
+// NOTE: The counters are created up front for every rule and group so that they can be updated
+// from the hunting threads without a lock.
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub groups: FxHashMap<Uuid, Counter>,
+    pub rules: FxHashMap<Uuid, Counter>,
+
+    pub parsing: Timer,
+    pub mapping: Timer,
+    pub matching: Timer,
+}
+
+impl Profile {
+    pub fn new(hunts: &[Hunt], rules: &BTreeMap<Uuid, Rule>) -> Self {
+        Self {
+            groups: hunts
+                .iter()
+                .filter(|h| matches!(h.kind, HuntKind::Group { .. }))
+                .map(|h| (h.id, Counter::default()))
+                .collect(),
+            rules: rules.keys().map(|id| (*id, Counter::default())).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Summarises the profile, the entries are sorted from the slowest to the fastest.
+    pub fn report(
+        &self,
+        hunts: &[Hunt],
+        rules: &BTreeMap<Uuid, Rule>,
+        output: Duration,
+        total: Duration,
+    ) -> Report {
+        let entry = |name: String, counter: &Counter| Entry {
+            name,
+            evaluations: counter.evaluations.load(Ordering::Relaxed),
+            matches: counter.matches.load(Ordering::Relaxed),
+            time: Duration::from_nanos(counter.nanos.load(Ordering::Relaxed)),
+        };
+        let mut groups = hunts
+            .iter()
+            .filter_map(|h| self.groups.get(&h.id).map(|c| entry(h.group.clone(), c)))
+            .collect::<Vec<_>>();
+        groups.sort_by(|x, y| y.time.cmp(&x.time).then_with(|| x.name.cmp(&y.name)));
+        let mut entries = rules
+            .iter()
+            .filter_map(|(id, r)| self.rules.get(id).map(|c| entry(r.name().clone(), c)))
+            .filter(|e| e.evaluations > 0)
+            .collect::<Vec<_>>();
+        entries.sort_by(|x, y| y.time.cmp(&x.time).then_with(|| x.name.cmp(&y.name)));
+        Report {
+            parsing: self.parsing.elapsed(),
+            mapping: self.mapping.elapsed(),
+            matching: self.matching.elapsed(),
+            output,
+            total,
+            groups,
+            rules: entries,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub name: String,
+    pub evaluations: u64,
+    pub matches: u64,
+    pub time: Duration,
+}
+
+/// The phase timings are summed across the hunting threads, so they can exceed the total time.
+#[derive(Debug)]
+pub struct Report {
+    pub parsing: Duration,
+    pub mapping: Duration,
+    pub matching: Duration,
+    pub output: Duration,
+    pub total: Duration,
+    pub groups: Vec<Entry>,
+    pub rules: Vec<Entry>,
+}
@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use tau_engine::Document as TauDocument;
+use uuid::Uuid;
+
+use crate::file::Kind as FileKind;
+use crate::hunt::{Detections, Hunt, Kind};
+use crate::rule::{Level, Rule};
+use crate::value::Value;
+
+// The fields that entities are taken from, the first to be present in a document is used. The
+// mapped names are tried first, falling back to the raw event log fields for Chainsaw rules.
+const HOST_FIELDS: &[&str] = &["Computer", "Event.System.Computer"];
+const IP_FIELDS: &[&str] = &[
+    "IpAddress",
+    "SourceIp",
+    "SourceAddress",
+    "Event.EventData.IpAddress",
+    "Event.EventData.SourceIp",
+    "Event.EventData.SourceAddress",
+];
+const USER_FIELDS: &[&str] = &[
+    "TargetUserName",
+    "SubjectUserName",
+    "User",
+    "Event.EventData.TargetUserName",
+    "Event.EventData.SubjectUserName",
+    "Event.EventData.User",
+];
+
+/// The score given to each detection by its level, along with a bonus for each additional rule
+/// group that an entity was detected by.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    pub critical: u64,
+    pub high: u64,
+    pub medium: u64,
+    pub low: u64,
+    pub info: u64,
+    pub group: u64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            critical: 40,
+            high: 20,
+            medium: 10,
+            low: 5,
+            info: 1,
+            group: 10,
+        }
+    }
+}
+
+impl Weights {
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        Ok(serde_yaml::from_str(&content)?)
+    }
+
+    fn level(&self, level: &Level) -> u64 {
+        match level {
+            Level::Critical => self.critical,
+            Level::High => self.high,
+            Level::Medium => self.medium,
+            Level::Low => self.low,
+            Level::Info => self.info,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entity {
+    Host,
+    Ip,
+    User,
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Host => write!(f, "host"),
+            Self::Ip => write!(f, "ip"),
+            Self::User => write!(f, "user"),
+        }
+    }
+}
+
+/// A rule that contributed to an entity's score.
+#[derive(Debug, Serialize)]
+pub struct Contributor {
+    pub name: String,
+    pub group: String,
+    pub level: Level,
+    pub count: usize,
+}
+
+#[derive(Debug)]
+pub struct Risk {
+    pub entity: String,
+    pub kind: Entity,
+    pub score: u64,
+    pub first_seen: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+    pub groups: BTreeSet<String>,
+    pub detections: Vec<Contributor>,
+}
+
+struct Tally {
+    name: String,
+    score: u64,
+    first_seen: NaiveDateTime,
+    last_seen: NaiveDateTime,
+    groups: BTreeSet<String>,
+    detections: BTreeMap<(String, String), (Level, usize)>,
+}
+
+/// Accumulates the detections from each hunted file so that entities can be ranked once hunting
+/// has finished.
+pub struct Scorer {
+    entities: HashMap<(Entity, String), Tally>,
+    weights: Weights,
+}
+
+impl Scorer {
+    pub fn new(weights: Weights) -> Self {
+        Self {
+            entities: HashMap::new(),
+            weights,
+        }
+    }
+
+    pub fn add(&mut self, detections: &[Detections], hunts: &[Hunt], rules: &BTreeMap<Uuid, Rule>) {
+        let hunts: HashMap<_, _> = hunts.iter().map(|h| (&h.id, h)).collect();
+        for detection in detections {
+            let documents = match &detection.kind {
+                Kind::Individual { document } => vec![document],
+                Kind::Aggregate { documents } => documents.iter().collect(),
+            };
+            let values = documents
+                .iter()
+                .filter_map(|d| {
+                    bincode::deserialize::<Value>(&d.data)
+                        .ok()
+                        .map(|v| (d.kind.clone(), v))
+                })
+                .collect::<Vec<_>>();
+            for hit in &detection.hits {
+                // NOTE: Suppressed detections are known-good, so they do not add to the risk.
+                if hit.suppressed.is_some() {
+                    continue;
+                }
+                let hunt = hunts.get(&hit.hunt).expect("could not get hunt");
+                let rule = rules.get(&hit.rule).expect("could not get rule");
+                let mut entities = BTreeMap::new();
+                for (kind, value) in &values {
+                    let wrapper;
+                    let mapped = match kind {
+                        FileKind::Evtx => {
+                            wrapper = crate::evtx::Wrapper(value);
+                            hunt.mapper.mapped(&wrapper)
+                        }
+                        _ => hunt.mapper.mapped(value),
+                    };
+                    for (entity, fields) in [
+                        (Entity::Host, HOST_FIELDS),
+                        (Entity::Ip, IP_FIELDS),
+                        (Entity::User, USER_FIELDS),
+                    ] {
+                        if let Some(name) = find(&mapped, fields) {
+                            entities.insert((entity, name.to_lowercase()), name);
+                        }
+                    }
+                }
+                for ((entity, key), name) in entities {
+                    let tally = self.entities.entry((entity, key)).or_insert(Tally {
+                        name,
+                        score: 0,
+                        first_seen: hit.timestamp,
+                        last_seen: hit.timestamp,
+                        groups: BTreeSet::new(),
+                        detections: BTreeMap::new(),
+                    });
+                    tally.score += self.weights.level(rule.level());
+                    tally.first_seen = tally.first_seen.min(hit.timestamp);
+                    tally.last_seen = tally.last_seen.max(hit.timestamp);
+                    tally.groups.insert(hunt.group.clone());
+                    tally
+                        .detections
+                        .entry((hunt.group.clone(), rule.name().clone()))
+                        .or_insert((rule.level().clone(), 0))
+                        .1 += 1;
+                }
+            }
+        }
+    }
+
+    /// Returns the entities ranked from the highest risk to the lowest.
+    pub fn finish(self) -> Vec<Risk> {
+        let weights = self.weights;
+        let mut risks = self
+            .entities
+            .into_iter()
+            .map(|((kind, _), tally)| {
+                let bonus = weights.group * (tally.groups.len().saturating_sub(1) as u64);
+                let mut detections = tally
+                    .detections
+                    .into_iter()
+                    .map(|((group, name), (level, count))| Contributor {
+                        name,
+                        group,
+                        level,
+                        count,
+                    })
+                    .collect::<Vec<_>>();
+                detections.sort_by(|x, y| {
+                    (weights.level(&y.level) * y.count as u64)
+                        .cmp(&(weights.level(&x.level) * x.count as u64))
+                        .then_with(|| x.name.cmp(&y.name))
+                });
+                Risk {
+                    entity: tally.name,
+                    kind,
+                    score: tally.score + bonus,
+                    first_seen: tally.first_seen,
+                    last_seen: tally.last_seen,
+                    groups: tally.groups,
+                    detections,
+                }
+            })
+            .collect::<Vec<_>>();
+        risks.sort_by(|x, y| {
+            y.score
+                .cmp(&x.score)
+                .then_with(|| y.last_seen.cmp(&x.last_seen))
+                .then_with(|| x.entity.cmp(&y.entity))
+        });
+        risks
+    }
+}
+
+fn find(document: &dyn TauDocument, fields: &[&str]) -> Option<String> {
+    for field in fields {
+        if let Some(value) = document.find(field).and_then(|v| v.to_string()) {
+            let value = value.trim();
+            // NOTE: Windows uses a dash when a field has no value.
+            if !value.is_empty() && value != "-" {
+                return Some(value.to_owned());
+            }
+        }
+    }
+    None
+}
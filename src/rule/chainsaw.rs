@@ -3,10 +3,15 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
+use base64::{engine::general_purpose, Engine as _};
+use quick_xml::events::{BytesStart, Event as XmlEvent};
+use regex::Regex;
 use serde::{
     de::{self, MapAccess, Visitor},
     Deserialize,
 };
+use serde_json::Value as Json;
+use serde_yaml::Value as Yaml;
 use tau_engine::core::{
     optimiser,
     parser::{Expression, ModSym},
@@ -14,11 +19,151 @@ use tau_engine::core::{
 
 use crate::file::Kind;
 use crate::rule::{Aggregate, Filter, Level, Status};
+use crate::timestamp::Format as TimestampFormat;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Container {
     pub field: String,
     pub format: Format,
+
+    /// The delimiter between entries, used by the `csv` and `kv` formats.
+    #[serde(default)]
+    pub delimiter: Option<String>,
+    /// The column names for the `csv` format, when not set the columns are addressed by index.
+    #[serde(default)]
+    pub headers: Option<Vec<String>>,
+    /// The separator between keys and values, used by the `kv` format.
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+impl Container {
+    pub fn parse(&self, data: &str) -> Option<Json> {
+        match self.format {
+            Format::Csv => {
+                // NOTE: The delimiter is checked to be a single byte when the rule is loaded.
+                let delimiter = match &self.delimiter {
+                    Some(delimiter) => *delimiter.as_bytes().first()?,
+                    None => b',',
+                };
+                let mut reader = csv::ReaderBuilder::new()
+                    .delimiter(delimiter)
+                    .flexible(true)
+                    .has_headers(false)
+                    .from_reader(data.as_bytes());
+                let record = reader.records().next()?.ok()?;
+                let mut object = serde_json::Map::with_capacity(record.len());
+                for (i, value) in record.iter().enumerate() {
+                    let key = match &self.headers {
+                        Some(headers) => match headers.get(i) {
+                            Some(header) => header.clone(),
+                            None => continue,
+                        },
+                        None => i.to_string(),
+                    };
+                    object.insert(key, Json::String(value.to_owned()));
+                }
+                Some(Json::Object(object))
+            }
+            Format::Json => serde_json::from_str::<Json>(data).ok(),
+            Format::Kv => {
+                let delimiter = self.delimiter.as_deref().unwrap_or("\n");
+                let separator = self.separator.as_deref().unwrap_or("=");
+                let mut object = serde_json::Map::new();
+                for pair in data.split(delimiter) {
+                    if let Some((key, value)) = pair.split_once(separator) {
+                        object.insert(key.trim().to_owned(), Json::String(value.trim().to_owned()));
+                    }
+                }
+                Some(Json::Object(object))
+            }
+            Format::Xml => xml(data),
+        }
+    }
+}
+
+/// Converts an XML document into JSON, keeping its root element. Attributes are prefixed with an
+/// `@`, repeated elements are collected into an array and the text of elements that also have
+/// attributes or children is held under `$text`.
+fn xml(data: &str) -> Option<Json> {
+    let mut reader = quick_xml::Reader::from_str(data);
+    reader.trim_text(true);
+    let mut root = serde_json::Map::new();
+    let mut stack: Vec<(String, serde_json::Map<String, Json>, String)> = vec![];
+    loop {
+        match reader.read_event().ok()? {
+            XmlEvent::Start(e) => stack.push(element(&e)?),
+            XmlEvent::Empty(e) => {
+                let (name, object, text) = element(&e)?;
+                let parent = match stack.last_mut() {
+                    Some((_, parent, _)) => parent,
+                    None => &mut root,
+                };
+                insert(parent, name, object, text);
+            }
+            XmlEvent::End(_) => {
+                let (name, object, text) = stack.pop()?;
+                let parent = match stack.last_mut() {
+                    Some((_, parent, _)) => parent,
+                    None => &mut root,
+                };
+                insert(parent, name, object, text);
+            }
+            XmlEvent::Text(e) => {
+                let (_, _, text) = stack.last_mut()?;
+                text.push_str(&e.unescape().ok()?);
+            }
+            XmlEvent::CData(e) => {
+                let (_, _, text) = stack.last_mut()?;
+                text.push_str(&String::from_utf8_lossy(&e.into_inner()));
+            }
+            XmlEvent::Eof => break,
+            _ => {}
+        }
+    }
+    if !stack.is_empty() || root.is_empty() {
+        return None;
+    }
+    Some(Json::Object(root))
+}
+
+fn element(e: &BytesStart) -> Option<(String, serde_json::Map<String, Json>, String)> {
+    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+    let mut object = serde_json::Map::new();
+    for attribute in e.attributes() {
+        let attribute = attribute.ok()?;
+        let key = format!("@{}", String::from_utf8_lossy(attribute.key.as_ref()));
+        let value = attribute.unescape_value().ok()?.into_owned();
+        object.insert(key, Json::String(value));
+    }
+    Some((name, object, String::new()))
+}
+
+fn insert(
+    parent: &mut serde_json::Map<String, Json>,
+    name: String,
+    mut object: serde_json::Map<String, Json>,
+    text: String,
+) {
+    let value = if object.is_empty() {
+        Json::String(text)
+    } else {
+        if !text.is_empty() {
+            object.insert("$text".to_owned(), Json::String(text));
+        }
+        Json::Object(object)
+    };
+    // NOTE: Elements are never arrays themselves, so an array means that the element repeats.
+    match parent.get_mut(&name) {
+        Some(Json::Array(array)) => array.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Json::Array(vec![first, value]);
+        }
+        None => {
+            parent.insert(name, value);
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -29,6 +174,7 @@ pub struct Field {
 
     pub cast: Option<ModSym>,
     pub container: Option<Container>,
+    pub transforms: Vec<Transform>,
     pub visible: bool,
 }
 
@@ -55,6 +201,7 @@ impl<'de> Deserialize<'de> for Field {
                 let mut from = None;
                 let mut name = None;
                 let mut to = None;
+                let mut transforms = None;
                 let mut visible = None;
                 while let Some(key) = map.next_key::<String>()? {
                     match key.as_str() {
@@ -90,6 +237,12 @@ impl<'de> Deserialize<'de> for Field {
                             }
                             container = Some(map.next_value()?);
                         }
+                        "transforms" => {
+                            if transforms.is_some() {
+                                return Err(de::Error::duplicate_field("transforms"));
+                            }
+                            transforms = Some(map.next_value()?);
+                        }
                         "visible" => {
                             if visible.is_some() {
                                 return Err(de::Error::duplicate_field("visible"));
@@ -119,7 +272,20 @@ impl<'de> Deserialize<'de> for Field {
                     let from = from.unwrap_or_else(|| to.clone());
                     (name, from, to)
                 };
-                let container = container.unwrap_or_default();
+                let container: Option<Container> = container.unwrap_or_default();
+                if let Some(Container {
+                    format: Format::Csv,
+                    delimiter: Some(delimiter),
+                    ..
+                }) = &container
+                {
+                    if delimiter.len() != 1 {
+                        return Err(de::Error::custom(
+                            "csv container delimiters must be a single byte",
+                        ));
+                    }
+                }
+                let transforms = transforms.unwrap_or_default();
                 let visible = visible.unwrap_or(true);
                 Ok(Field {
                     name,
@@ -127,12 +293,13 @@ impl<'de> Deserialize<'de> for Field {
                     from,
                     cast,
                     container,
+                    transforms,
                     visible,
                 })
             }
         }
 
-        const FIELDS: &[&str] = &["container", "from", "name", "to", "visible"];
+        const FIELDS: &[&str] = &["container", "from", "name", "to", "transforms", "visible"];
         deserializer.deserialize_struct("Field", FIELDS, FieldVisitor)
     }
 }
@@ -140,7 +307,210 @@ impl<'de> Deserialize<'de> for Field {
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Format {
+    Csv,
     Json,
+    Kv,
+    Xml,
+}
+
+/// A transform applied to a field's value, transforms are chained in the order they are defined.
+#[derive(Clone, Debug)]
+pub enum Transform {
+    Base64,
+    Kv {
+        key: String,
+        delimiter: String,
+        separator: String,
+    },
+    Lowercase,
+    RegexExtract {
+        regex: Regex,
+        group: usize,
+    },
+    Split {
+        separator: String,
+        index: usize,
+    },
+    UrlDecode,
+    Utf16le,
+}
+
+impl Transform {
+    // NOTE: We work on bytes so that decoders can be chained, i.e. base64 then utf16le.
+    pub fn apply(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+        match self {
+            Self::Base64 => {
+                let s = std::str::from_utf8(&data).ok()?.trim();
+                match general_purpose::STANDARD.decode(s) {
+                    Ok(decoded) => Some(decoded),
+                    Err(_) => general_purpose::STANDARD_NO_PAD
+                        .decode(s.trim_end_matches('='))
+                        .ok(),
+                }
+            }
+            Self::Kv {
+                key,
+                delimiter,
+                separator,
+            } => {
+                let s = String::from_utf8_lossy(&data);
+                s.split(delimiter.as_str()).find_map(|pair| {
+                    let (k, v) = pair.split_once(separator.as_str())?;
+                    if k.trim() == key {
+                        Some(v.trim().as_bytes().to_vec())
+                    } else {
+                        None
+                    }
+                })
+            }
+            Self::Lowercase => Some(String::from_utf8_lossy(&data).to_lowercase().into_bytes()),
+            Self::RegexExtract { regex, group } => {
+                let s = String::from_utf8_lossy(&data);
+                let captures = regex.captures(&s)?;
+                Some(captures.get(*group)?.as_str().as_bytes().to_vec())
+            }
+            Self::Split { separator, index } => {
+                let s = String::from_utf8_lossy(&data);
+                Some(s.split(separator.as_str()).nth(*index)?.as_bytes().to_vec())
+            }
+            Self::UrlDecode => Some(percent_encoding::percent_decode(&data).collect()),
+            Self::Utf16le => {
+                let wide = data
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
+                Some(String::from_utf16_lossy(&wide).into_bytes())
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Transform {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Kv {
+            key: String,
+            #[serde(default = "Kv::delimiter")]
+            delimiter: String,
+            #[serde(default = "Kv::separator")]
+            separator: String,
+        }
+        impl Kv {
+            fn delimiter() -> String {
+                ";".to_owned()
+            }
+            fn separator() -> String {
+                "=".to_owned()
+            }
+        }
+
+        #[derive(Deserialize)]
+        struct RegexExtract {
+            pattern: String,
+            #[serde(default = "RegexExtract::group")]
+            group: usize,
+        }
+        impl RegexExtract {
+            fn group() -> usize {
+                1
+            }
+        }
+
+        #[derive(Deserialize)]
+        struct Split {
+            separator: String,
+            index: usize,
+        }
+
+        struct TransformVisitor;
+
+        impl<'de> Visitor<'de> for TransformVisitor {
+            type Value = Transform;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a transform")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Transform, E>
+            where
+                E: de::Error,
+            {
+                match value {
+                    "base64" => Ok(Transform::Base64),
+                    "lowercase" => Ok(Transform::Lowercase),
+                    "url_decode" => Ok(Transform::UrlDecode),
+                    "utf16le" => Ok(Transform::Utf16le),
+                    "kv" | "regex_extract" | "split" => Err(de::Error::custom(format!(
+                        "the '{}' transform requires arguments",
+                        value
+                    ))),
+                    _ => Err(de::Error::unknown_variant(value, TRANSFORMS)),
+                }
+            }
+
+            fn visit_map<V>(self, mut map: V) -> Result<Transform, V::Error>
+            where
+                V: MapAccess<'de>,
+            {
+                let key = match map.next_key::<String>()? {
+                    Some(key) => key,
+                    None => return Err(de::Error::custom("expected a transform")),
+                };
+                let transform = match key.as_str() {
+                    "kv" => {
+                        let kv: Kv = map.next_value()?;
+                        Transform::Kv {
+                            key: kv.key,
+                            delimiter: kv.delimiter,
+                            separator: kv.separator,
+                        }
+                    }
+                    "regex_extract" => {
+                        let extract: RegexExtract = map.next_value()?;
+                        let regex = Regex::new(&extract.pattern).map_err(de::Error::custom)?;
+                        if extract.group >= regex.captures_len() {
+                            return Err(de::Error::custom(format!(
+                                "regex_extract group {} does not exist in '{}'",
+                                extract.group, extract.pattern
+                            )));
+                        }
+                        Transform::RegexExtract {
+                            regex,
+                            group: extract.group,
+                        }
+                    }
+                    "split" => {
+                        let split: Split = map.next_value()?;
+                        Transform::Split {
+                            separator: split.separator,
+                            index: split.index,
+                        }
+                    }
+                    _ => return Err(de::Error::unknown_variant(&key, TRANSFORMS)),
+                };
+                if map.next_key::<String>()?.is_some() {
+                    return Err(de::Error::custom(
+                        "only one transform can be given per entry",
+                    ));
+                }
+                Ok(transform)
+            }
+        }
+
+        const TRANSFORMS: &[&str] = &[
+            "base64",
+            "kv",
+            "lowercase",
+            "regex_extract",
+            "split",
+            "url_decode",
+            "utf16le",
+        ];
+        deserializer.deserialize_any(TransformVisitor)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -155,6 +525,8 @@ pub struct Rule {
     pub level: Level,
     pub status: Status,
     pub timestamp: String,
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
 
     pub fields: Vec<Field>,
 
@@ -162,6 +534,16 @@ pub struct Rule {
 
     #[serde(default)]
     pub aggregate: Option<Aggregate>,
+    /// The ATT&CK techniques that the rule detects, i.e. `T1059.001`.
+    #[serde(default)]
+    pub attack: Option<Vec<String>>,
+
+    /// Documents that the rule must match, tested with `chainsaw test`.
+    #[serde(default)]
+    pub true_positives: Vec<Yaml>,
+    /// Documents that the rule must not match, tested with `chainsaw test`.
+    #[serde(default)]
+    pub true_negatives: Vec<Yaml>,
 }
 
 pub fn load(rule: &Path) -> crate::Result<Rule> {
@@ -169,18 +551,30 @@ pub fn load(rule: &Path) -> crate::Result<Rule> {
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
 
+    let base = rule.parent();
     let mut rule: Rule = serde_yaml::from_str(&contents)?;
     rule.filter = match rule.filter {
         Filter::Detection(mut detection) => {
             detection.expression =
                 optimiser::coalesce(detection.expression, &detection.identifiers);
-            detection.identifiers.clear();
+            // NOTE: The identifiers are kept so that matches can be explained.
+            for expression in detection.identifiers.values_mut() {
+                let e = std::mem::replace(expression, Expression::Null);
+                *expression = crate::modifiers::rewrite(crate::lists::rewrite(e, base)?);
+                crate::modifiers::check(expression)?;
+            }
+            detection.expression = crate::lists::rewrite(detection.expression, base)?;
+            detection.expression = crate::modifiers::rewrite(detection.expression);
+            crate::modifiers::check(&detection.expression)?;
             detection.expression = optimiser::shake(detection.expression);
             detection.expression = optimiser::rewrite(detection.expression);
             detection.expression = optimiser::matrix(detection.expression);
             Filter::Detection(detection)
         }
         Filter::Expression(expression) => Filter::Expression({
+            let expression = crate::lists::rewrite(expression, base)?;
+            let expression = crate::modifiers::rewrite(expression);
+            crate::modifiers::check(&expression)?;
             let expression = optimiser::shake(expression);
             let expression = optimiser::rewrite(expression);
             optimiser::matrix(expression)
@@ -188,3 +582,121 @@ pub fn load(rule: &Path) -> crate::Result<Rule> {
     };
     Ok(rule)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn container(format: Format, delimiter: Option<&str>, headers: Option<Vec<&str>>) -> Container {
+        Container {
+            field: "Data".to_owned(),
+            format,
+            delimiter: delimiter.map(|d| d.to_owned()),
+            headers: headers.map(|h| h.into_iter().map(|h| h.to_owned()).collect()),
+            separator: None,
+        }
+    }
+
+    #[test]
+    fn test_container_csv() {
+        let data = "2023-03-16,Trojan:Win32/Emotet,C:\\Users\\Public\\a.exe,Quarantined";
+        let parsed = container(Format::Csv, None, None).parse(data).unwrap();
+        assert_eq!(parsed["1"], json!("Trojan:Win32/Emotet"));
+        assert_eq!(parsed["3"], json!("Quarantined"));
+
+        let data = "Trojan:Win32/Emotet|\"C:\\Program Files\\a|b.exe\"";
+        let headers = Some(vec!["Threat", "Path"]);
+        let parsed = container(Format::Csv, Some("|"), headers)
+            .parse(data)
+            .unwrap();
+        assert_eq!(parsed["Threat"], json!("Trojan:Win32/Emotet"));
+        assert_eq!(parsed["Path"], json!("C:\\Program Files\\a|b.exe"));
+    }
+
+    #[test]
+    fn test_container_csv_delimiter() {
+        let field =
+            "name: Threat\nto: Threat\ncontainer:\n  field: Data\n  format: csv\n  delimiter: ";
+        assert!(serde_yaml::from_str::<Field>(&format!("{}'|'", field)).is_ok());
+        assert!(serde_yaml::from_str::<Field>(&format!("{}'||'", field)).is_err());
+        assert!(serde_yaml::from_str::<Field>(&format!("{}'¦'", field)).is_err());
+        // NOTE: Other formats support delimiters of any length.
+        let field =
+            "name: Host\nto: Host\ncontainer:\n  field: Data\n  format: kv\n  delimiter: ';;'";
+        assert!(serde_yaml::from_str::<Field>(field).is_ok());
+    }
+
+    #[test]
+    fn test_container_kv() {
+        // NOTE: The HostApplication blob from PowerShell 400 and 600 events.
+        let data = "NewEngineState=Available\r\n\tPreviousEngineState=None\r\n\r\n\tSequenceNumber=13\r\n\r\n\tHostName=ConsoleHost\r\n\tHostVersion=5.1.19041.1\r\n\tHostApplication=powershell.exe -nop -w hidden -enc SQBFAFgA\r\n\tEngineVersion=5.1.19041.1";
+        let parsed = container(Format::Kv, None, None).parse(data).unwrap();
+        assert_eq!(parsed["HostName"], json!("ConsoleHost"));
+        assert_eq!(
+            parsed["HostApplication"],
+            json!("powershell.exe -nop -w hidden -enc SQBFAFgA")
+        );
+        assert_eq!(parsed["SequenceNumber"], json!("13"));
+
+        let mut kv = container(Format::Kv, Some(";"), None);
+        kv.separator = Some(": ".to_owned());
+        let parsed = kv.parse("a: 1; b: x=y").unwrap();
+        assert_eq!(parsed, json!({"a": "1", "b": "x=y"}));
+    }
+
+    #[test]
+    fn test_container_xml() {
+        // NOTE: The TaskContent from a scheduled task created event (4698).
+        let data = r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Author>CORP\alice</Author>
+    <URI>\Updater</URI>
+  </RegistrationInfo>
+  <Settings>
+    <Hidden>true</Hidden>
+    <RunOnlyIfIdle />
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>cmd.exe</Command>
+      <Arguments>/c whoami &amp;&amp; hostname</Arguments>
+    </Exec>
+    <Exec>
+      <Command>powershell.exe</Command>
+    </Exec>
+  </Actions>
+</Task>"#;
+        let parsed = container(Format::Xml, None, None).parse(data).unwrap();
+        let task = &parsed["Task"];
+        assert_eq!(task["@version"], json!("1.2"));
+        assert_eq!(task["RegistrationInfo"]["Author"], json!("CORP\\alice"));
+        assert_eq!(task["Settings"]["Hidden"], json!("true"));
+        assert_eq!(task["Settings"]["RunOnlyIfIdle"], json!(""));
+        assert_eq!(task["Actions"]["@Context"], json!("Author"));
+        assert_eq!(
+            task["Actions"]["Exec"],
+            json!([
+                {"Command": "cmd.exe", "Arguments": "/c whoami && hostname"},
+                {"Command": "powershell.exe"},
+            ])
+        );
+
+        let parsed = container(Format::Xml, None, None)
+            .parse(r#"<Data Name="Script"><![CDATA[a < b]]></Data>"#)
+            .unwrap();
+        assert_eq!(
+            parsed,
+            json!({"Data": {"@Name": "Script", "$text": "a < b"}})
+        );
+
+        assert!(container(Format::Xml, None, None)
+            .parse("<a><b></a>")
+            .is_none());
+        assert!(container(Format::Xml, None, None)
+            .parse("not xml")
+            .is_none());
+    }
+}
@@ -1,30 +1,39 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::path::Path;
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
+use serde_yaml::Value as Yaml;
 use tau_engine::{
     core::{
         optimiser,
-        parser::{Expression, Pattern},
+        parser::{BoolSym, Expression, Pattern},
         Detection,
     },
     Document,
 };
 
 use crate::file::Kind as FileKind;
+use crate::lists::Lists;
 
 pub use self::chainsaw::Rule as Chainsaw;
 pub use self::sigma::Rule as Sigma;
+pub use self::yara::Rule as Yara;
 
 pub mod chainsaw;
 pub mod sigma;
+pub mod yara;
+
+/// The identifiers of a rule that were true for a document, mapped to the fields and values that
+/// satisfied them.
+pub type Matched = BTreeMap<String, BTreeMap<String, String>>;
 
 #[derive(Clone, Debug)]
 pub enum Rule {
     Chainsaw(Chainsaw),
     Sigma(Sigma),
+    Yara(Yara),
 }
 
 impl Rule {
@@ -33,14 +42,59 @@ impl Rule {
         match self {
             Self::Chainsaw(c) => &c.aggregate,
             Self::Sigma(s) => &s.aggregate,
+            Self::Yara(_) => &None,
         }
     }
 
+    /// Returns the ATT&CK techniques that the rule has been tagged with.
+    pub fn attack(&self) -> Vec<String> {
+        let tags = match self {
+            Self::Chainsaw(c) => c.attack.as_ref(),
+            Self::Sigma(s) => s.tags.as_ref(),
+            Self::Yara(_) => None,
+        };
+        tags.map(|t| {
+            t.iter()
+                .filter_map(|t| crate::attack::technique(t))
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn id(&self) -> Option<&String> {
+        match self {
+            Self::Chainsaw(_) => None,
+            Self::Sigma(s) => s.id.as_ref(),
+            Self::Yara(_) => None,
+        }
+    }
+
+    /// Returns the labels of the list entries that the rule matched in the document, which for
+    /// intel rules are the ids of the indicators that matched.
+    pub fn indicators(&self, document: &dyn Document, lists: &Lists) -> Vec<String> {
+        let expression = match self {
+            Self::Sigma(s) => &s.tau.detection.expression,
+            Self::Chainsaw(_) | Self::Yara(_) => return vec![],
+        };
+        let mut indicators = vec![];
+        for field in crate::ext::tau::extract_fields(expression) {
+            for label in lists.labels(document, &field) {
+                if !indicators.contains(&label) {
+                    indicators.push(label);
+                }
+            }
+        }
+        indicators.sort();
+        indicators
+    }
+
     #[inline]
     pub fn is_kind(&self, kind: &Kind) -> bool {
         match self {
             Self::Chainsaw(_) => kind == &Kind::Chainsaw,
             Self::Sigma(_) => kind == &Kind::Sigma,
+            Self::Yara(_) => kind == &Kind::Yara,
         }
     }
 
@@ -49,6 +103,7 @@ impl Rule {
         match self {
             Self::Chainsaw(c) => &c.level,
             Self::Sigma(s) => &s.level,
+            Self::Yara(y) => &y.level,
         }
     }
 
@@ -56,8 +111,51 @@ impl Rule {
     pub fn types(&self) -> &FileKind {
         match self {
             Self::Chainsaw(c) => &c.kind,
-            Self::Sigma(_) => &FileKind::Unknown,
+            Self::Sigma(_) | Self::Yara(_) => &FileKind::Unknown,
+        }
+    }
+
+    /// Drops the identifiers that are kept to explain matches, they are not used when solving.
+    pub fn clear_identifiers(&mut self) {
+        match self {
+            Self::Chainsaw(c) => {
+                if let Filter::Detection(d) = &mut c.filter {
+                    d.identifiers.clear();
+                }
+            }
+            Self::Sigma(s) => s.tau.detection.identifiers.clear(),
+            Self::Yara(_) => {}
+        }
+    }
+
+    /// Explains why the rule matched the document, expressions without identifiers are reported
+    /// under `condition`.
+    pub fn matched(&self, document: &dyn Document) -> Matched {
+        let (expression, identifiers) = match self {
+            Self::Chainsaw(c) => match &c.filter {
+                Filter::Detection(d) => (&d.expression, Some(&d.identifiers)),
+                Filter::Expression(e) => (e, None),
+            },
+            Self::Sigma(s) => (
+                &s.tau.detection.expression,
+                Some(&s.tau.detection.identifiers),
+            ),
+            Self::Yara(_) => return Matched::new(),
+        };
+        let mut matched = Matched::new();
+        match identifiers {
+            Some(identifiers) if !identifiers.is_empty() => {
+                for (name, expression) in identifiers {
+                    if tau_engine::core::solve(expression, document) {
+                        matched.insert(name.clone(), satisfied(expression, document));
+                    }
+                }
+            }
+            _ => {
+                matched.insert("condition".to_owned(), satisfied(expression, document));
+            }
         }
+        matched
     }
 
     #[inline]
@@ -65,6 +163,17 @@ impl Rule {
         match self {
             Self::Chainsaw(c) => &c.name,
             Self::Sigma(s) => &s.name,
+            Self::Yara(y) => &y.name,
+        }
+    }
+
+    /// Returns the sample documents that the rule must and must not match, each one is either
+    /// embedded in the rule or a path to a file of them.
+    pub fn samples(&self) -> (&[Yaml], &[Yaml]) {
+        match self {
+            Self::Chainsaw(c) => (&c.true_positives, &c.true_negatives),
+            Self::Sigma(s) => (&s.tau.true_positives, &s.tau.true_negatives),
+            Self::Yara(_) => (&[], &[]),
         }
     }
 
@@ -76,6 +185,8 @@ impl Rule {
                 Filter::Expression(expression) => tau_engine::core::solve(expression, document),
             },
             Self::Sigma(s) => tau_engine::solve(&s.tau.detection, document),
+            // NOTE: YARA rules are not solved against documents, the hunter scans fields instead.
+            Self::Yara(_) => false,
         }
     }
 
@@ -84,6 +195,7 @@ impl Rule {
         match self {
             Self::Chainsaw(c) => &c.status,
             Self::Sigma(s) => &s.status,
+            Self::Yara(y) => &y.status,
         }
     }
 }
@@ -108,6 +220,7 @@ pub enum Filter {
 pub enum Kind {
     Chainsaw,
     Sigma,
+    Yara,
 }
 
 impl Default for Kind {
@@ -121,6 +234,7 @@ impl fmt::Display for Kind {
         match self {
             Self::Chainsaw => write!(f, "chainsaw"),
             Self::Sigma => write!(f, "sigma"),
+            Self::Yara => write!(f, "yara"),
         }
     }
 }
@@ -132,7 +246,8 @@ impl FromStr for Kind {
         let v = match s {
             "chainsaw" => Self::Chainsaw,
             "sigma" => Self::Sigma,
-            _ => anyhow::bail!("unknown kind, must be: chainsaw, or sigma"),
+            "yara" => Self::Yara,
+            _ => anyhow::bail!("unknown kind, must be: chainsaw, sigma or yara"),
         };
         Ok(v)
     }
@@ -210,7 +325,11 @@ pub fn load(
     kinds: &Option<HashSet<Kind>>,
     levels: &Option<HashSet<Level>>,
     statuses: &Option<HashSet<Status>>,
+    config: &sigma::Config,
 ) -> crate::Result<Vec<Rule>> {
+    if let Kind::Yara = kind {
+        anyhow::bail!("yara rules must be compiled together, use the hunter to load them");
+    }
     if let Some(x) = path.extension() {
         if x != "yml" && x != "yaml" {
             anyhow::bail!("rule must have a yaml file extension");
@@ -232,7 +351,18 @@ pub fn load(
                     return Ok(vec![]);
                 }
             }
-            let sigma = match sigma::load(path)?
+            // NOTE: Documents that are not rules are skipped here, the linter reports them, and
+            // filters only apply when they are provided through the config.
+            let collection = sigma::load(path, config)?;
+            if !collection.filters.is_empty() {
+                cs_eyellowln!(
+                    "[!] Ignoring {} Sigma filters in '{}', provide them with '--sigma-filter'",
+                    collection.filters.len(),
+                    path.display()
+                );
+            }
+            let sigma = match collection
+                .rules
                 .into_iter()
                 .map(serde_yaml::from_value::<Sigma>)
                 .collect::<Result<Vec<_>, _>>()
@@ -242,21 +372,23 @@ pub fn load(
                     anyhow::bail!("failed to load rule, run the linter for more information");
                 }
             };
+            let base = path.parent();
             sigma
                 .into_iter()
-                .map(|mut s| {
-                    s.tau.detection.expression = optimiser::coalesce(
-                        s.tau.detection.expression,
-                        &s.tau.detection.identifiers,
-                    );
-                    s.tau.detection.identifiers.clear();
-                    s.tau.detection.expression = optimiser::shake(s.tau.detection.expression);
-                    s.tau.detection.expression = optimiser::rewrite(s.tau.detection.expression);
-                    s.tau.detection.expression = optimiser::matrix(s.tau.detection.expression);
-                    Rule::Sigma(s)
+                .map(|s| {
+                    let mut s = sigma::apply_filters(s, &config.filters);
+                    for expression in s.tau.detection.identifiers.values_mut() {
+                        let e = std::mem::replace(expression, Expression::Null);
+                        *expression = crate::lists::rewrite(e, base)?;
+                        crate::modifiers::check(expression)?;
+                    }
+                    let e = std::mem::replace(&mut s.tau.detection.expression, Expression::Null);
+                    s.tau.detection.expression = crate::lists::rewrite(e, base)?;
+                    Ok(Rule::Sigma(optimise(s)))
                 })
-                .collect()
+                .collect::<crate::Result<Vec<_>>>()?
         }
+        Kind::Yara => unreachable!(),
     };
     if let Some(levels) = levels.as_ref() {
         rules.retain(|r| levels.contains(r.level()));
@@ -267,31 +399,114 @@ pub fn load(
     Ok(rules)
 }
 
-pub fn lint(kind: &Kind, path: &Path) -> crate::Result<Vec<Filter>> {
+/// Collapses the rule's identifiers into a single optimised expression.
+// NOTE: The identifiers are kept so that matches can be explained, the hunter drops them unless
+// they are needed.
+pub(crate) fn optimise(mut sigma: Sigma) -> Sigma {
+    sigma.tau.detection.expression = optimiser::coalesce(
+        sigma.tau.detection.expression,
+        &sigma.tau.detection.identifiers,
+    );
+    sigma.tau.detection.expression = optimiser::shake(sigma.tau.detection.expression);
+    sigma.tau.detection.expression = optimiser::rewrite(sigma.tau.detection.expression);
+    sigma.tau.detection.expression = optimiser::matrix(sigma.tau.detection.expression);
+    sigma
+}
+
+// Collects the fields and values from the parts of an expression that are true for the document,
+// negated expressions are skipped as they are satisfied by what is not in the document.
+fn satisfied(expression: &Expression, document: &dyn Document) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    match expression {
+        Expression::BooleanGroup(_, expressions) => {
+            for expression in expressions {
+                fields.extend(satisfied(expression, document));
+            }
+        }
+        Expression::BooleanExpression(left, BoolSym::And, right)
+        | Expression::BooleanExpression(left, BoolSym::Or, right) => {
+            fields.extend(satisfied(left, document));
+            fields.extend(satisfied(right, document));
+        }
+        Expression::BooleanExpression(..)
+        | Expression::Cast(..)
+        | Expression::Field(_)
+        | Expression::Match(..)
+        | Expression::Matrix(..)
+        | Expression::Nested(..)
+        | Expression::Search(..) => {
+            if tau_engine::core::solve(expression, document) {
+                for field in crate::ext::tau::extract_fields(expression) {
+                    // NOTE: Virtual fields report the values of the fields that they check.
+                    for field in crate::modifiers::fields(&field) {
+                        if let Some(value) = document.find(field) {
+                            let value = match value.to_string() {
+                                Some(v) => v,
+                                None => crate::cli::tau_to_json(value).to_string(),
+                            };
+                            fields.insert(field.to_owned(), value);
+                        }
+                    }
+                }
+            }
+        }
+        Expression::Boolean(_)
+        | Expression::Float(_)
+        | Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Negate(_)
+        | Expression::Null => {}
+    }
+    fields
+}
+
+/// Validates a rule file, returning its detections along with any Sigma documents that were not
+/// loaded as rules.
+pub fn lint(
+    kind: &Kind,
+    path: &Path,
+    config: &sigma::Config,
+) -> crate::Result<(Vec<Filter>, Vec<sigma::Discarded>)> {
+    if let Kind::Yara = kind {
+        if let Some(x) = path.extension() {
+            if x != "yar" && x != "yara" {
+                anyhow::bail!("rule must have a yar or yara file extension");
+            }
+        }
+        // NOTE: YARA rules have no tau representation, so we just ensure that they compile.
+        yara::lint(path)?;
+        return Ok((vec![], vec![]));
+    }
     if let Some(x) = path.extension() {
         if x != "yml" && x != "yaml" {
             anyhow::bail!("rule must have a yaml file extension");
         }
     }
-    let detections = match kind {
+    let (detections, discarded) = match kind {
         Kind::Chainsaw => match chainsaw::load(path) {
-            Ok(rule) => {
-                vec![rule.filter]
-            }
+            Ok(rule) => (vec![rule.filter], vec![]),
             Err(e) => {
                 anyhow::bail!("{}", e);
             }
         },
-        Kind::Sigma => match sigma::load(path) {
-            Ok(yamls) => {
-                let sigma = yamls
+        Kind::Sigma => match sigma::load(path, config) {
+            Ok(collection) => {
+                let sigma = collection
+                    .rules
                     .into_iter()
                     .map(serde_yaml::from_value::<Sigma>)
                     .collect::<Result<Vec<_>, _>>()?;
-                sigma
+                let detections = sigma
                     .into_iter()
                     .map(|r| Filter::Detection(r.tau.detection))
-                    .collect()
+                    .chain(
+                        collection
+                            .filters
+                            .into_iter()
+                            .map(|f| Filter::Detection(f.detection)),
+                    )
+                    .collect();
+                (detections, collection.discarded)
             }
             Err(e) => {
                 if let Some(source) = e.source() {
@@ -301,6 +516,7 @@ pub fn lint(kind: &Kind, path: &Path) -> crate::Result<Vec<Filter>> {
                 }
             }
         },
+        Kind::Yara => unreachable!(),
     };
-    Ok(detections)
+    Ok((detections, discarded))
 }
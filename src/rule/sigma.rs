@@ -1,16 +1,28 @@
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
+use std::str::FromStr;
 
 use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Sequence, Value as Yaml};
-use tau_engine::{Document, Rule as Tau};
+use tau_engine::{
+    core::{
+        optimiser,
+        parser::{BoolSym, Expression},
+        Detection as TauDetection,
+    },
+    Document, Rule as Tau,
+};
 
 use super::{Level, Status};
+use crate::lists::Cidr;
+use crate::modifiers::{CIDR, EXISTS, FIELDREF, GLOB};
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -71,6 +83,52 @@ impl Document for Rule {
     }
 }
 
+/// The environment specific values that Sigma rules can reference, loaded from the file provided
+/// with `--sigma-config`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    /// The values that each placeholder expands into when used with the `expand` modifier.
+    #[serde(default)]
+    pub placeholders: HashMap<String, Vec<String>>,
+    /// The filter rules to apply to the rules that they reference, loaded from `--sigma-filter`.
+    #[serde(skip)]
+    pub filters: Vec<MetaFilter>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        Ok(serde_yaml::from_str(&content)?)
+    }
+
+    /// The filters that do not reference any of the rules, usually due to a typo in their ids.
+    pub fn unreferenced<'a>(&'a self, rules: &[super::Rule]) -> Vec<&'a MetaFilter> {
+        self.filters
+            .iter()
+            .filter(|f| {
+                !rules.iter().any(|r| match r {
+                    super::Rule::Sigma(s) => f.references(s),
+                    _ => false,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The error returned when a rule expands a placeholder that has no values in the config.
+#[derive(Debug)]
+pub struct Unresolved(pub String);
+
+impl fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unresolved placeholder - %{}%", self.0)
+    }
+}
+
+impl std::error::Error for Unresolved {}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Aggregate {
     pub count: String,
@@ -90,8 +148,6 @@ struct Header {
     pub title: String,
     pub description: String,
     #[serde(default)]
-    pub action: Option<String>,
-    #[serde(default)]
     pub author: Option<String>,
     #[serde(default)]
     pub falsepositives: Option<Vec<String>>,
@@ -105,6 +161,10 @@ struct Header {
     pub status: Option<String>,
     #[serde(default)]
     pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub true_negatives: Option<Sequence>,
+    #[serde(default)]
+    pub true_positives: Option<Sequence>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -119,6 +179,33 @@ pub struct LogSource {
     pub service: Option<String>,
 }
 
+impl LogSource {
+    /// The category, product and service of the logsource, in that order.
+    pub(crate) fn parts(&self) -> [Option<&str>; 3] {
+        [
+            self.category.as_deref(),
+            self.product.as_deref(),
+            self.service.as_deref(),
+        ]
+    }
+}
+
+/// Checks that each part of a logsource that is wanted is one of the values provided for it,
+/// ignoring case. The parts are the category, product and service, where a part that is not
+/// wanted is always satisfied and a part that is not provided never is.
+pub(crate) fn within(wanted: [Option<&str>; 3], provided: [Option<Vec<&str>>; 3]) -> bool {
+    wanted
+        .iter()
+        .zip(provided.iter())
+        .all(|(wanted, provided)| match (wanted, provided) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(wanted), Some(provided)) => {
+                provided.iter().any(|p| p.eq_ignore_ascii_case(wanted))
+            }
+        })
+}
+
 #[derive(Clone, Deserialize)]
 struct Sigma {
     #[serde(default)]
@@ -176,6 +263,21 @@ impl Sigma {
         }
         Some(tau)
     }
+
+    /// Returns the sample documents from the rule, these are not part of the Sigma specification
+    /// but are carried over so that rules can be tested with `chainsaw test`.
+    pub fn samples(&self) -> Mapping {
+        let mut samples = Mapping::new();
+        if let Some(header) = &self.header {
+            if let Some(negatives) = &header.true_negatives {
+                samples.insert("true_negatives".into(), negatives.clone().into());
+            }
+            if let Some(positives) = &header.true_positives {
+                samples.insert("true_positives".into(), positives.clone().into());
+            }
+        }
+        samples
+    }
 }
 
 trait Condition {
@@ -196,113 +298,368 @@ impl Condition for String {
 }
 
 trait Match {
+    fn as_cased(&self, leading: bool, trailing: bool) -> String;
     fn as_contains(&self) -> String;
     fn as_endswith(&self) -> String;
-    fn as_match(&self) -> Option<String>;
-    fn as_regex(&self, convert: bool) -> Option<String>;
+    fn as_match(&self) -> String;
+    fn as_regex(&self) -> Option<String>;
     fn as_startswith(&self) -> String;
 }
 
 impl Match for String {
+    fn as_cased(&self, leading: bool, trailing: bool) -> String {
+        as_cased(self, leading, trailing)
+    }
     fn as_contains(&self) -> String {
-        format!("i*{}*", self)
+        as_wildcard(self, true, true)
     }
     fn as_endswith(&self) -> String {
-        format!("i*{}", self)
-    }
-    fn as_match(&self) -> Option<String> {
-        // TODO: Handle nested wildcards
-        let len = self.len();
-        if len > 1 {
-            let mut start = 0;
-            let mut end = len;
-            if self.starts_with('*') {
-                start += 1;
-            }
-            if self.ends_with('*') {
-                end -= 1;
-            }
-            if self[start..end].contains('*') || self[start..end].contains('?') {
-                return None;
-            }
+        as_wildcard(self, true, false)
+    }
+    fn as_match(&self) -> String {
+        as_wildcard(self, false, false)
+    }
+    fn as_regex(&self) -> Option<String> {
+        let _ = Regex::new(self).ok()?;
+        Some(format!("?{}", self))
+    }
+    fn as_startswith(&self) -> String {
+        as_wildcard(self, false, true)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Any,
+    One,
+    Text(char),
+}
+
+// Splits a value into its wildcards and text, a backslash escapes a wildcard or another backslash
+// and is otherwise kept as is.
+// https://github.com/SigmaHQ/sigma-specification/blob/main/specification/sigma-rules-specification.md#escaping
+fn tokenize(value: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '*' => Token::Any,
+            '?' => Token::One,
+            '\\' => match chars.peek() {
+                Some(&e @ ('*' | '?' | '\\')) => {
+                    chars.next();
+                    Token::Text(e)
+                }
+                _ => Token::Text('\\'),
+            },
+            c => Token::Text(c),
+        };
+        if token == Token::Any && tokens.last() == Some(&Token::Any) {
+            continue;
         }
-        Some(format!("i{}", self))
-    }
-    fn as_regex(&self, convert: bool) -> Option<String> {
-        if convert {
-            let literal = regex::escape(self);
-            let mut scratch = Vec::with_capacity(literal.len());
-            let mut escaped = false;
-            for c in literal.chars() {
-                match c {
-                    '*' | '?' => {
-                        if !escaped {
-                            scratch.push('.');
+        tokens.push(token);
+    }
+    tokens
+}
+
+// Converts a value into a tau pattern when its only wildcards are at either end, otherwise it is
+// converted into a glob that the hunt engine matches, which is marked with the glob prefix.
+fn as_wildcard(value: &str, leading: bool, trailing: bool) -> String {
+    let mut tokens = tokenize(value);
+    if leading && tokens.first() != Some(&Token::Any) {
+        tokens.insert(0, Token::Any);
+    }
+    if trailing && tokens.last() != Some(&Token::Any) {
+        tokens.push(Token::Any);
+    }
+    let start = usize::from(tokens.first() == Some(&Token::Any));
+    let end = if tokens.len() > start && tokens.last() == Some(&Token::Any) {
+        tokens.len() - 1
+    } else {
+        tokens.len()
+    };
+    // NOTE: Tau treats a `*` or `?` in the text as a wildcard, so text containing them has to be
+    // matched as a glob too.
+    let text = tokens[start..end]
+        .iter()
+        .map(|t| match t {
+            Token::Text(c) if *c != '*' && *c != '?' => Some(*c),
+            _ => None,
+        })
+        .collect::<Option<String>>();
+    match text {
+        Some(text) => format!(
+            "i{}{}{}",
+            if start == 1 { "*" } else { "" },
+            text,
+            if end < tokens.len() { "*" } else { "" }
+        ),
+        None => {
+            let mut glob = GLOB.to_owned();
+            for token in tokens {
+                match token {
+                    Token::Any => glob.push('*'),
+                    Token::One => glob.push('?'),
+                    Token::Text(c) => {
+                        if matches!(c, '*' | '?' | '\\' | '|') {
+                            glob.push('\\');
                         }
-                    }
-                    '\\' => {
-                        escaped = !escaped;
-                    }
-                    _ => {
-                        escaped = false;
+                        glob.push(c);
                     }
                 }
-                scratch.push(c);
             }
-            Some(format!("?{}", scratch.into_iter().collect::<String>()))
+            glob
+        }
+    }
+}
+
+// Converts a value into a case-sensitive regex, which avoids tau treating the start of the value as
+// a pattern prefix and supports wildcards anywhere in it.
+fn as_cased(value: &str, leading: bool, trailing: bool) -> String {
+    let mut tokens = tokenize(value);
+    if leading && tokens.first() != Some(&Token::Any) {
+        tokens.insert(0, Token::Any);
+    }
+    if trailing && tokens.last() != Some(&Token::Any) {
+        tokens.push(Token::Any);
+    }
+    let mut regex = "?(?s)^".to_owned();
+    for token in tokens {
+        match token {
+            Token::Any => regex.push_str(".*"),
+            Token::One => regex.push('.'),
+            Token::Text(c) => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+// Tau has no pattern for wildcards in the middle of a value, so when any value of a field needs
+// one they are all matched as globs through a virtual field instead.
+fn as_glob(field: &str, value: Yaml) -> Result<(String, Yaml)> {
+    let values = match &value {
+        Yaml::Sequence(s) => s.iter().collect::<Vec<_>>(),
+        v => vec![v],
+    };
+    let globbed = values
+        .iter()
+        .any(|v| v.as_str().map(|s| s.starts_with(GLOB)).unwrap_or_default());
+    if !globbed {
+        return Ok((field.to_owned(), value));
+    }
+    let mut globs = vec![];
+    for value in values {
+        let glob = match value {
+            Yaml::String(s) => match s.strip_prefix(GLOB) {
+                Some(glob) => glob.to_owned(),
+                // NOTE: Tau patterns only have wildcards at either end, so the rest is text.
+                None => match s.strip_prefix('i') {
+                    Some(s) => s.replace('\\', "\\\\").replace('|', "\\|"),
+                    None => return Err(anyhow!(s.to_owned()).context("unsupported match")),
+                },
+            },
+            Yaml::Bool(b) => b.to_string(),
+            Yaml::Number(n) => n.to_string(),
+            _ => return Err(anyhow!("{:?}", value).context("unsupported match")),
+        };
+        globs.push(glob);
+    }
+    Ok((
+        format!("{}{}|{}", GLOB, field, globs.join("|")),
+        "true".into(),
+    ))
+}
+
+// The modifiers that can be converted, any others would silently change the logic of a rule so we
+// refuse to convert it instead.
+const MODIFIERS: &[&str] = &[
+    "all",
+    "base64",
+    "base64offset",
+    "cased",
+    "cidr",
+    "contains",
+    "endswith",
+    "exists",
+    "expand",
+    "fieldref",
+    "gt",
+    "gte",
+    "i",
+    "lt",
+    "lte",
+    "m",
+    "re",
+    "s",
+    "startswith",
+    "utf16",
+    "utf16be",
+    "utf16le",
+    "wide",
+    "windash",
+];
+
+// The modifiers that replace the comparison of a value entirely, so cannot be combined.
+const STANDALONE: &[&str] = &["cidr", "exists", "fieldref", "gt", "gte", "lt", "lte"];
+
+// The flags that can be set on a regular expression, which only apply to the `re` modifier.
+const REGEX_FLAGS: &[&str] = &["i", "m", "s"];
+
+// The characters that Windows accepts in place of a dash for command line flags.
+const DASHES: &[char] = &['-', '/', '\u{2013}', '\u{2014}', '\u{2015}'];
+
+fn check_modifiers(modifiers: &HashSet<String>) -> Result<()> {
+    for modifier in modifiers {
+        if !MODIFIERS.contains(&modifier.as_str()) {
+            return Err(anyhow!(modifier.to_owned()).context("unsupported modifier"));
+        }
+    }
+    if modifiers.len() > 1 && modifiers.iter().any(|m| STANDALONE.contains(&m.as_str())) {
+        let mut modifiers = modifiers.iter().cloned().collect::<Vec<_>>();
+        modifiers.sort();
+        return Err(anyhow!(modifiers.join(", ")).context("unsupported modifier combination"));
+    }
+    let flagged = modifiers.iter().any(|m| REGEX_FLAGS.contains(&m.as_str()));
+    let regex = modifiers.contains("re");
+    if (flagged && !regex) || (regex && modifiers.contains("cased")) {
+        let mut modifiers = modifiers.iter().cloned().collect::<Vec<_>>();
+        modifiers.sort();
+        return Err(anyhow!(modifiers.join(", ")).context("unsupported modifier combination"));
+    }
+    Ok(())
+}
+
+// Expands the dashes that start a flag into each of the characters that Windows also accepts for
+// them, a dash only starts a flag when it is at the start of a word.
+fn windash(value: &str, modifiers: &HashSet<String>) -> Vec<String> {
+    if !modifiers.contains("windash") {
+        return vec![value.to_owned()];
+    }
+    let word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let chars = value.chars().collect::<Vec<_>>();
+    let mut variants = vec![String::new()];
+    for (i, c) in chars.iter().enumerate() {
+        let flag = (*c == '-' || *c == '/')
+            && (i == 0 || !word(&chars[i - 1]))
+            && chars.get(i + 1).map(word).unwrap_or_default();
+        if flag {
+            variants = variants
+                .into_iter()
+                .flat_map(|v| DASHES.iter().map(move |d| format!("{}{}", v, d)))
+                .collect();
         } else {
-            let _ = Regex::new(self).ok()?;
-            Some(format!("?{}", self))
+            for variant in &mut variants {
+                variant.push(*c);
+            }
         }
     }
-    fn as_startswith(&self) -> String {
-        format!("i{}*", self)
+    variants
+}
+
+// Expands the placeholders in a value into each of the values that they are configured with, a
+// value that uses more than one placeholder expands into every combination of their values.
+fn expand(value: &str, modifiers: &HashSet<String>, config: &Config) -> Result<Vec<String>> {
+    if !modifiers.contains("expand") {
+        return Ok(vec![value.to_owned()]);
+    }
+    let mut variants = vec![String::new()];
+    let mut rest = value;
+    while let Some((head, tail)) = rest.split_once('%') {
+        let (name, tail) = match tail.split_once('%') {
+            Some(parts) => parts,
+            None => break,
+        };
+        let values = match config.placeholders.get(name) {
+            Some(values) if !values.is_empty() => values,
+            _ => return Err(Unresolved(name.to_owned()).into()),
+        };
+        variants = variants
+            .into_iter()
+            .flat_map(|v| values.iter().map(move |x| format!("{}{}{}", v, head, x)))
+            .collect();
+        rest = tail;
     }
+    for variant in &mut variants {
+        variant.push_str(rest);
+    }
+    Ok(variants)
 }
 
-fn parse_identifier(value: &Yaml, modifiers: &HashSet<String>) -> Result<Yaml> {
+// Applies the encoding modifiers to a value, as the position of a value within an encoded blob is
+// unknown `base64offset` returns the three variants that it could be encoded as.
+fn encode(value: &str, modifiers: &HashSet<String>) -> Result<Vec<String>> {
+    let bytes: Vec<u8> = if modifiers.contains("utf16le") || modifiers.contains("wide") {
+        value.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    } else if modifiers.contains("utf16be") {
+        value.encode_utf16().flat_map(|c| c.to_be_bytes()).collect()
+    } else if modifiers.contains("utf16") {
+        // NOTE: Sigma defines this as little endian with a byte order mark.
+        [0xFF, 0xFE]
+            .into_iter()
+            .chain(value.encode_utf16().flat_map(|c| c.to_le_bytes()))
+            .collect()
+    } else {
+        value.as_bytes().to_vec()
+    };
+    if modifiers.contains("base64offset") {
+        const START: [usize; 3] = [0, 2, 3];
+        const END: [usize; 3] = [0, 3, 2];
+        let mut variants = Vec::with_capacity(3);
+        for i in 0..3 {
+            let mut padded = vec![b' '; i];
+            padded.extend(&bytes);
+            let encoded = general_purpose::STANDARD.encode(&padded);
+            let end = encoded.len() - END[(bytes.len() + i) % 3];
+            if START[i] < end {
+                variants.push(encoded[START[i]..end].to_owned());
+            }
+        }
+        Ok(variants)
+    } else if modifiers.contains("base64") {
+        Ok(vec![general_purpose::STANDARD.encode(&bytes)])
+    } else {
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(vec![s]),
+            Err(_) => Err(anyhow!(value.to_owned()).context("unsupported encoding")),
+        }
+    }
+}
+
+fn parse_identifier(value: &Yaml, modifiers: &HashSet<String>, config: &Config) -> Result<Yaml> {
     let v = match value {
         Yaml::Mapping(m) => {
             let mut scratch = Mapping::new();
             for (k, v) in m {
-                scratch.insert(k.clone(), parse_identifier(v, modifiers)?);
+                scratch.insert(k.clone(), parse_identifier(v, modifiers, config)?);
             }
             Yaml::Mapping(scratch)
         }
         Yaml::Sequence(s) => {
             let mut scratch = vec![];
             for s in s {
-                scratch.push(parse_identifier(s, modifiers)?);
+                // NOTE: Encodings can expand a value into a list of variants, which are flattened
+                // into the list as either of them can match.
+                match parse_identifier(s, modifiers, config)? {
+                    Yaml::Sequence(s) => scratch.extend(s),
+                    s => scratch.push(s),
+                }
             }
             Yaml::Sequence(scratch)
         }
         Yaml::String(s) => {
-            if modifiers.contains("contains") {
-                Yaml::String(s.as_contains())
-            } else if modifiers.contains("endswith") {
-                Yaml::String(s.as_endswith())
-            } else if modifiers.contains("re") {
-                let r = match s.as_regex(false) {
-                    Some(r) => r,
-                    None => {
-                        return Err(anyhow!(s.to_owned()).context("unsupported regex"));
+            let mut scratch = vec![];
+            for s in expand(s, modifiers, config)? {
+                for s in windash(&s, modifiers) {
+                    for s in encode(&s, modifiers)? {
+                        scratch.push(Yaml::String(parse_string(s, modifiers)?));
                     }
-                };
-                Yaml::String(r)
-            } else if modifiers.contains("startswith") {
-                Yaml::String(s.as_startswith())
+                }
+            }
+            if scratch.len() == 1 {
+                scratch.remove(0)
             } else {
-                let s = match s.as_match() {
-                    Some(s) => s,
-                    None => {
-                        if let Some(r) = s.as_regex(true) {
-                            r
-                        } else {
-                            return Err(anyhow!(s.to_owned()).context("unsupported match"));
-                        }
-                    }
-                };
-                Yaml::String(s)
+                Yaml::Sequence(scratch)
             }
         }
         _ => value.clone(),
@@ -310,6 +667,169 @@ fn parse_identifier(value: &Yaml, modifiers: &HashSet<String>) -> Result<Yaml> {
     Ok(v)
 }
 
+// Converts a field and its value into the mappings that must all match, the modifiers that tau has
+// no primitive for are converted into virtual fields that are resolved when hunting.
+fn parse_field(
+    field: &str,
+    value: &Yaml,
+    modifiers: &HashSet<String>,
+    config: &Config,
+) -> Result<Vec<Mapping>> {
+    let mapping = |k: String, v: Yaml| {
+        let mut mapping = Mapping::new();
+        mapping.insert(k.into(), v);
+        mapping
+    };
+    let values = match value {
+        Yaml::Sequence(s) => s.iter().collect::<Vec<_>>(),
+        v => vec![v],
+    };
+    if modifiers.contains("cidr") {
+        let mut cidrs = vec![];
+        for value in values {
+            match value.as_str().map(Cidr::from_str) {
+                Some(Ok(_)) => cidrs.push(value.as_str().expect("could not get cidr")),
+                _ => return Err(anyhow!("{:?}", value).context("unsupported cidr")),
+            }
+        }
+        let key = format!("{}{}|{}", CIDR, field, cidrs.join(","));
+        return Ok(vec![mapping(key, "true".into())]);
+    }
+    if modifiers.contains("fieldref") {
+        let mut fields = vec![];
+        for value in values {
+            match value.as_str() {
+                Some(f) if !f.is_empty() && !f.contains(',') => fields.push(f),
+                _ => return Err(anyhow!("{:?}", value).context("unsupported fieldref")),
+            }
+        }
+        let key = format!("{}{}|{}", FIELDREF, field, fields.join(","));
+        return Ok(vec![mapping(key, "true".into())]);
+    }
+    if modifiers.contains("exists") {
+        let exists = match value.as_bool() {
+            Some(exists) => exists,
+            None => return Err(anyhow!("{:?}", value).context("unsupported exists")),
+        };
+        let key = format!("{}{}", EXISTS, field);
+        return Ok(vec![mapping(key, exists.to_string().into())]);
+    }
+    for (modifier, symbol) in [("gt", ">"), ("gte", ">="), ("lt", "<"), ("lte", "<=")] {
+        if modifiers.contains(modifier) {
+            let mut scratch = vec![];
+            let mut float = false;
+            for value in &values {
+                let number = match value {
+                    Yaml::Number(n) => Some(n.to_string()),
+                    Yaml::String(s) => Some(s.trim().to_owned()),
+                    _ => None,
+                };
+                // NOTE: Integers are compared as such, so that large values do not lose precision,
+                // while a single float means that they are all compared as floats.
+                let number = match number {
+                    Some(n) if n.parse::<i64>().is_ok() => n,
+                    Some(n) if n.parse::<f64>().map(f64::is_finite).unwrap_or_default() => {
+                        float = true;
+                        n
+                    }
+                    _ => return Err(anyhow!("{:?}", value).context("unsupported comparison")),
+                };
+                scratch.push(number);
+            }
+            let mut scratch = scratch
+                .into_iter()
+                .map(|n| match float {
+                    // NOTE: Debug always writes a float with a fraction or exponent, so that Tau
+                    // does not parse it as an integer.
+                    true => format!("{:?}", n.parse::<f64>().expect("could not parse float")),
+                    false => n,
+                })
+                .map(|n| Yaml::String(format!("{}{}", symbol, n)))
+                .collect::<Vec<_>>();
+            let value = if scratch.len() == 1 {
+                scratch.remove(0)
+            } else {
+                Yaml::Sequence(scratch)
+            };
+            let cast = if float { "flt" } else { "int" };
+            return Ok(vec![mapping(format!("{}({})", cast, field), value)]);
+        }
+    }
+    // NOTE: Each value gets its own mapping so that a value which expands into variants only
+    // needs one of them to match.
+    if modifiers.contains("all") && value.is_sequence() {
+        return values
+            .into_iter()
+            .map(|v| {
+                let (k, v) = as_glob(field, parse_identifier(v, modifiers, config)?)?;
+                Ok(mapping(k, v))
+            })
+            .collect();
+    }
+    let (k, v) = as_glob(field, parse_identifier(value, modifiers, config)?)?;
+    Ok(vec![mapping(k, v)])
+}
+
+/// Converts a Sigma field, which can carry modifiers, and its value into the mappings that must
+/// all match. This is used for the conditions that processing pipelines add to rules.
+pub(crate) fn parse_condition(field: &str, value: &Yaml) -> Result<Vec<Mapping>> {
+    let mut it = field.split('|');
+    let field = it.next().expect("could not get field");
+    let modifiers: HashSet<String> = it.map(|s| s.to_string()).collect();
+    check_modifiers(&modifiers)?;
+    parse_field(field, value, &modifiers, &Config::default())
+}
+
+fn parse_string(s: String, modifiers: &HashSet<String>) -> Result<String> {
+    let contains = modifiers.contains("contains");
+    let leading = contains || modifiers.contains("endswith");
+    let trailing = contains || modifiers.contains("startswith");
+    if modifiers.contains("cased") {
+        return Ok(s.as_cased(leading, trailing));
+    }
+    // NOTE: Changing the case of encoded text changes what it decodes to, so encoded values are
+    // matched case-sensitively. Base64 has no wildcards, but a value starting with an `i` would be
+    // taken as the insensitive prefix by tau, so those are matched as a regex instead.
+    if modifiers.contains("base64") || modifiers.contains("base64offset") {
+        if !leading && s.starts_with('i') {
+            return Ok(s.as_cased(leading, trailing));
+        }
+        return Ok(format!(
+            "{}{}{}",
+            if leading { "*" } else { "" },
+            s,
+            if trailing { "*" } else { "" }
+        ));
+    }
+    let s = if modifiers.contains("contains") {
+        s.as_contains()
+    } else if modifiers.contains("endswith") {
+        s.as_endswith()
+    } else if modifiers.contains("re") {
+        let flags = REGEX_FLAGS
+            .iter()
+            .filter(|f| modifiers.contains(**f))
+            .copied()
+            .collect::<String>();
+        let s = if flags.is_empty() {
+            s
+        } else {
+            format!("(?{}){}", flags, s)
+        };
+        match s.as_regex() {
+            Some(r) => r,
+            None => {
+                return Err(anyhow!(s).context("unsupported regex"));
+            }
+        }
+    } else if modifiers.contains("startswith") {
+        s.as_startswith()
+    } else {
+        s.as_match()
+    };
+    Ok(s)
+}
+
 fn prepare_condition(condition: &str) -> Result<(String, Option<Aggregate>)> {
     if condition.contains(" | ") {
         let (condition, agg) = condition
@@ -452,7 +972,7 @@ fn prepare(
     Ok((detection, aggregate))
 }
 
-fn detections_to_tau(detection: Detection) -> Result<Mapping> {
+fn detections_to_tau(detection: Detection, config: &Config) -> Result<Mapping> {
     let mut tau = Mapping::new();
     let mut det = Mapping::new();
 
@@ -497,20 +1017,18 @@ fn detections_to_tau(detection: Detection) -> Result<Mapping> {
                             None => bail!("[!] keys must strings"),
                         };
                         let mut it = f.split('|');
-                        let mut f = it.next().expect("could not get field").to_string();
+                        let f = it.next().expect("could not get field").to_string();
                         if seen.contains(&f) {
                             collect = false;
                         }
                         seen.insert(f.clone());
                         let modifiers: HashSet<String> = it.map(|s| s.to_string()).collect();
-                        if modifiers.contains("all") {
-                            f = format!("all({})", f);
+                        check_modifiers(&modifiers)?;
+                        let parsed = parse_field(&f, v, &modifiers, config)?;
+                        if parsed.len() > 1 {
+                            collect = false;
                         }
-                        let v = parse_identifier(v, &modifiers)?;
-                        let f = f.into();
-                        let mut map = Mapping::new();
-                        map.insert(f, v);
-                        maps.push(map);
+                        maps.extend(parsed);
                     }
                     if collect {
                         let mut m = Mapping::new();
@@ -555,20 +1073,18 @@ fn detections_to_tau(detection: Detection) -> Result<Mapping> {
                         None => bail!("[!] keys must strings"),
                     };
                     let mut it = f.split('|');
-                    let mut f = it.next().expect("could not get field").to_string();
+                    let f = it.next().expect("could not get field").to_string();
                     if seen.contains(&f) {
                         collect = false;
                     }
                     seen.insert(f.clone());
                     let modifiers: HashSet<String> = it.map(|s| s.to_string()).collect();
-                    if modifiers.contains("all") {
-                        f = format!("all({})", f);
+                    check_modifiers(&modifiers)?;
+                    let parsed = parse_field(&f, &v, &modifiers, config)?;
+                    if parsed.len() > 1 {
+                        collect = false;
                     }
-                    let v = parse_identifier(&v, &modifiers)?;
-                    let f = f.into();
-                    let mut map = Mapping::new();
-                    map.insert(f, v);
-                    maps.push(map);
+                    maps.extend(parsed);
                 }
                 if collect {
                     let mut m = Mapping::new();
@@ -712,101 +1228,293 @@ fn detections_to_tau(detection: Detection) -> Result<Mapping> {
     Ok(tau)
 }
 
-pub fn load(rule: &Path) -> Result<Vec<Yaml>> {
-    let regex = Regex::new(r"---\s*\n").expect("invalid regex");
-    let mut file = File::open(rule)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+/// A document in a Sigma file that was not loaded as a rule.
+#[derive(Debug)]
+pub struct Discarded {
+    /// The position of the document in the file, starting from 1.
+    pub document: usize,
+    pub reason: String,
+}
 
-    let mut sigma: Vec<Sigma> = regex
-        .split(&contents)
-        .filter_map(|p| {
-            if !p.is_empty() {
-                serde_yaml::from_str::<Sigma>(p).ok()
-            } else {
-                None
+impl fmt::Display for Discarded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "document {} was discarded - {}",
+            self.document, self.reason
+        )
+    }
+}
+
+/// A Sigma filter rule, which adds exclusions to the rules that it references so that they can be
+/// tuned without being edited.
+#[derive(Clone, Debug)]
+pub struct MetaFilter {
+    pub name: String,
+    pub detection: TauDetection,
+    pub logsource: Option<LogSource>,
+    pub rules: Vec<String>,
+}
+
+impl MetaFilter {
+    /// Whether the filter references the rule, by either its id or title.
+    pub fn references(&self, rule: &Rule) -> bool {
+        self.rules
+            .iter()
+            .any(|r| rule.id.as_ref() == Some(r) || &rule.name == r)
+    }
+
+    // NOTE: A filter only applies to the rules that it references, and only when the rule has the
+    // parts of the logsource that the filter sets.
+    fn applies(&self, rule: &Rule) -> bool {
+        if !self.references(rule) {
+            return false;
+        }
+        let filter = match &self.logsource {
+            Some(filter) => filter,
+            None => return true,
+        };
+        let logsource = rule.logsource.as_ref();
+        within(
+            filter.parts(),
+            [
+                logsource
+                    .and_then(|l| l.category.as_deref())
+                    .map(|v| vec![v]),
+                logsource
+                    .and_then(|l| l.product.as_deref())
+                    .map(|v| vec![v]),
+                logsource
+                    .and_then(|l| l.service.as_deref())
+                    .map(|v| vec![v]),
+            ],
+        )
+    }
+}
+
+/// Adds the conditions of the filters that apply to a rule, the rule then only matches when both
+/// its own condition and those of its filters are true.
+pub(crate) fn apply_filters(mut rule: Rule, filters: &[MetaFilter]) -> Rule {
+    for filter in filters.iter().filter(|f| f.applies(&rule)) {
+        // NOTE: The filter's identifiers are inlined so that they cannot clash with the rule's.
+        let expression = optimiser::coalesce(
+            filter.detection.expression.clone(),
+            &filter.detection.identifiers,
+        );
+        let detection = std::mem::replace(&mut rule.tau.detection.expression, Expression::Null);
+        rule.tau.detection.expression =
+            Expression::BooleanExpression(Box::new(detection), BoolSym::And, Box::new(expression));
+    }
+    rule
+}
+
+#[derive(Deserialize)]
+struct FilterRule {
+    pub title: String,
+    pub filter: FilterDetection,
+    #[serde(default)]
+    pub logsource: Option<LogSource>,
+}
+
+#[derive(Deserialize)]
+struct FilterDetection {
+    pub rules: Vec<String>,
+    #[serde(flatten)]
+    pub detection: Detection,
+}
+
+/// The rules loaded from a Sigma file, along with any documents that could not be loaded.
+#[derive(Debug, Default)]
+pub struct Collection {
+    pub rules: Vec<Yaml>,
+    pub filters: Vec<MetaFilter>,
+    pub discarded: Vec<Discarded>,
+}
+
+impl Collection {
+    fn discard(&mut self, document: usize, reason: String) {
+        self.discarded.push(Discarded { document, reason });
+    }
+}
+
+// Merges a document onto another as the Sigma specification describes, mappings are merged while
+// any other value is replaced.
+fn merge(base: Yaml, extension: Yaml) -> Yaml {
+    match (base, extension) {
+        (Yaml::Mapping(mut base), Yaml::Mapping(extension)) => {
+            for (k, v) in extension {
+                match base.get_mut(&k) {
+                    Some(b) => *b = merge(std::mem::take(b), v),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
             }
-        })
-        .collect();
+            Yaml::Mapping(base)
+        }
+        // NOTE: Not part of the specification, but a mapping merged onto a list of mappings is
+        // merged into each of them so that every alternative of an identifier is extended.
+        (Yaml::Sequence(base), Yaml::Mapping(extension)) if base.iter().all(|b| b.is_mapping()) => {
+            Yaml::Sequence(
+                base.into_iter()
+                    .map(|b| merge(b, Yaml::Mapping(extension.clone())))
+                    .collect(),
+            )
+        }
+        (_, extension) => extension,
+    }
+}
+
+enum Converted {
+    Discarded(String),
+    Filter(MetaFilter),
+    Rule(Yaml),
+}
 
-    // Silently error if we found no sigma rules.
-    if sigma.is_empty() {
-        return Ok(vec![]);
+fn convert_filter(document: Yaml, config: &Config) -> Result<Converted> {
+    let rule = match serde_yaml::from_value::<FilterRule>(document) {
+        Ok(rule) => rule,
+        Err(e) => return Ok(Converted::Discarded(e.to_string())),
+    };
+    let (detection, agg) = prepare(rule.filter.detection, None)?;
+    if agg.is_some() {
+        return Ok(Converted::Discarded(
+            "filters cannot use aggregations".to_owned(),
+        ));
     }
+    let mut tau = detections_to_tau(detection, config)?;
+    let detection = match tau.remove("detection") {
+        Some(detection) => serde_yaml::from_value(detection)?,
+        None => bail!("failed to convert filter"),
+    };
+    Ok(Converted::Filter(MetaFilter {
+        name: rule.title,
+        detection,
+        logsource: rule.logsource,
+        rules: rule.filter.rules,
+    }))
+}
 
-    let main = sigma.remove(0);
-    let base = match main.as_base() {
+// Converts a merged document into a rule or filter, a document that is neither is returned with the
+// reason why it is not, while a rule that cannot be converted is an error.
+fn convert(document: Yaml, config: &Config) -> Result<Converted> {
+    if document.get("filter").is_some() && document.get("detection").is_none() {
+        return convert_filter(document, config);
+    }
+    let sigma = match serde_yaml::from_value::<Sigma>(document.clone()) {
+        Ok(sigma) => sigma,
+        Err(e) => return Ok(Converted::Discarded(e.to_string())),
+    };
+    // NOTE: The header is flattened, so we deserialise it again to find out why it is missing.
+    let mut rule = match sigma.as_base() {
         Some(base) => base,
-        None => bail!("failed to parse sigma rule"),
+        None => {
+            let reason = match serde_yaml::from_value::<Header>(document) {
+                Ok(_) => "invalid header".to_owned(),
+                Err(e) => e.to_string(),
+            };
+            return Ok(Converted::Discarded(reason));
+        }
+    };
+    let detection = match sigma.detection.clone() {
+        Some(detection) => detection,
+        None => return Ok(Converted::Discarded("missing detection".to_owned())),
     };
+    let (detection, agg) = prepare(detection, None)?;
+    let tau = detections_to_tau(detection, config)?;
+    if let Some(level) = &sigma.level {
+        let level = match level.as_str() {
+            "critical" | "high" | "medium" | "low" => level.to_owned(),
+            _ => "info".to_owned(),
+        };
+        rule.insert("level".into(), level.into());
+    } else {
+        rule.insert("level".into(), "info".into());
+    }
+    for (k, v) in tau {
+        rule.insert(k, v);
+    }
+    for (k, v) in sigma.samples() {
+        rule.insert(k, v);
+    }
+    if let Some(agg) = agg.and_then(|a| serde_yaml::to_value(a).ok()) {
+        rule.insert(Yaml::String("aggregate".to_owned()), agg);
+    }
+    Ok(Converted::Rule(rule.into()))
+}
 
-    let mut rules = vec![];
+pub fn load(rule: &Path, config: &Config) -> Result<Collection> {
+    let regex = Regex::new(r"---\s*\n").expect("invalid regex");
+    let mut file = File::open(rule)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
 
     // Sigma has this annoying feature called Rule Collections which makes parsing a PITA at the
     // cost of slightly better maintainability. I am not a fan but we have to handle it as best as
     // possible.
     // https://github.com/SigmaHQ/sigma/wiki/Specification#rule-collections
-    // TODO: This is a minimal implementation which supports most of the styles found in the
-    // Windows rules. We can do a more complete one when required.
-    let mut single = false;
-    if main.header.and_then(|m| m.action).is_some() {
-        for sigma in sigma.into_iter() {
-            if let Some(extension) = sigma.detection {
-                let (detection, agg) = match &main.detection {
-                    Some(d) => prepare(d.clone(), Some(extension)),
-                    None => prepare(extension, None),
-                }?;
-                let tau = detections_to_tau(detection)?;
-                let mut rule = base.clone();
-                if let Some(level) = &main.level {
-                    let level = match level.as_str() {
-                        "critical" | "high" | "medium" | "low" => level.to_owned(),
-                        _ => "info".to_owned(),
-                    };
-                    rule.insert("level".into(), level.into());
-                } else {
-                    rule.insert("level".into(), "info".into());
-                }
-                for (k, v) in tau {
-                    rule.insert(k, v);
-                }
-                if let Some(agg) = agg.and_then(|a| serde_yaml::to_value(a).ok()) {
-                    rule.insert(Yaml::String("aggregate".to_owned()), agg);
-                }
-                rules.push(rule.into());
-            } else {
-                single = true;
+    // A `global` document is merged into every document that follows it until a `reset`, while
+    // a `repeat` document is merged onto the previous rule.
+    let mut collection = Collection::default();
+    let mut global: Option<Yaml> = None;
+    let mut previous: Option<Yaml> = None;
+    let documents = regex.split(&contents).filter(|p| !p.trim().is_empty());
+    for (index, document) in documents.enumerate() {
+        let index = index + 1;
+        let mut document = match serde_yaml::from_str::<Yaml>(document) {
+            Ok(Yaml::Mapping(m)) => m,
+            Ok(Yaml::Null) => continue,
+            Ok(_) => {
+                collection.discard(index, "document is not a mapping".to_owned());
+                continue;
             }
-        }
-    } else {
-        single = true;
-    }
-
-    if single {
-        let mut rule = base;
-        if let Some(detection) = main.detection {
-            let (detection, agg) = prepare(detection, None)?;
-            let tau = detections_to_tau(detection)?;
-            if let Some(level) = &main.level {
-                let level = match level.as_str() {
-                    "critical" | "high" | "medium" | "low" => level.to_owned(),
-                    _ => "info".to_owned(),
-                };
-                rule.insert("level".into(), level.into());
-            } else {
-                rule.insert("level".into(), "info".into());
+            Err(e) => {
+                collection.discard(index, e.to_string());
+                continue;
             }
-            for (k, v) in tau {
-                rule.insert(k, v);
+        };
+        let action = document.remove("action");
+        let document = match action.as_ref().map(|a| a.as_str()) {
+            None => match &global {
+                Some(global) => merge(global.clone(), Yaml::Mapping(document)),
+                None => Yaml::Mapping(document),
+            },
+            Some(Some("global")) => {
+                global = Some(match global.take() {
+                    Some(global) => merge(global, Yaml::Mapping(document)),
+                    None => Yaml::Mapping(document),
+                });
+                continue;
+            }
+            Some(Some("reset")) => {
+                global = None;
+                continue;
             }
-            if let Some(agg) = agg.and_then(|a| serde_yaml::to_value(a).ok()) {
-                rule.insert(Yaml::String("aggregate".to_owned()), agg);
+            Some(Some("repeat")) => match &previous {
+                Some(previous) => merge(previous.clone(), Yaml::Mapping(document)),
+                None => {
+                    collection.discard(index, "repeat action without a previous rule".to_owned());
+                    continue;
+                }
+            },
+            Some(Some(action)) => {
+                collection.discard(index, format!("unsupported action - {}", action));
+                continue;
             }
-            rules.push(rule.into());
+            Some(None) => {
+                collection.discard(index, "action must be a string".to_owned());
+                continue;
+            }
+        };
+        previous = Some(document.clone());
+        match convert(document, config)? {
+            Converted::Discarded(reason) => collection.discard(index, reason),
+            Converted::Filter(filter) => collection.filters.push(filter),
+            Converted::Rule(rule) => collection.rules.push(rule),
         }
     }
 
-    Ok(rules)
+    Ok(collection)
 }
 
 #[cfg(test)]
@@ -840,28 +1548,106 @@ mod tests {
     #[test]
     fn test_match() {
         let x = "foobar".to_owned();
-        assert_eq!(x.as_match().unwrap(), "ifoobar");
+        assert_eq!(x.as_match(), "ifoobar");
 
         let x = "*foobar".to_owned();
-        assert_eq!(x.as_match().unwrap(), "i*foobar");
+        assert_eq!(x.as_match(), "i*foobar");
 
         let x = "foobar*".to_owned();
-        assert_eq!(x.as_match().unwrap(), "ifoobar*");
+        assert_eq!(x.as_match(), "ifoobar*");
 
         let x = "*foobar*".to_owned();
-        assert_eq!(x.as_match().unwrap(), "i*foobar*");
+        assert_eq!(x.as_match(), "i*foobar*");
 
-        // NOTE: These are none as we need to write regex to support them...
         let x = "foo*bar".to_owned();
-        assert_eq!(x.as_match().is_none(), true);
+        assert_eq!(x.as_match(), "@glob:foo*bar");
         let x = "foo?bar".to_owned();
-        assert_eq!(x.as_match().is_none(), true);
+        assert_eq!(x.as_match(), "@glob:foo?bar");
+    }
+
+    #[test]
+    fn test_match_escaped() {
+        let x = "foo\\*".to_owned();
+        assert_eq!(x.as_match(), "@glob:foo\\*");
+
+        let x = "C:\\Windows\\\\*".to_owned();
+        assert_eq!(x.as_match(), "iC:\\Windows\\*");
+
+        let x = "C:\\Windows\\*".to_owned();
+        assert_eq!(x.as_match(), "@glob:C:\\\\Windows\\*");
+
+        let x = "C:\\Users\\\\*\\AppData".to_owned();
+        assert_eq!(x.as_match(), "@glob:C:\\\\Users\\\\*\\\\AppData");
+
+        let x = "a|b?".to_owned();
+        assert_eq!(x.as_contains(), "@glob:*a\\|b?*");
+    }
+
+    #[test]
+    fn test_match_nested() {
+        let x = "foo*bar".to_owned();
+        assert_eq!(x.as_contains(), "@glob:*foo*bar*");
+        assert_eq!(x.as_endswith(), "@glob:*foo*bar");
+        assert_eq!(x.as_startswith(), "@glob:foo*bar*");
+
+        let x = "*foo*".to_owned();
+        assert_eq!(x.as_contains(), "i*foo*");
     }
 
     #[test]
     fn test_match_regex() {
         let x = "foobar".to_owned();
-        assert_eq!(x.as_regex(false).unwrap(), "?foobar");
+        assert_eq!(x.as_regex().unwrap(), "?foobar");
+    }
+
+    #[test]
+    fn test_match_cased() {
+        let x = "Foo.Bar".to_owned();
+        assert_eq!(x.as_cased(false, false), "?(?s)^Foo\\.Bar$");
+        assert_eq!(x.as_cased(true, true), "?(?s)^.*Foo\\.Bar.*$");
+        assert_eq!(x.as_cased(true, false), "?(?s)^.*Foo\\.Bar$");
+        assert_eq!(x.as_cased(false, true), "?(?s)^Foo\\.Bar.*$");
+
+        let x = "in*B?r\\*".to_owned();
+        assert_eq!(x.as_cased(false, false), "?(?s)^in.*B.r\\*$");
+    }
+
+    #[test]
+    fn test_parse_string_modifiers() {
+        let modifiers = |m: &[&str]| m.iter().map(|m| m.to_string()).collect::<HashSet<_>>();
+
+        let s = parse_string("Foo".to_owned(), &modifiers(&["contains", "cased"])).unwrap();
+        assert_eq!(s, "?(?s)^.*Foo.*$");
+        let s = parse_string("foo".to_owned(), &modifiers(&["re", "i"])).unwrap();
+        assert_eq!(s, "?(?i)foo");
+        let s = parse_string("^foo$".to_owned(), &modifiers(&["re", "m", "s"])).unwrap();
+        assert_eq!(s, "?(?ms)^foo$");
+
+        assert!(check_modifiers(&modifiers(&["contains", "cased"])).is_ok());
+        assert!(check_modifiers(&modifiers(&["re", "i", "m", "s"])).is_ok());
+        assert!(check_modifiers(&modifiers(&["contains", "i"])).is_err());
+        assert!(check_modifiers(&modifiers(&["re", "cased"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_field_glob() {
+        let yaml = Yaml::Sequence(vec![
+            Yaml::String("C:\\Users\\\\*\\AppData".to_owned()),
+            Yaml::String("C:\\Windows\\\\*".to_owned()),
+        ]);
+        let mappings = parse_field("Image", &yaml, &HashSet::new(), &Config::default()).unwrap();
+        let mut mapping = Mapping::new();
+        mapping.insert(
+            "@glob:Image|C:\\\\Users\\\\*\\\\AppData|C:\\\\Windows\\\\*".into(),
+            "true".into(),
+        );
+        assert_eq!(mappings, vec![mapping]);
+
+        let yaml = Yaml::String("C:\\Windows\\\\*".to_owned());
+        let mappings = parse_field("Image", &yaml, &HashSet::new(), &Config::default()).unwrap();
+        let mut mapping = Mapping::new();
+        mapping.insert("Image".into(), "iC:\\Windows\\*".into());
+        assert_eq!(mappings, vec![mapping]);
     }
 
     #[test]
@@ -897,10 +1683,252 @@ mod tests {
             string: abcd
         "#;
         let yaml: serde_yaml::Value = serde_yaml::from_str(&rule).unwrap();
-        let yaml = parse_identifier(&yaml, &HashSet::new()).unwrap();
+        let yaml = parse_identifier(&yaml, &HashSet::new(), &Config::default()).unwrap();
+        assert_eq!(yaml, expected);
+    }
+
+    #[test]
+    fn test_parse_identifier_base64() {
+        let modifiers = HashSet::from(["base64".to_owned(), "contains".to_owned()]);
+        let yaml = Yaml::String("ping".to_owned());
+        let yaml = parse_identifier(&yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(yaml, Yaml::String("*cGluZw==*".to_owned()));
+
+        let modifiers = HashSet::from(["base64".to_owned(), "wide".to_owned()]);
+        let yaml = Yaml::String("\u{88}".to_owned());
+        let yaml = parse_identifier(&yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(yaml, Yaml::String("?(?s)^iAA=$".to_owned()));
+    }
+
+    #[test]
+    fn test_parse_identifier_base64offset() {
+        let expected = r#"
+            - "*aHR0cDovL*"
+            - "*h0dHA6Ly*"
+            - "*odHRwOi8v*"
+            - "*ZnRwOi8v*"
+            - "*Z0cDovL*"
+            - "*mdHA6Ly*"
+        "#;
+        let expected: serde_yaml::Value = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from(["base64offset".to_owned(), "contains".to_owned()]);
+        let yaml = r#"
+            - http://
+            - ftp://
+        "#;
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let yaml = parse_identifier(&yaml, &modifiers, &Config::default()).unwrap();
         assert_eq!(yaml, expected);
     }
 
+    #[test]
+    fn test_parse_identifier_wide() {
+        let expected = r#"
+            - "*cABpAG4AZw*"
+            - "*AAaQBuAGcA*"
+            - "*wAGkAbgBnA*"
+        "#;
+        let expected: serde_yaml::Value = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from([
+            "wide".to_owned(),
+            "base64offset".to_owned(),
+            "contains".to_owned(),
+        ]);
+        let yaml = Yaml::String("ping".to_owned());
+        let yaml = parse_identifier(&yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(yaml, expected);
+
+        let modifiers = HashSet::from(["utf16be".to_owned()]);
+        let yaml = Yaml::String("ab".to_owned());
+        let yaml = parse_identifier(&yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(yaml, Yaml::String("i\0a\0b".to_owned()));
+    }
+
+    #[test]
+    fn test_unsupported_modifiers() {
+        let modifiers = HashSet::from(["contains".to_owned(), "foobar".to_owned()]);
+        assert_eq!(check_modifiers(&modifiers).is_err(), true);
+
+        let modifiers = HashSet::from(["cidr".to_owned(), "contains".to_owned()]);
+        assert_eq!(check_modifiers(&modifiers).is_err(), true);
+
+        let modifiers = HashSet::from(["all".to_owned(), "base64offset".to_owned()]);
+        assert_eq!(check_modifiers(&modifiers).is_err(), false);
+
+        let modifiers = HashSet::from(["wide".to_owned(), "base64offset".to_owned()]);
+        assert_eq!(check_modifiers(&modifiers).is_err(), false);
+    }
+
+    #[test]
+    fn test_parse_identifier_windash() {
+        let expected = r#"
+            - i* -enc*
+            - i* /enc*
+            - "i* \u2013enc*"
+            - "i* \u2014enc*"
+            - "i* \u2015enc*"
+        "#;
+        let expected: serde_yaml::Value = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from(["windash".to_owned(), "contains".to_owned()]);
+        let yaml = Yaml::String(" -enc".to_owned());
+        let yaml = parse_identifier(&yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(yaml, expected);
+
+        let modifiers = HashSet::from(["windash".to_owned()]);
+        let yaml = Yaml::String("x-ray".to_owned());
+        let yaml = parse_identifier(&yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(yaml, Yaml::String("ix-ray".to_owned()));
+    }
+
+    #[test]
+    fn test_parse_identifier_expand() {
+        let expected = r#"
+            - iC:\Users\admin\*
+            - iC:\Users\root\*
+        "#;
+        let expected: serde_yaml::Value = serde_yaml::from_str(&expected).unwrap();
+
+        let config = Config {
+            placeholders: HashMap::from([(
+                "Administrators".to_owned(),
+                vec!["admin".to_owned(), "root".to_owned()],
+            )]),
+            ..Default::default()
+        };
+        let modifiers = HashSet::from(["expand".to_owned(), "startswith".to_owned()]);
+        let yaml = Yaml::String("C:\\Users\\%Administrators%\\".to_owned());
+        let yaml = parse_identifier(&yaml, &modifiers, &config).unwrap();
+        assert_eq!(yaml, expected);
+
+        let yaml = Yaml::String("%DomainControllers%".to_owned());
+        let error = parse_identifier(&yaml, &modifiers, &config).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Unresolved>().unwrap().0,
+            "DomainControllers"
+        );
+
+        // NOTE: Placeholders are only resolved when the value is expanded.
+        let modifiers = HashSet::new();
+        let yaml = Yaml::String("%APPDATA%".to_owned());
+        let yaml = parse_identifier(&yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(yaml, Yaml::String("i%APPDATA%".to_owned()));
+    }
+
+    #[test]
+    fn test_parse_field_all() {
+        let expected = r#"
+            - CommandLine:
+                - i*-a*
+                - i*/a*
+                - "i*\u2013a*"
+                - "i*\u2014a*"
+                - "i*\u2015a*"
+            - CommandLine: i*b*
+        "#;
+        let expected: Vec<Mapping> = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from([
+            "all".to_owned(),
+            "contains".to_owned(),
+            "windash".to_owned(),
+        ]);
+        let yaml = r#"
+            - -a
+            - b
+        "#;
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let maps = parse_field("CommandLine", &yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(maps, expected);
+    }
+
+    #[test]
+    fn test_parse_field_cidr() {
+        let expected = r#"
+            - "@cidr:DestinationIp|10.0.0.0/8,fd00::/8": "true"
+        "#;
+        let expected: Vec<Mapping> = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from(["cidr".to_owned()]);
+        let yaml = r#"
+            - 10.0.0.0/8
+            - fd00::/8
+        "#;
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let maps = parse_field("DestinationIp", &yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(maps, expected);
+
+        let yaml = Yaml::String("10.0.0.0/33".to_owned());
+        assert_eq!(
+            parse_field("DestinationIp", &yaml, &modifiers, &Config::default()).is_err(),
+            true
+        );
+    }
+
+    #[test]
+    fn test_parse_field_comparison() {
+        let expected = r#"
+            - int(EventID): ">=4624"
+        "#;
+        let expected: Vec<Mapping> = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from(["gte".to_owned()]);
+        let yaml: serde_yaml::Value = serde_yaml::from_str("4624").unwrap();
+        let maps = parse_field("EventID", &yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(maps, expected);
+
+        let yaml = Yaml::String("abc".to_owned());
+        assert_eq!(
+            parse_field("EventID", &yaml, &modifiers, &Config::default()).is_err(),
+            true
+        );
+
+        let expected = r#"
+            - flt(Score):
+                - "<0.5"
+                - "<2.0"
+        "#;
+        let expected: Vec<Mapping> = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from(["lt".to_owned()]);
+        let yaml: serde_yaml::Value = serde_yaml::from_str("[0.5, 2]").unwrap();
+        let maps = parse_field("Score", &yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(maps, expected);
+    }
+
+    #[test]
+    fn test_parse_field_fieldref() {
+        let expected = r#"
+            - "@fieldref:SubjectUserName|TargetUserName": "true"
+        "#;
+        let expected: Vec<Mapping> = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from(["fieldref".to_owned()]);
+        let yaml = Yaml::String("TargetUserName".to_owned());
+        let maps = parse_field("SubjectUserName", &yaml, &modifiers, &Config::default()).unwrap();
+        assert_eq!(maps, expected);
+    }
+
+    #[test]
+    fn test_parse_field_exists() {
+        let expected = r#"
+            - "@exists:CommandLine": "false"
+        "#;
+        let expected: Vec<Mapping> = serde_yaml::from_str(&expected).unwrap();
+
+        let modifiers = HashSet::from(["exists".to_owned()]);
+        let maps = parse_field(
+            "CommandLine",
+            &Yaml::Bool(false),
+            &modifiers,
+            &Config::default(),
+        )
+        .unwrap();
+        assert_eq!(maps, expected);
+    }
+
     #[test]
     fn test_prepare() {
         let expected = r#"
@@ -1006,7 +2034,7 @@ mod tests {
             condition: A and B and C
         "#;
         let detection: Detection = serde_yaml::from_str(&detection).unwrap();
-        let detection = detections_to_tau(detection).unwrap();
+        let detection = detections_to_tau(detection, &Config::default()).unwrap();
         assert_eq!(detection, *expected.as_mapping().unwrap());
     }
 
@@ -1033,7 +2061,7 @@ mod tests {
         "#;
 
         let detection: Detection = serde_yaml::from_str(&detection).unwrap();
-        let detection = detections_to_tau(detection).unwrap();
+        let detection = detections_to_tau(detection, &Config::default()).unwrap();
         assert_eq!(detection, *expected.as_mapping().unwrap());
     }
 
@@ -1060,7 +2088,7 @@ mod tests {
         "#;
 
         let detection: Detection = serde_yaml::from_str(&detection).unwrap();
-        let detection = detections_to_tau(detection).unwrap();
+        let detection = detections_to_tau(detection, &Config::default()).unwrap();
         assert_eq!(detection, *expected.as_mapping().unwrap());
     }
 
@@ -1091,7 +2119,7 @@ mod tests {
         "#;
 
         let detection: Detection = serde_yaml::from_str(&detection).unwrap();
-        let detection = detections_to_tau(detection).unwrap();
+        let detection = detections_to_tau(detection, &Config::default()).unwrap();
         assert_eq!(detection, *expected.as_mapping().unwrap());
     }
 
@@ -1122,7 +2150,53 @@ mod tests {
         "#;
 
         let detection: Detection = serde_yaml::from_str(&detection).unwrap();
-        let detection = detections_to_tau(detection).unwrap();
+        let detection = detections_to_tau(detection, &Config::default()).unwrap();
         assert_eq!(detection, *expected.as_mapping().unwrap());
     }
+
+    #[test]
+    fn test_samples() {
+        let expected = r#"
+            true_negatives:
+                - string: efgh
+            true_positives:
+                - string: abcd
+        "#;
+        let expected: serde_yaml::Value = serde_yaml::from_str(&expected).unwrap();
+
+        let sigma = r#"
+            title: test
+            description: test
+            detection:
+                A:
+                    string: abcd
+                condition: A
+            true_positives:
+                - string: abcd
+            true_negatives:
+                - string: efgh
+        "#;
+        let sigma: Sigma = serde_yaml::from_str(&sigma).unwrap();
+        assert_eq!(sigma.samples(), *expected.as_mapping().unwrap());
+    }
+
+    #[test]
+    fn test_logsource_within() {
+        let provided = [
+            Some(vec!["process_creation"]),
+            Some(vec!["windows"]),
+            Some(vec!["sysmon", "security"]),
+        ];
+        assert!(within([None, None, None], provided.clone()));
+        assert!(within(
+            [Some("Process_Creation"), Some("windows"), None],
+            provided.clone()
+        ));
+        assert!(within([None, None, Some("Security")], provided.clone()));
+        assert!(!within([None, None, Some("system")], provided));
+        assert!(!within(
+            [Some("process_creation"), None, None],
+            [None, None, None]
+        ));
+    }
 }
@@ -0,0 +1,130 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use boreal::{Compiler, MetadataValue, Scanner as Boreal};
+use serde::Serialize;
+
+use crate::rule::{Level, Status};
+
+/// The fields that are scanned when none are provided, these are the Sigma field names for
+/// PowerShell script blocks and process command lines.
+pub const DEFAULT_FIELDS: &[&str] = &["CommandLine", "ParentCommandLine", "ScriptBlockText"];
+
+// NOTE: YARA rules do not have a standard way to express severity, so it is read from the `level`
+// and `status` metadata when present. As a match is a strong signal we otherwise treat them as high
+// and stable.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub tags: Vec<String>,
+
+    pub authors: Vec<String>,
+    pub level: Level,
+    pub status: Status,
+}
+
+/// A string from a YARA rule that matched the value of a field.
+#[derive(Clone, Debug, Serialize)]
+pub struct Match {
+    pub field: String,
+    pub identifier: String,
+    pub offset: usize,
+    pub data: String,
+}
+
+/// The YARA rules compiled from a collection of files, each file is loaded into its own namespace
+/// so that rule names only need to be unique within a file.
+pub struct Scanner {
+    scanner: Boreal,
+}
+
+impl Scanner {
+    pub fn load(paths: &[impl AsRef<Path>]) -> crate::Result<Self> {
+        let mut compiler = Compiler::new();
+        for path in paths {
+            let path = path.as_ref();
+            let namespace = path.display().to_string();
+            if let Err(e) = compiler.add_rules_file_in_namespace(path, &namespace) {
+                anyhow::bail!("could not compile '{}' - {}", path.display(), e);
+            }
+        }
+        Ok(Self {
+            scanner: compiler.into_scanner(),
+        })
+    }
+
+    pub fn rules(&self) -> crate::Result<Vec<Rule>> {
+        let mut rules = vec![];
+        for rule in self.scanner.rules().filter(|r| !r.is_private) {
+            let mut authors = vec![];
+            let mut level = Level::High;
+            let mut status = Status::Stable;
+            for metadata in rule.metadatas {
+                let value = match &metadata.value {
+                    MetadataValue::Bytes(b) => {
+                        String::from_utf8_lossy(self.scanner.get_bytes_symbol(*b)).into_owned()
+                    }
+                    _ => continue,
+                };
+                let result = match self.scanner.get_string_symbol(metadata.name) {
+                    "author" | "authors" => {
+                        authors.extend(value.split(',').map(|a| a.trim().to_owned()));
+                        Ok(())
+                    }
+                    "level" => Level::from_str(&value.to_lowercase()).map(|l| level = l),
+                    "status" => Status::from_str(&value.to_lowercase()).map(|s| status = s),
+                    _ => Ok(()),
+                };
+                if let Err(e) = result {
+                    anyhow::bail!("invalid metadata in rule '{}' - {}", rule.name, e);
+                }
+            }
+            rules.push(Rule {
+                name: rule.name.to_owned(),
+                namespace: rule.namespace.map(|n| n.to_owned()),
+                tags: rule.tags.to_vec(),
+
+                authors,
+                level,
+                status,
+            });
+        }
+        Ok(rules)
+    }
+
+    /// Scans the value, returning the namespace and name of each rule that matched along with
+    /// the strings that caused it to.
+    pub fn scan(&self, field: &str, data: &[u8]) -> Vec<((Option<String>, String), Vec<Match>)> {
+        let result = match self.scanner.scan_mem(data) {
+            Ok(result) => result,
+            // NOTE: A timeout still returns the rules that matched before it was hit.
+            Err((_, result)) => result,
+        };
+        result
+            .matched_rules
+            .into_iter()
+            .map(|r| {
+                let mut matches = vec![];
+                for string in r.matches {
+                    for m in string.matches {
+                        matches.push(Match {
+                            field: field.to_owned(),
+                            identifier: format!("${}", string.name),
+                            offset: m.offset,
+                            data: String::from_utf8_lossy(&m.data).into_owned(),
+                        });
+                    }
+                }
+                (
+                    (r.namespace.map(|n| n.to_owned()), r.name.to_owned()),
+                    matches,
+                )
+            })
+            .collect()
+    }
+}
+
+pub fn lint(path: &Path) -> crate::Result<Vec<Rule>> {
+    Scanner::load(&[path])?.rules()
+}
@@ -11,6 +11,7 @@ use tau_engine::{
 
 use crate::ext;
 use crate::file::{Document, Documents, Reader};
+use crate::timestamp::Format as TimestampFormat;
 
 pub struct Hits<'a> {
     reader: Reader,
@@ -53,15 +54,12 @@ impl<'a> Iterator for Iter<'a> {
                     .timestamp
                     .as_ref()
                     .expect("could not get timestamp");
-                // TODO: Default to RFC 3339
+                let format = &self.searcher.timestamp_format;
                 let result = match &document {
                     Document::Evtx(evtx) => {
                         match crate::evtx::WrapperLegacy(&evtx.data).find(field) {
-                            Some(value) => match value.as_str() {
-                                Some(timestamp) => NaiveDateTime::parse_from_str(
-                                    timestamp,
-                                    "%Y-%m-%dT%H:%M:%S%.6fZ",
-                                ),
+                            Some(value) => match format.parse(&value) {
+                                Some(result) => result,
                                 None => continue,
                             },
                             None => continue,
@@ -71,10 +69,8 @@ impl<'a> Iterator for Iter<'a> {
                     | Document::Json(json)
                     | Document::Xml(json)
                     | Document::Mft(json) => match json.find(field) {
-                        Some(value) => match value.as_str() {
-                            Some(timestamp) => {
-                                NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.6fZ")
-                            }
+                        Some(value) => match format.parse(&value) {
+                            Some(result) => result,
                             None => continue,
                         },
                         None => continue,
@@ -84,10 +80,10 @@ impl<'a> Iterator for Iter<'a> {
                     Ok(t) => t,
                     Err(e) => {
                         if self.searcher.skip_errors {
-                            cs_eyellowln!("failed to parse timestamp - {}", e);
+                            cs_eyellowln!("{}", e);
                             continue;
                         } else {
-                            return Some(Err(anyhow::anyhow!("failed to parse timestamp - {}", e)));
+                            return Some(Err(e));
                         }
                     }
                 };
@@ -186,6 +182,7 @@ pub struct SearcherBuilder {
     skip_errors: Option<bool>,
     tau: Option<Vec<String>>,
     timestamp: Option<String>,
+    timestamp_format: Option<TimestampFormat>,
     timezone: Option<Tz>,
     to: Option<NaiveDateTime>,
 }
@@ -230,6 +227,7 @@ impl SearcherBuilder {
                 skip_errors,
                 tau,
                 timestamp: self.timestamp,
+                timestamp_format: self.timestamp_format.unwrap_or_default(),
                 timezone: self.timezone,
                 to: self.to.map(|d| DateTime::from_utc(d, Utc)),
             },
@@ -276,6 +274,11 @@ impl SearcherBuilder {
         self
     }
 
+    pub fn timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = Some(format);
+        self
+    }
+
     pub fn timezone(mut self, tz: Tz) -> Self {
         self.timezone = Some(tz);
         self
@@ -296,6 +299,7 @@ pub struct SearcherInner {
     skip_errors: bool,
     tau: Option<Expression>,
     timestamp: Option<String>,
+    timestamp_format: TimestampFormat,
     timezone: Option<Tz>,
     to: Option<DateTime<Utc>>,
 }
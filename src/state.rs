@@ -0,0 +1,451 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::file::Kind as FileKind;
+
+// NOTE: The EVTX file header is rewritten as records are added, so the log is identified by its
+// first chunk instead.
+const EVTX_HEADER: u64 = 4096;
+const EVTX_CHUNK: u64 = 65536;
+const EVTX_FIRST_RECORD: u64 = EVTX_HEADER + 24;
+
+// NOTE: Other files are identified by their size and a prefix of their contents, so that large
+// files do not have to be read twice.
+const PREFIX: u64 = 65536;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6f";
+
+/// The documents that contributed to an aggregate in previous runs.
+// NOTE: Only their number and the earliest timestamp are kept, as storing the documents themselves
+// would grow the state with every run.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Saved {
+    pub count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+impl Saved {
+    pub fn push(&mut self, timestamp: &NaiveDateTime) {
+        self.count += 1;
+        let timestamp = timestamp.format(TIMESTAMP_FORMAT).to_string();
+        // NOTE: The format is fixed width, so the timestamps can be compared as strings.
+        match &self.timestamp {
+            Some(earliest) if earliest <= &timestamp => {}
+            _ => self.timestamp = Some(timestamp),
+        }
+    }
+
+    pub fn timestamp(&self) -> crate::Result<Option<NaiveDateTime>> {
+        match &self.timestamp {
+            Some(timestamp) => Ok(Some(NaiveDateTime::parse_from_str(
+                timestamp,
+                TIMESTAMP_FORMAT,
+            )?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Entry {
+    pub hash: String,
+    /// The number of bytes covered by the hash.
+    #[serde(default)]
+    pub prefix: u64,
+    pub size: u64,
+    /// The id of the first record in the first chunk of an EVTX file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first: Option<u64>,
+    /// The id of the last record processed in an EVTX file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<u64>,
+    /// The offset of the last line processed in a JSONL file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    /// The documents counted against each aggregate, keyed by group and rule name and then by the
+    /// hash of the aggregate's fields.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aggregates: BTreeMap<String, BTreeMap<u64, Saved>>,
+}
+
+impl Entry {
+    fn new(hash: String, prefix: u64, size: u64, first: Option<u64>) -> Self {
+        Self {
+            hash,
+            prefix,
+            size,
+            first,
+            ..Default::default()
+        }
+    }
+
+    fn resume(&self, hash: String, prefix: u64, size: u64, first: Option<u64>) -> Self {
+        Self {
+            hash,
+            prefix,
+            size,
+            first,
+            record: self.record,
+            offset: self.offset,
+            aggregates: self.aggregates.clone(),
+        }
+    }
+}
+
+/// The progress of previous hunts, used to only process what has changed since the last run.
+#[derive(Default, Deserialize, Serialize)]
+pub struct State {
+    #[serde(skip)]
+    path: PathBuf,
+    /// The digest of the rules that the progress was made with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rules: Option<String>,
+    #[serde(default)]
+    files: BTreeMap<PathBuf, Entry>,
+    /// The files that have been hunted but whose results have not been output yet.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pending: BTreeMap<PathBuf, Entry>,
+}
+
+impl State {
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        let mut state: Self = if path.exists() {
+            let mut file = File::open(path)?;
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            serde_json::from_str(&contents)?
+        } else {
+            Self::default()
+        };
+        if !state.pending.is_empty() {
+            cs_eyellowln!(
+                "[!] The previous hunt was interrupted before its results were output, {} files will be hunted again",
+                state.pending.len()
+            );
+            state.pending.clear();
+        }
+        state.path = path.to_path_buf();
+        Ok(state)
+    }
+
+    /// Sets the digest of the loaded rules, discarding all progress if it differs from the one
+    /// that the progress was made with.
+    pub fn rules(&mut self, digest: String) {
+        if let Some(previous) = &self.rules {
+            if previous != &digest && !self.files.is_empty() {
+                cs_eyellowln!(
+                    "[!] The rules have changed since the state was saved, all files will be hunted again"
+                );
+                self.files.clear();
+            }
+        }
+        self.rules = Some(digest);
+    }
+
+    /// Returns the entry to resume the file from, or None if it has not changed since the last
+    /// run. Files that have been replaced are processed from the start.
+    pub fn check(&self, file: &Path, kind: &FileKind) -> crate::Result<Option<Entry>> {
+        let mut f = File::open(file)?;
+        let size = f.metadata()?.len();
+        let previous = self.files.get(&key(file));
+        match kind {
+            FileKind::Evtx => {
+                let hash = hash(&mut f, EVTX_HEADER, EVTX_CHUNK)?;
+                let first = first(&mut f)?;
+                let prefix = EVTX_CHUNK;
+                let previous = match previous {
+                    Some(previous) => previous,
+                    None => return Ok(Some(Entry::new(hash, prefix, size, first))),
+                };
+                // NOTE: Record ids only ever increase, so we can always resume from the last one.
+                // Once the log is full, its oldest chunks are overwritten and so its first chunk
+                // changes, but it will then start from a later record than it used to.
+                let wrapped = match (previous.first, first) {
+                    (Some(previous), Some(first)) => first > previous,
+                    _ => false,
+                };
+                if previous.hash == hash || wrapped {
+                    Ok(Some(previous.resume(hash, prefix, size, first)))
+                } else {
+                    Ok(Some(Entry::new(hash, prefix, size, first)))
+                }
+            }
+            _ => {
+                let prefix = size.min(PREFIX);
+                let previous = match previous {
+                    Some(previous) if previous.prefix <= size => previous,
+                    _ => {
+                        let hash = hash(&mut f, 0, prefix)?;
+                        return Ok(Some(Entry::new(hash, prefix, size, None)));
+                    }
+                };
+                let hash = hash(&mut f, 0, previous.prefix)?;
+                if previous.hash != hash {
+                    let hash = self::hash(&mut f, 0, prefix)?;
+                    return Ok(Some(Entry::new(hash, prefix, size, None)));
+                }
+                if previous.size == size {
+                    return Ok(None);
+                }
+                let hash = if previous.prefix == prefix {
+                    hash
+                } else {
+                    self::hash(&mut f, 0, prefix)?
+                };
+                // NOTE: Only JSONL files can be resumed once they have grown, as they are the
+                // only ones that are appended to.
+                match (kind, previous.offset) {
+                    (FileKind::Jsonl, Some(_)) if previous.size < size => {
+                        Ok(Some(previous.resume(hash, prefix, size, None)))
+                    }
+                    _ => Ok(Some(Entry::new(hash, prefix, size, None))),
+                }
+            }
+        }
+    }
+
+    /// Records the progress made on a file, which is saved but not used until it is committed.
+    pub fn update(&mut self, file: &Path, entry: Entry) {
+        self.pending.insert(key(file), entry);
+    }
+
+    /// Commits the progress of all files that have been hunted, this must only be called once
+    /// their results have been output.
+    pub fn commit(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        self.files.extend(pending);
+    }
+
+    pub fn save(&self) -> crate::Result<()> {
+        // NOTE: Write then rename so that a crash cannot leave behind a truncated state file.
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Computes a digest of the rule set from the files that it was loaded from and the names of the
+/// rules that were loaded from them.
+pub fn digest<'a>(
+    mut paths: Vec<PathBuf>,
+    names: impl Iterator<Item = &'a String>,
+) -> crate::Result<String> {
+    paths.sort();
+    paths.dedup();
+    let mut context = md5::Context::new();
+    for path in paths {
+        io::copy(&mut File::open(&path)?, &mut context)?;
+    }
+    let mut names = names.collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        context.consume(name.as_bytes());
+    }
+    Ok(format!("{:x}", context.compute()))
+}
+
+fn first(f: &mut File) -> crate::Result<Option<u64>> {
+    let mut buf = [0; 8];
+    f.seek(SeekFrom::Start(EVTX_FIRST_RECORD))?;
+    match f.read_exact(&mut buf) {
+        Ok(()) => Ok(Some(u64::from_le_bytes(buf))),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn hash(f: &mut File, offset: u64, length: u64) -> crate::Result<String> {
+    let mut context = md5::Context::new();
+    f.seek(SeekFrom::Start(offset))?;
+    io::copy(&mut f.by_ref().take(length), &mut context)?;
+    Ok(format!("{:x}", context.compute()))
+}
+
+fn key(file: &Path) -> PathBuf {
+    fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("chainsaw-state");
+        fs::create_dir_all(&dir).expect("could not create directory");
+        dir.join(name)
+    }
+
+    fn evtx(first: u64, byte: u8) -> Vec<u8> {
+        let mut data = vec![0; (EVTX_HEADER + EVTX_CHUNK) as usize];
+        data[EVTX_HEADER as usize..EVTX_HEADER as usize + 8].copy_from_slice(b"ElfChnk\0");
+        data[EVTX_FIRST_RECORD as usize..EVTX_FIRST_RECORD as usize + 8]
+            .copy_from_slice(&first.to_le_bytes());
+        data[(EVTX_HEADER + 512) as usize] = byte;
+        data
+    }
+
+    #[test]
+    fn test_check_unchanged() {
+        let path = temp("unchanged.json");
+        fs::write(&path, b"{\"a\": 1}\n").unwrap();
+        let mut state = State::default();
+        let entry = state.check(&path, &FileKind::Json).unwrap().unwrap();
+        assert_eq!(entry.prefix, 9);
+        assert_eq!(entry.size, 9);
+        state.update(&path, entry);
+        // NOTE: Progress is not used until it has been committed.
+        assert!(state.check(&path, &FileKind::Json).unwrap().is_some());
+        state.commit();
+        assert!(state.check(&path, &FileKind::Json).unwrap().is_none());
+
+        fs::write(&path, b"{\"a\": 2}\n").unwrap();
+        let entry = state.check(&path, &FileKind::Json).unwrap().unwrap();
+        assert_eq!(entry.record, None);
+    }
+
+    #[test]
+    fn test_check_prefix() {
+        let path = temp("prefix.json");
+        let mut data = vec![b' '; PREFIX as usize + 10];
+        fs::write(&path, &data).unwrap();
+        let mut state = State::default();
+        let entry = state.check(&path, &FileKind::Json).unwrap().unwrap();
+        assert_eq!(entry.prefix, PREFIX);
+        state.update(&path, entry);
+        state.commit();
+        assert!(state.check(&path, &FileKind::Json).unwrap().is_none());
+
+        // NOTE: Changes past the prefix are only detected when the size changes.
+        data[PREFIX as usize + 5] = b'x';
+        fs::write(&path, &data).unwrap();
+        assert!(state.check(&path, &FileKind::Json).unwrap().is_none());
+        data.push(b' ');
+        fs::write(&path, &data).unwrap();
+        assert!(state.check(&path, &FileKind::Json).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_check_jsonl_appended() {
+        let path = temp("appended.jsonl");
+        fs::write(&path, b"{\"a\": 1}\n").unwrap();
+        let mut state = State::default();
+        let mut entry = state.check(&path, &FileKind::Jsonl).unwrap().unwrap();
+        entry.offset = Some(9);
+        state.update(&path, entry);
+        state.commit();
+
+        fs::write(&path, b"{\"a\": 1}\n{\"a\": 2}\n").unwrap();
+        let entry = state.check(&path, &FileKind::Jsonl).unwrap().unwrap();
+        assert_eq!(entry.offset, Some(9));
+        assert_eq!(entry.prefix, 18);
+        assert_eq!(entry.size, 18);
+
+        // NOTE: Other files are processed from the start once they have grown.
+        let entry = state.check(&path, &FileKind::Json).unwrap().unwrap();
+        assert_eq!(entry.offset, None);
+
+        fs::write(&path, b"{\"a\": 3}\n{\"a\": 2}\n").unwrap();
+        let entry = state.check(&path, &FileKind::Jsonl).unwrap().unwrap();
+        assert_eq!(entry.offset, None);
+    }
+
+    #[test]
+    fn test_check_evtx() {
+        let path = temp("resumed.evtx");
+        fs::write(&path, evtx(1, 0)).unwrap();
+        let mut state = State::default();
+        let mut entry = state.check(&path, &FileKind::Evtx).unwrap().unwrap();
+        assert_eq!(entry.first, Some(1));
+        entry.record = Some(100);
+        state.update(&path, entry);
+        state.commit();
+
+        let entry = state.check(&path, &FileKind::Evtx).unwrap().unwrap();
+        assert_eq!(entry.record, Some(100));
+
+        // NOTE: A log that has wrapped overwrites its first chunk with later records.
+        fs::write(&path, evtx(80, 1)).unwrap();
+        let entry = state.check(&path, &FileKind::Evtx).unwrap().unwrap();
+        assert_eq!(entry.first, Some(80));
+        assert_eq!(entry.record, Some(100));
+
+        // NOTE: A log that has been cleared starts from the first record again.
+        fs::write(&path, evtx(1, 2)).unwrap();
+        let entry = state.check(&path, &FileKind::Evtx).unwrap().unwrap();
+        assert_eq!(entry.record, None);
+    }
+
+    #[test]
+    fn test_rules() {
+        let path = temp("rules.json");
+        fs::write(&path, b"{}").unwrap();
+        let mut state = State::default();
+        state.rules("a".to_owned());
+        let entry = state.check(&path, &FileKind::Json).unwrap().unwrap();
+        state.update(&path, entry);
+        state.commit();
+
+        state.rules("a".to_owned());
+        assert!(state.check(&path, &FileKind::Json).unwrap().is_none());
+        state.rules("b".to_owned());
+        assert!(state.check(&path, &FileKind::Json).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_load_pending() {
+        let path = temp("pending.state");
+        let file = temp("pending.json");
+        fs::write(&file, b"{}").unwrap();
+        let mut state = State::load(&path).unwrap();
+        let entry = state.check(&file, &FileKind::Json).unwrap().unwrap();
+        state.update(&file, entry);
+        state.save().unwrap();
+
+        let mut state = State::load(&path).unwrap();
+        assert!(state.check(&file, &FileKind::Json).unwrap().is_some());
+        let entry = state.check(&file, &FileKind::Json).unwrap().unwrap();
+        state.update(&file, entry);
+        state.commit();
+        state.save().unwrap();
+
+        let state = State::load(&path).unwrap();
+        assert!(state.check(&file, &FileKind::Json).unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_saved_push() {
+        let later =
+            NaiveDateTime::parse_from_str("2023-03-16T20:55:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+        let earlier =
+            NaiveDateTime::parse_from_str("2023-03-16T20:54:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+        let mut saved = Saved::default();
+        assert_eq!(saved.timestamp().unwrap(), None);
+        saved.push(&later);
+        saved.push(&earlier);
+        saved.push(&later);
+        assert_eq!(saved.count, 3);
+        assert_eq!(saved.timestamp().unwrap(), Some(earlier));
+    }
+
+    #[test]
+    fn test_digest() {
+        let path = temp("digest.yml");
+        fs::write(&path, b"title: a").unwrap();
+        let names = vec!["a".to_owned(), "b".to_owned()];
+        let a = digest(vec![path.clone()], names.iter()).unwrap();
+        assert_eq!(a, digest(vec![path.clone()], names.iter().rev()).unwrap());
+        assert_ne!(a, digest(vec![path.clone()], names.iter().take(1)).unwrap());
+        fs::write(&path, b"title: b").unwrap();
+        assert_ne!(a, digest(vec![path], names.iter()).unwrap());
+    }
+}
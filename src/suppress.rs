@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tau_engine::{core::optimiser, Document};
+use uuid::Uuid;
+
+use crate::rule::{Filter, Rule};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Suppression {
+    #[serde(skip, default = "Uuid::new_v4")]
+    pub id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub filter: Option<Filter>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub justification: Option<String>,
+}
+
+impl Suppression {
+    pub fn expired(&self) -> crate::Result<bool> {
+        if let Some(expires) = &self.expires {
+            let date = match NaiveDate::parse_from_str(expires, "%Y-%m-%d") {
+                Ok(date) => date,
+                Err(e) => anyhow::bail!("invalid expiry date '{}' - {}", expires, e),
+            };
+            return Ok(date < Utc::now().date_naive());
+        }
+        Ok(false)
+    }
+
+    #[inline]
+    pub fn is_match(&self, group: &str, rule: &Rule, document: &dyn Document) -> bool {
+        if let Some(name) = &self.rule {
+            if name != rule.name() && Some(name) != rule.id() {
+                return false;
+            }
+        }
+        if let Some(g) = &self.group {
+            if g != group {
+                return false;
+            }
+        }
+        match &self.filter {
+            Some(Filter::Detection(detection)) => tau_engine::solve(detection, document),
+            Some(Filter::Expression(expression)) => tau_engine::core::solve(expression, document),
+            None => true,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Suppressions {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub suppressions: Vec<Suppression>,
+}
+
+pub fn load(path: &Path) -> crate::Result<Vec<Suppression>> {
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    let suppressions: Suppressions = serde_yaml::from_str(&contents)?;
+    let mut loaded = Vec::with_capacity(suppressions.suppressions.len());
+    for mut suppression in suppressions.suppressions {
+        if suppression.rule.is_none() && suppression.group.is_none() {
+            anyhow::bail!("suppressions must specify a rule or a group");
+        }
+        if suppression.expired()? {
+            cs_eyellowln!(
+                "[!] Ignoring expired suppression for '{}' (expired {})",
+                suppression
+                    .rule
+                    .as_ref()
+                    .or(suppression.group.as_ref())
+                    .expect("could not get suppression target"),
+                suppression.expires.as_ref().expect("could not get expiry"),
+            );
+            continue;
+        }
+        suppression.filter = suppression.filter.map(|filter| match filter {
+            Filter::Detection(mut detection) => {
+                detection.expression =
+                    optimiser::coalesce(detection.expression, &detection.identifiers);
+                detection.identifiers.clear();
+                detection.expression = optimiser::shake(detection.expression);
+                detection.expression = optimiser::rewrite(detection.expression);
+                detection.expression = optimiser::matrix(detection.expression);
+                Filter::Detection(detection)
+            }
+            Filter::Expression(expression) => Filter::Expression({
+                let expression = optimiser::shake(expression);
+                let expression = optimiser::rewrite(expression);
+                optimiser::matrix(expression)
+            }),
+        });
+        loaded.push(suppression);
+    }
+    Ok(loaded)
+}
@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::Value as Json;
+use serde_yaml::Value as Yaml;
+
+use crate::file::{Document as File, Kind as FileKind, Reader};
+use crate::hunt::Hunter;
+use crate::rule::{load, sigma::Config, Kind as RuleKind};
+use crate::value::Value;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Tally {
+    pub passed: usize,
+    pub total: usize,
+}
+
+/// A sample that did not behave as expected, a true positive that was not matched or a true
+/// negative that was.
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub sample: String,
+    pub expected: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub name: String,
+    pub path: PathBuf,
+    pub kind: RuleKind,
+    pub true_positives: Tally,
+    pub true_negatives: Tally,
+    pub failures: Vec<Failure>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Report {
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.failures.is_empty()
+    }
+
+    #[inline]
+    pub fn skipped(&self) -> bool {
+        self.error.is_none() && self.true_positives.total == 0 && self.true_negatives.total == 0
+    }
+}
+
+/// Tests rules against the sample documents that they embed or reference, Sigma rules are tested
+/// through the mapping files so that they behave exactly as they would when hunting.
+pub struct Tester {
+    config: Config,
+    mappings: Vec<PathBuf>,
+}
+
+impl Tester {
+    pub fn new(mappings: Vec<PathBuf>, config: Config) -> Self {
+        Self { config, mappings }
+    }
+
+    /// Tests the rules in a file, a file that holds a Sigma rule collection passes a true positive
+    /// when any of its rules match and a true negative when none of them do.
+    pub fn test(&self, kind: &RuleKind, path: &Path) -> Option<Report> {
+        let rules = match load(kind.clone(), path, &None, &None, &None, &self.config) {
+            Ok(rules) => rules,
+            Err(e) => {
+                return Some(Report {
+                    name: path.display().to_string(),
+                    path: path.to_path_buf(),
+                    kind: kind.clone(),
+                    true_positives: Tally::default(),
+                    true_negatives: Tally::default(),
+                    failures: vec![],
+                    error: Some(e.to_string()),
+                })
+            }
+        };
+        let rule = rules.first()?;
+        let mut report = Report {
+            name: rule.name().clone(),
+            path: path.to_path_buf(),
+            kind: kind.clone(),
+            true_positives: Tally::default(),
+            true_negatives: Tally::default(),
+            failures: vec![],
+            error: None,
+        };
+        let (positives, negatives) = rule.samples();
+        let (positives, negatives) = (positives.to_vec(), negatives.to_vec());
+        if positives.is_empty() && negatives.is_empty() {
+            return Some(report);
+        }
+
+        let mappings = match kind {
+            RuleKind::Sigma => self.mappings.clone(),
+            _ => vec![],
+        };
+        let hunter = match Hunter::builder().rules(rules).mappings(mappings).build() {
+            Ok(hunter) => hunter,
+            Err(e) => {
+                report.error = Some(e.to_string());
+                return Some(report);
+            }
+        };
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        for (expected, samples) in [(true, positives), (false, negatives)] {
+            for (i, sample) in samples.into_iter().enumerate() {
+                let name = if expected {
+                    format!("true_positives[{}]", i)
+                } else {
+                    format!("true_negatives[{}]", i)
+                };
+                let documents = match documents(base, sample) {
+                    Ok(documents) => documents,
+                    Err(e) => {
+                        report.error = Some(format!("{} - {}", name, e));
+                        return Some(report);
+                    }
+                };
+                let tally = if expected {
+                    &mut report.true_positives
+                } else {
+                    &mut report.true_negatives
+                };
+                for (j, (kind, value)) in documents.iter().enumerate() {
+                    tally.total += 1;
+                    if hunter.test(kind.as_ref(), value) == expected {
+                        tally.passed += 1;
+                    } else {
+                        let sample = if documents.len() > 1 {
+                            format!("{} (document {})", name, j + 1)
+                        } else {
+                            name.clone()
+                        };
+                        report.failures.push(Failure { sample, expected });
+                    }
+                }
+            }
+        }
+        Some(report)
+    }
+}
+
+// Embedded samples and text formats can be checked against any hunt, as they are often exports
+// of other artefacts, whereas binary artefacts are only checked against hunts of their kind.
+fn documents(base: &Path, sample: Yaml) -> crate::Result<Vec<(Option<FileKind>, Value)>> {
+    let path = match sample {
+        Yaml::String(path) => base.join(path),
+        Yaml::Mapping(_) => {
+            let json: Json = serde_yaml::from_value(sample)?;
+            return Ok(vec![(None, json.into())]);
+        }
+        _ => anyhow::bail!("samples must be a document or a path to a file of them"),
+    };
+    let mut reader = Reader::load(&path, false, false)?;
+    let kind = match reader.kind() {
+        FileKind::Json | FileKind::Jsonl | FileKind::Xml => None,
+        FileKind::Unknown => anyhow::bail!("unsupported sample file '{}'", path.display()),
+        kind => Some(kind),
+    };
+    let mut documents = vec![];
+    for document in reader.documents() {
+        let value: Value = match document? {
+            File::Evtx(evtx) => evtx.data.into(),
+            File::Hve(hve) => hve.into(),
+            File::Json(json) => json.into(),
+            File::Mft(mft) => mft.into(),
+            File::Xml(xml) => xml.into(),
+        };
+        documents.push((kind.clone(), value));
+    }
+    Ok(documents)
+}
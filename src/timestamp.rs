@@ -0,0 +1,231 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDateTime};
+use serde::{de, Deserialize, Deserializer};
+use tau_engine::Value as Tau;
+
+// The number of seconds between the FILETIME epoch (1601-01-01) and the UNIX epoch.
+const FILETIME_OFFSET: i64 = 11_644_473_600;
+
+/// The format used to parse timestamps out of documents.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Format {
+    Rfc3339,
+    Epoch,
+    EpochMs,
+    Filetime,
+    Strftime(String),
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::Strftime("%Y-%m-%dT%H:%M:%S%.6fZ".to_owned())
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rfc3339 => write!(f, "rfc3339"),
+            Self::Epoch => write!(f, "epoch"),
+            Self::EpochMs => write!(f, "epoch_ms"),
+            Self::Filetime => write!(f, "filetime"),
+            Self::Strftime(format) => write!(f, "strftime:{}", format),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v = match s {
+            "rfc3339" => Self::Rfc3339,
+            "epoch" => Self::Epoch,
+            "epoch_ms" => Self::EpochMs,
+            "filetime" => Self::Filetime,
+            _ => match s.strip_prefix("strftime:") {
+                Some(format) if !format.is_empty() => Self::Strftime(format.to_owned()),
+                _ => anyhow::bail!(
+                    "unknown timestamp format, must be: rfc3339, epoch, epoch_ms, filetime or strftime:<format>"
+                ),
+            },
+        };
+        Ok(v)
+    }
+}
+
+impl<'de> Deserialize<'de> for Format {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Format::from_str(&s).map_err(de::Error::custom)
+    }
+}
+
+impl Format {
+    /// Parses the timestamp into UTC, returning None when the value is not of a type that the
+    /// format can be parsed from.
+    pub fn parse(&self, value: &Tau) -> Option<crate::Result<NaiveDateTime>> {
+        let timestamp = match self {
+            Self::Rfc3339 => {
+                let s = value.as_str()?;
+                DateTime::parse_from_rfc3339(s)
+                    .map(|t| t.naive_utc())
+                    .map_err(|e| anyhow!("failed to parse timestamp '{}' - {}", s, e))
+            }
+            Self::Strftime(format) => {
+                let s = value.as_str()?;
+                // NOTE: Formats with an offset are converted to UTC, those without are assumed
+                // to already be in UTC.
+                let timestamp =
+                    if format.contains("%z") || format.contains("%:z") || format.contains("%#z") {
+                        DateTime::parse_from_str(s, format).map(|t| t.naive_utc())
+                    } else {
+                        NaiveDateTime::parse_from_str(s, format)
+                    };
+                timestamp.map_err(|e| anyhow!("failed to parse timestamp '{}' - {}", s, e))
+            }
+            Self::Epoch => match number(value)? {
+                Ok(Number::Float(f)) => from_timestamp(f.floor() as i64, nanos(f), value),
+                Ok(Number::Int(i)) => from_timestamp(i, 0, value),
+                Err(e) => Err(e),
+            },
+            Self::EpochMs => match number(value)? {
+                Ok(Number::Float(f)) => {
+                    let secs = f / 1e3;
+                    from_timestamp(secs.floor() as i64, nanos(secs), value)
+                }
+                Ok(Number::Int(i)) => from_timestamp(
+                    i.div_euclid(1_000),
+                    (i.rem_euclid(1_000) * 1_000_000) as u32,
+                    value,
+                ),
+                Err(e) => Err(e),
+            },
+            Self::Filetime => match number(value)? {
+                Ok(Number::Float(f)) => {
+                    let secs = f / 1e7 - FILETIME_OFFSET as f64;
+                    from_timestamp(secs.floor() as i64, nanos(secs), value)
+                }
+                Ok(Number::Int(i)) => from_timestamp(
+                    i.div_euclid(10_000_000) - FILETIME_OFFSET,
+                    (i.rem_euclid(10_000_000) * 100) as u32,
+                    value,
+                ),
+                Err(e) => Err(e),
+            },
+        };
+        Some(timestamp)
+    }
+}
+
+enum Number {
+    Float(f64),
+    Int(i64),
+}
+
+// Returns the number held by the value, None is returned when the value is not a number or string
+// while a string that does not hold a number is an error.
+fn number(value: &Tau) -> Option<crate::Result<Number>> {
+    let number = match value {
+        Tau::Float(f) => Number::Float(*f),
+        Tau::Int(i) => Number::Int(*i),
+        Tau::UInt(u) => Number::Int(*u as i64),
+        // NOTE: Exports often quote numbers, so we try to handle those too.
+        Tau::String(s) => match s.trim().parse::<i64>() {
+            Ok(i) => Number::Int(i),
+            Err(_) => match s.trim().parse::<f64>() {
+                Ok(f) => Number::Float(f),
+                Err(_) => {
+                    return Some(Err(anyhow!(
+                        "failed to parse timestamp '{}' - not a number",
+                        s
+                    )))
+                }
+            },
+        },
+        _ => return None,
+    };
+    if let Number::Float(f) = number {
+        if !f.is_finite() {
+            return Some(Err(anyhow!(
+                "failed to parse timestamp '{}' - not a number",
+                f
+            )));
+        }
+    }
+    Some(Ok(number))
+}
+
+// Returns the nanoseconds of the fractional part of a number of seconds, which for negative
+// numbers is the time after the second that they are floored to.
+fn nanos(secs: f64) -> u32 {
+    (((secs - secs.floor()) * 1e9) as u32).min(999_999_999)
+}
+
+fn from_timestamp(secs: i64, nsecs: u32, value: &Tau) -> crate::Result<NaiveDateTime> {
+    match NaiveDateTime::from_timestamp_opt(secs, nsecs) {
+        Some(timestamp) => Ok(timestamp),
+        None => anyhow::bail!(
+            "failed to parse timestamp '{}' - out of range",
+            value.to_string().unwrap_or_default()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_parse_epoch() {
+        let timestamp = Format::Epoch.parse(&Tau::Float(-1.25)).unwrap().unwrap();
+        assert_eq!(timestamp.timestamp(), -2);
+        assert_eq!(timestamp.timestamp_subsec_millis(), 750);
+
+        let timestamp = Format::EpochMs
+            .parse(&Tau::String(Cow::Borrowed("-1500.0")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamp.timestamp(), -2);
+        assert_eq!(timestamp.timestamp_subsec_millis(), 500);
+
+        let timestamp = Format::EpochMs.parse(&Tau::Int(-1)).unwrap().unwrap();
+        assert_eq!(timestamp.timestamp(), -1);
+        assert_eq!(timestamp.timestamp_subsec_millis(), 999);
+
+        let timestamp = Format::Filetime
+            .parse(&Tau::Int(116_444_736_015_000_000))
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamp.timestamp(), 1);
+        assert_eq!(timestamp.timestamp_subsec_millis(), 500);
+
+        let timestamp = Format::Filetime
+            .parse(&Tau::Float(116_444_736_005_000_000.0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamp.timestamp(), 0);
+        assert_eq!(timestamp.timestamp_subsec_millis(), 500);
+
+        let timestamp = Format::Filetime.parse(&Tau::Int(-1)).unwrap().unwrap();
+        assert_eq!(timestamp.timestamp(), -FILETIME_OFFSET - 1);
+        assert_eq!(timestamp.timestamp_subsec_nanos(), 999_999_900);
+
+        assert!(Format::Epoch
+            .parse(&Tau::String(Cow::Borrowed("yesterday")))
+            .unwrap()
+            .is_err());
+        assert!(Format::Epoch
+            .parse(&Tau::String(Cow::Borrowed("NaN")))
+            .unwrap()
+            .is_err());
+        assert!(Format::Epoch.parse(&Tau::Bool(true)).is_none());
+    }
+}
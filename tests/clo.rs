@@ -47,6 +47,23 @@ fn search_q_simple_string()-> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn search_q_jsonl_epoch_ms()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("epoch_sample.jsonl");
+    let sample_expected_output_path = Path::new(root).join("tests/evtx").join("clo_search_q_jsonl_epoch_ms.txt");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("search").arg("-e").arg("msg").arg(sample_path).arg("-q").arg("--jsonl")
+        .arg("--timestamp").arg("ts").arg("--timestamp-format").arg("epoch_ms")
+        .arg("--from").arg("2023-03-16T20:54:00").arg("--to").arg("2023-03-16T20:56:00");
+    cmd.assert()
+        .success()
+        .stdout( predicate::path::eq_file(sample_expected_output_path).utf8().unwrap());
+
+    Ok(())
+}
+
 #[test]
 fn hunt_r_any_logon()-> Result<(), Box<dyn std::error::Error>> {
     let root = env!("CARGO_MANIFEST_DIR");
@@ -61,4 +78,744 @@ fn hunt_r_any_logon()-> Result<(), Box<dyn std::error::Error>> {
         .stdout( predicate::path::eq_file(sample_expected_output_path).utf8().unwrap());
     
     Ok(())
-}
\ No newline at end of file
+}
+#[test]
+fn hunt_r_any_logon_risk()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let sample_expected_output_path = Path::new(root).join("tests/evtx").join("clo_hunt_r_any_logon_risk.txt");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-any-logon.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path).arg("--risk");
+    cmd.assert()
+        .success()
+        .stdout( predicate::path::eq_file(sample_expected_output_path).utf8().unwrap());
+    
+    Ok(())
+}
+#[test]
+fn hunt_r_any_logon_risk_json_timezone()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-any-logon.yml");
+    let output_path = std::env::temp_dir().join("chainsaw_hunt_r_any_logon_risk_json_timezone.json");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path).arg("--risk-json").arg(&output_path).arg("--timezone").arg("Europe/London").arg("-q");
+    cmd.assert()
+        .success();
+    let json = std::fs::read_to_string(&output_path)?;
+    assert!(json.contains("\"first_seen\": \"2022-10-11T19:26:52+01:00\""));
+    assert!(json.contains("\"last_seen\": \"2022-10-11T19:26:56+01:00\""));
+
+    Ok(())
+}
+#[test]
+fn hunt_r_any_logon_suppressed()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let sample_expected_output_path = Path::new(root).join("tests/evtx").join("clo_hunt_r_any_logon_suppressed.txt");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-any-logon.yml");
+    let suppress_path = Path::new(root).join("tests/evtx").join("suppress-any-logon.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path).arg("--suppress").arg(suppress_path);
+    cmd.assert()
+        .success()
+        .stdout( predicate::path::eq_file(sample_expected_output_path).utf8().unwrap());
+    
+    Ok(())
+}
+
+#[test]
+fn hunt_r_any_logon_tag_suppressed_json()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-any-logon.yml");
+    let suppress_path = Path::new(root).join("tests/evtx").join("suppress-any-logon.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path).arg("--suppress").arg(suppress_path).arg("--tag-suppressed").arg("--json").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\":\"Any Logon\"").count(2))
+        .stdout(predicate::str::contains("\"suppressed\":{\"rule\":\"Any Logon\",\"justification\":\"Testing\"}").count(1));
+
+    Ok(())
+}
+#[test]
+fn hunt_r_repeated_logon_suppressed()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-repeated-logon.yml");
+    let suppress_path = Path::new(root).join("tests/evtx").join("suppress-lateral-movement.yml");
+
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--json").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\":\"Repeated Logon\"").count(1));
+
+    // NOTE: Suppressed documents are excluded from aggregates even when tagging, so the count
+    // drops below the threshold.
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--suppress").arg(&suppress_path).arg("--tag-suppressed").arg("--json").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Repeated Logon").not());
+
+    Ok(())
+}
+
+#[test]
+fn hunt_r_host_transform()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let sample_expected_output_path = Path::new(root).join("tests/evtx").join("clo_hunt_r_host_transform.txt");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-host-transform.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path);
+    cmd.assert()
+        .success()
+        .stdout( predicate::path::eq_file(sample_expected_output_path).utf8().unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn hunt_r_list_host()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let sample_expected_output_path = Path::new(root).join("tests/evtx").join("clo_hunt_r_list_host.txt");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-list-host.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path);
+    cmd.assert()
+        .success()
+        .stdout( predicate::path::eq_file(sample_expected_output_path).utf8().unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn hunt_intel_csv()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let intel_path = Path::new(root).join("tests/evtx").join("intel-indicators.csv");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-all.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("--intel").arg(intel_path).arg("--mapping").arg(mapping_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\":\"Intel - Test Feed: file name\""))
+        .stdout(predicate::str::contains("\"indicators\":[\"indicator--services\"]"))
+        .stdout(predicate::str::contains("\"authors\":[\"Test Feed\"]"))
+        .stdout(predicate::str::contains("\"tags\":[\"attack.t1543.003\"]"))
+        .stdout(predicate::str::contains("indicator--ip").not());
+
+    Ok(())
+}
+
+#[test]
+fn hunt_yara_field()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let yara_path = Path::new(root).join("tests/evtx").join("rule-services.yar");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-all.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("--yara").arg(yara_path).arg("--yara-field").arg("ProcessName").arg("--mapping").arg(mapping_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\":\"Services_Process\""))
+        .stdout(predicate::str::contains("\"source\":\"yara\""))
+        .stdout(predicate::str::contains("\"tags\":[\"testing\"]"))
+        .stdout(predicate::str::contains("\"level\":\"medium\""))
+        .stdout(predicate::str::contains("\"identifier\":\"$services\""));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_yara_unmapped()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let yara_path = Path::new(root).join("tests/evtx").join("rule-services.yar");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("--yara").arg(yara_path).arg("--yara-field").arg("Event.EventData.ProcessName").arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\":\"Services_Process\""))
+        .stdout(predicate::str::contains("\"group\":\"YARA\""));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_attack_coverage()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rules_path = Path::new(root).join("rules");
+    let expected_output_path = Path::new(root).join("tests/evtx").join("clo_hunt_attack_coverage.json");
+    let output_path = std::env::temp_dir().join("chainsaw_hunt_attack_coverage.json");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(rules_path).arg("--attack-coverage").arg(&output_path).arg("-q");
+    cmd.assert()
+        .success();
+    assert!(predicate::path::eq_file(expected_output_path).eval(output_path.as_path()));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_explain_unmapped()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-unmapped.yml");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-all.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg("-s").arg(sigma_path).arg("--mapping").arg(mapping_path).arg("--explain").arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"name\":\"Unmapped Field\""))
+        .stdout(predicate::str::contains("\"coverage\":\"unmapped\""))
+        .stdout(predicate::str::contains("\"unmapped\":[\"NotAMappedField\"]"));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_explain_logsource_routing()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-unmapped.yml");
+    let mapping_path = Path::new(root).join("tests/evtx").join("mapping-logsource.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg("-s").arg(&sigma_path).arg("--mapping").arg(mapping_path).arg("--explain").arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"group\":\"Suspicious Process Creation\""))
+        .stdout(predicate::str::contains("\"group\":\"Suspicious Command Line\""))
+        .stdout(predicate::str::contains("Suspicious Network Connection").not());
+
+    // The bundled mappings do not declare logsources, so every rule still runs under every group.
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-legacy.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg("-s").arg(&sigma_path).arg("--mapping").arg(mapping_path).arg("--explain").arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"group\":\"Suspicious Network Connection\""));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_r_any_logon_matched()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-any-logon.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path).arg("--matched").arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"matched\":{\"any_4624\":{\"Event.System.EventID\":\"4624\"}}"));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_r_any_logon_matched_csv()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-any-logon.yml");
+    let output_path = std::env::temp_dir().join("chainsaw_hunt_r_any_logon_matched_csv");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path).arg("--matched").arg("--csv").arg("-o").arg(&output_path).arg("-q");
+    cmd.assert()
+        .success();
+    let csv = std::fs::read_to_string(output_path.join("lateral_movement.csv"))?;
+    assert!(csv.starts_with("timestamp,detections,matched,"));
+    assert!(csv.contains("any_4624: Event.System.EventID=4624"));
+
+    Ok(())
+}
+
+#[test]
+fn test_r_logon_samples()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-test-logon.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg(rule_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"true_positives\":{\"passed\":3,\"total\":3}"))
+        .stdout(predicate::str::contains("\"true_negatives\":{\"passed\":2,\"total\":2}"))
+        .stdout(predicate::str::contains("\"failures\":[]"));
+
+    Ok(())
+}
+
+#[test]
+fn test_r_logon_samples_failed()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-test-logon-broken.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg(rule_path).arg("-jq");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("\"true_negatives\":{\"passed\":1,\"total\":2}"))
+        .stdout(predicate::str::contains("{\"sample\":\"true_negatives[0]\",\"expected\":false}"));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_r_any_logon_profile()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-any-logon.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(sample_path).arg("-r").arg(rule_path).arg("--profile").arg("-j");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[+] Profile"))
+        .stderr(predicate::str::contains("[+] Slowest Rules (top 1 of 1)"))
+        .stderr(predicate::str::contains("Any Logon"));
+
+    Ok(())
+}
+
+#[test]
+fn test_s_modifier_samples()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-test-modifiers.yml");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-all.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg("-s").arg(sigma_path).arg("--mapping").arg(mapping_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"true_positives\":{\"passed\":2,\"total\":2}"))
+        .stdout(predicate::str::contains("\"true_negatives\":{\"passed\":4,\"total\":4}"))
+        .stdout(predicate::str::contains("\"failures\":[]"));
+
+    Ok(())
+}
+
+#[test]
+fn test_s_encoding_samples()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-test-encoding.yml");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-all.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg("-s").arg(sigma_path).arg("--mapping").arg(mapping_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"true_positives\":{\"passed\":2,\"total\":2}"))
+        .stdout(predicate::str::contains("\"true_negatives\":{\"passed\":3,\"total\":3}"))
+        .stdout(predicate::str::contains("\"failures\":[]"));
+
+    Ok(())
+}
+
+#[test]
+fn test_s_pipeline_samples()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-test-modifiers.yml");
+    let pipeline_path = Path::new(root).join("tests/evtx").join("pipeline-windows.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg("-s").arg(sigma_path).arg("--mapping").arg(pipeline_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"true_positives\":{\"passed\":2,\"total\":2}"))
+        .stdout(predicate::str::contains("\"true_negatives\":{\"passed\":4,\"total\":4}"))
+        .stdout(predicate::str::contains("\"failures\":[]"));
+
+    Ok(())
+}
+
+#[test]
+fn test_r_fieldref_samples()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-test-fieldref.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg(rule_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"true_positives\":{\"passed\":2,\"total\":2}"))
+        .stdout(predicate::str::contains("\"true_negatives\":{\"passed\":2,\"total\":2}"))
+        .stdout(predicate::str::contains("\"failures\":[]"));
+
+    Ok(())
+}
+
+#[test]
+fn lint_s_unresolved_placeholder()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-placeholder.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("lint").arg(sigma_path).arg("--kind").arg("sigma");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("unresolved placeholder - %Administrators%"))
+        .stderr(predicate::str::contains("[+] Validated 0 detection rules out of 1"));
+
+    Ok(())
+}
+
+#[test]
+fn lint_s_sigma_config()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-placeholder.yml");
+    let config_path = Path::new(root).join("tests/evtx").join("sigma-config.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("lint").arg(sigma_path).arg("--kind").arg("sigma").arg("--sigma-config").arg(config_path);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[+] Validated 1 detection rules out of 1"));
+
+    Ok(())
+}
+
+#[test]
+fn lint_s_discarded_documents()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/convert").join("sigma_repeat.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("lint").arg(sigma_path).arg("--kind").arg("sigma");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("document 6 was discarded - missing field `title`"))
+        .stderr(predicate::str::contains("[+] Validated 1 detection rules out of 1"));
+
+    Ok(())
+}
+
+#[test]
+fn test_s_sigma_filter()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-test-modifiers.yml");
+    let filter_path = Path::new(root).join("tests/evtx").join("sigma-filter.yml");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-all.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg("-s").arg(sigma_path).arg("--mapping").arg(mapping_path).arg("--sigma-filter").arg(filter_path).arg("-jq");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("\"true_positives\":{\"passed\":1,\"total\":2}"))
+        .stdout(predicate::str::contains("\"sample\":\"true_positives[1]\""));
+
+    Ok(())
+}
+
+#[test]
+fn lint_s_sigma_filter()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let filter_path = Path::new(root).join("tests/evtx").join("sigma-filter.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("lint").arg(filter_path).arg("--kind").arg("sigma");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[+] Validated 1 detection rules out of 1"));
+
+    Ok(())
+}
+
+#[test]
+fn convert_r_to_sigma()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-test-logon.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("convert").arg(rule_path).arg("--kind").arg("chainsaw");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("title: Tested Logon"))
+        .stdout(predicate::str::contains("Event.System.EventID: 4624"))
+        .stdout(predicate::str::contains("condition: logon and network"))
+        .stdout(predicate::str::contains("level: informational"))
+        .stderr(predicate::str::contains("[+] Converted 1 rules out of 1"));
+
+    Ok(())
+}
+
+#[test]
+fn convert_s_to_chainsaw()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-unmapped.yml");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-legacy.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("convert").arg(sigma_path).arg("--kind").arg("sigma").arg("--mapping").arg(mapping_path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("group: Suspicious Process Creation"))
+        .stdout(predicate::str::contains("Event.EventData.CommandLine:"))
+        .stdout(predicate::str::contains("condition: group_filter and (selection and filter)"))
+        .stderr(predicate::str::contains("uses field 'NotAMappedField' which is not mapped"))
+        .stderr(predicate::str::contains("[+] Converted 1 rules out of 1"));
+
+    Ok(())
+}
+
+#[test]
+fn test_s_wildcard_samples()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-test-wildcards.yml");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-all.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg("-s").arg(sigma_path).arg("--mapping").arg(mapping_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"true_positives\":{\"passed\":2,\"total\":2}"))
+        .stdout(predicate::str::contains("\"true_negatives\":{\"passed\":3,\"total\":3}"))
+        .stdout(predicate::str::contains("\"failures\":[]"));
+
+    Ok(())
+}
+
+#[test]
+fn test_s_list_samples()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-test-lists.yml");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-all.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("test").arg("-s").arg(sigma_path).arg("--mapping").arg(mapping_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"true_positives\":{\"passed\":2,\"total\":2}"))
+        .stdout(predicate::str::contains("\"true_negatives\":{\"passed\":1,\"total\":1}"))
+        .stdout(predicate::str::contains("\"failures\":[]"));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_state_jsonl_resume()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-json-logon.yml");
+    let dir = std::env::temp_dir().join("chainsaw-hunt-state-jsonl-resume");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    let sample_path = dir.join("logons.jsonl");
+    let state_path = dir.join("hunt.state");
+    std::fs::copy(Path::new(root).join("tests/evtx").join("state-logons.jsonl"), &sample_path)?;
+
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--state").arg(&state_path).arg("--jsonl").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("alice"))
+        .stdout(predicate::str::contains("bob"));
+
+    // An unchanged file is skipped.
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--state").arg(&state_path).arg("--jsonl").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+
+    // A file that has been appended to is resumed from where the last hunt stopped.
+    let mut file = std::fs::OpenOptions::new().append(true).open(&sample_path)?;
+    std::io::Write::write_all(&mut file, b"{\"Event\":{\"System\":{\"EventID\":4624,\"Computer\":\"DESKTOP-03\",\"TimeCreated\":\"2023-03-16T20:56:00.000000Z\"},\"EventData\":{\"LogonType\":3,\"TargetUserName\":\"carol\"}}}\n")?;
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--state").arg(&state_path).arg("--jsonl").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("carol"))
+        .stdout(predicate::str::contains("alice").not())
+        .stdout(predicate::str::contains("bob").not());
+
+    Ok(())
+}
+
+#[test]
+fn hunt_state_aggregate_resume()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-json-repeated-logon.yml");
+    let dir = std::env::temp_dir().join("chainsaw-hunt-state-aggregate-resume");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    let sample_path = dir.join("logons.jsonl");
+    let state_path = dir.join("hunt.state");
+    std::fs::copy(Path::new(root).join("tests/evtx").join("state-logons.jsonl"), &sample_path)?;
+
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--state").arg(&state_path).arg("--jsonl").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+
+    // Only the count of the documents is kept, not the documents themselves.
+    let state = std::fs::read_to_string(&state_path)?;
+    assert!(state.contains("\"count\":2"));
+    assert!(!state.contains("alice"));
+
+    // The documents from the previous hunt count towards the aggregate.
+    let mut file = std::fs::OpenOptions::new().append(true).open(&sample_path)?;
+    std::io::Write::write_all(&mut file, b"{\"Event\":{\"System\":{\"EventID\":4624,\"Computer\":\"DESKTOP-03\",\"TimeCreated\":\"2023-03-16T20:56:00.000000Z\"},\"EventData\":{\"LogonType\":3,\"TargetUserName\":\"carol\"}}}\n")?;
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--state").arg(&state_path).arg("--jsonl").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"timestamp\":\"2023-03-16T20:54:00+00:00\""))
+        .stdout(predicate::str::contains("carol"))
+        .stdout(predicate::str::contains("alice").not());
+
+    Ok(())
+}
+
+#[test]
+fn hunt_state_saved_per_file()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-json-logon.yml");
+    let dir = std::env::temp_dir().join("chainsaw-hunt-state-saved-per-file");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    let sample_path = dir.join("logons.jsonl");
+    let state_path = dir.join("hunt.state");
+    std::fs::copy(Path::new(root).join("tests/evtx").join("state-logons.jsonl"), &sample_path)?;
+
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--state").arg(&state_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("alice"));
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--state").arg(&state_path).arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("alice").not());
+
+    // Progress from a hunt that was interrupted before its results were output is discarded.
+    let state = std::fs::read_to_string(&state_path)?;
+    let state = state.replacen("\"files\":", "\"pending\":", 1);
+    std::fs::write(&state_path, state)?;
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&rule_path).arg("--state").arg(&state_path).arg("-j");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("alice"))
+        .stderr(predicate::str::contains("The previous hunt was interrupted before its results were output, 1 files will be hunted again"));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_state_rules_changed()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-json-logon.yml");
+    let dir = std::env::temp_dir().join("chainsaw-hunt-state-rules-changed");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    let sample_path = dir.join("logons.jsonl");
+    let state_path = dir.join("hunt.state");
+    let other_path = dir.join("rule.yml");
+    std::fs::copy(Path::new(root).join("tests/evtx").join("state-logons.jsonl"), &sample_path)?;
+    std::fs::copy(&rule_path, &other_path)?;
+
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&other_path).arg("--state").arg(&state_path).arg("--jsonl").arg("-q");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("alice"));
+
+    let rule = std::fs::read_to_string(&rule_path)?;
+    std::fs::write(&other_path, rule.replace("level: info", "level: low"))?;
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("hunt").arg(&sample_path).arg("-r").arg(&other_path).arg("--state").arg(&state_path).arg("--jsonl");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("alice"))
+        .stderr(predicate::str::contains("The rules have changed since the state was saved, all files will be hunted again"));
+
+    Ok(())
+}
+
+#[test]
+fn convert_round_trip()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-test-cased.yml");
+    let dir = std::env::temp_dir().join("chainsaw-convert-round-trip");
+    let _ = std::fs::remove_dir_all(&dir);
+    for name in ["a", "b"] {
+        std::fs::create_dir_all(dir.join("rules").join(name))?;
+        std::fs::copy(&rule_path, dir.join("rules").join(name).join("rule.yml"))?;
+    }
+
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("convert").arg(dir.join("rules")).arg("--kind").arg("chainsaw").arg("-o").arg(dir.join("sigma"));
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[+] Converted 2 rules out of 2"));
+    let converted = std::fs::read_to_string(dir.join("sigma").join("a").join("rule.yml"))?;
+    assert!(converted.contains("Event.EventData.Image|endswith|cased:"));
+    assert!(converted.contains("Event.EventData.CommandLine|re|i:"));
+    assert!(dir.join("sigma").join("b").join("rule.yml").exists());
+
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("lint").arg(dir.join("sigma")).arg("--kind").arg("sigma");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[+] Validated 2 detection rules out of 2"));
+
+    let sigma_path = Path::new(root).join("tests/evtx").join("sigma-unmapped.yml");
+    let mapping_path = Path::new(root).join("mappings").join("sigma-event-logs-legacy.yml");
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("convert").arg(sigma_path).arg("--kind").arg("sigma").arg("--mapping").arg(mapping_path).arg("-o").arg(dir.join("chainsaw"));
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[+] Converted 1 rules out of 1"));
+
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+    cmd.arg("lint").arg(dir.join("chainsaw")).arg("--kind").arg("chainsaw");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[+] Validated 1 detection rules out of 1"));
+
+    Ok(())
+}
+
+#[test]
+fn hunt_r_any_logon_dedup()-> Result<(), Box<dyn std::error::Error>> {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let sample_path = Path::new(root).join("tests/evtx").join("security_sample.evtx");
+    let rule_path = Path::new(root).join("tests/evtx").join("rule-any-logon.yml");
+    let dir = std::env::temp_dir().join("chainsaw-hunt-dedup");
+    let _ = std::fs::remove_dir_all(&dir);
+    let live_path = dir.join("live").join("Security.evtx");
+    let vss_path = dir.join("vss").join("Security.evtx");
+    for path in [&live_path, &vss_path] {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::copy(&sample_path, path)?;
+    }
+    let duplicates = format!("\"duplicates\":[\"{}\"]", vss_path.display());
+    let mut cmd = Command::cargo_bin("chainsaw")?;
+
+    cmd.arg("hunt").arg(&live_path).arg(&vss_path).arg("-r").arg(rule_path).arg("--dedup").arg("-jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::function(|s: &str| s.matches("\"name\":\"Any Logon\"").count() == 2))
+        .stdout(predicate::function(move |s: &str| s.matches(&duplicates).count() == 2));
+
+    Ok(())
+}
@@ -15,7 +15,7 @@ macro_rules! convert_sigma {
                 let rule = format!("sigma_{}.yml", $rule);
                 let root = env!("CARGO_MANIFEST_DIR");
                 let path = Path::new(root).join("tests/convert").join(&rule);
-                let rules = sigma::load(&path).unwrap();
+                let rules = sigma::load(&path, &sigma::Config::default()).unwrap().rules;
 
                 let output = format!("sigma_{}_output.yml", $rule);
                 let contents = common::load_file("tests/convert", &output).unwrap();
@@ -31,6 +31,7 @@ macro_rules! convert_sigma {
                     })
                     .collect();
                 println!("{}", yaml.len());
+                assert_eq!(yaml.len(), rules.len());
 
                 for (y, r) in yaml.iter().zip(rules.iter()) {
                     assert_eq!(y, r);
@@ -43,3 +44,4 @@ macro_rules! convert_sigma {
 
 convert_sigma!("simple");
 convert_sigma!("collection");
+convert_sigma!("repeat");